@@ -37,9 +37,97 @@ pub fn run(config: &config::Configuration) {
     # Event PUB socket bind.
     event_bind="{{ concentratord.api.event_bind }}"
 
-    # Command REP socket bind.
+    # Publish events with topic.
+    #
+    # When enabled, each event is published as a multipart message of which
+    # the first frame contains the topic. This makes it possible to use the
+    # ZeroMQ subscription filter. Topics:
+    #   * up/[modulation]/[frequency]: uplink (e.g. up/lora/868100000)
+    #   * stats: gateway stats
+    #   * ack: downlink tx result
+    #
+    # Note: subscribers that do not expect the topic frame will fail to decode
+    # the events.
+    event_topics={{ concentratord.api.event_topics }}
+
+    # Command socket bind.
     command_bind="{{ concentratord.api.command_bind }}"
 
+    # Command socket type.
+    #
+    # Valid options are:
+    #   * REP: handles one request at a time, in lock-step.
+    #   * ROUTER: handles clients independently. A client that does not read
+    #     its replies does not block other clients. Existing REQ clients are
+    #     supported. DEALER clients can prefix each request with a request ID
+    #     frame followed by an empty delimiter frame. These are returned with
+    #     the reply such that the client can match replies with requests.
+    command_socket_type="{{ concentratord.api.command_socket_type }}"
+
+    # Command timeout (ROUTER socket type only).
+    #
+    # When the reply can not be sent within this duration (e.g. because the
+    # client does not read its replies), the reply is discarded.
+    command_timeout="{{ concentratord.api.command_timeout }}"
+
+    # Metrics HTTP server bind (empty = disabled).
+    #
+    # When set, the (cumulative) gateway metrics are served in OpenMetrics
+    # format on /metrics, e.g. for scraping by Prometheus. Example:
+    #   metrics_bind="0.0.0.0:9100"
+    metrics_bind="{{ concentratord.api.metrics_bind }}"
+
+  # Packet capture configuration.
+  #
+  # When a file is configured, all received and sent packets are written to
+  # this file in PCAP format (LoRaTap link-type), such that it can be opened
+  # in Wireshark.
+  [concentratord.capture]
+    # Capture file (empty = disabled). Example:
+    #   file="/var/log/chirpstack-concentratord-2g4.pcap"
+    file="{{ concentratord.capture.file }}"
+
+    # Max. file size (bytes).
+    #
+    # When the capture file exceeds this size, it is rotated (e.g. file.pcap
+    # is renamed to file.pcap.1) and a new capture file is created. Set this
+    # to 0 to disable rotation.
+    max_file_size={{ concentratord.capture.max_file_size }}
+
+    # Max. number of capture files to keep (including the current file).
+    # When set to 1, the capture file is truncated instead of rotated.
+    max_files={{ concentratord.capture.max_files }}
+
+  # JIT (just-in-time) queue configuration.
+  #
+  # Downlinks are kept in the JIT queue until they must be handed to the
+  # concentrator. On slow hosts (e.g. SPI over USB or heavily loaded systems),
+  # downlinks might be dropped because they are too late. In this case, the
+  # tx_jit_delay can be increased. The max. latency between popping a downlink
+  # from the queue and handing it to the concentrator is exposed by the
+  # concentratord_jit_send_latency_max_seconds metric.
+  [concentratord.jit_queue]
+    # Max. number of downlinks in the queue.
+    capacity={{ concentratord.jit_queue.capacity }}
+
+    # Time needed by the concentrator to start the transmission.
+    tx_start_delay="{{ concentratord.jit_queue.tx_start_delay }}"
+
+    # Min. margin between two transmissions.
+    tx_margin_delay="{{ concentratord.jit_queue.tx_margin_delay }}"
+
+    # Time before the transmission at which the downlink is handed to the
+    # concentrator (in addition to the tx_start_delay).
+    tx_jit_delay="{{ concentratord.jit_queue.tx_jit_delay }}"
+
+    # Max. time in advance that a downlink can be enqueued.
+    tx_max_advance_delay="{{ concentratord.jit_queue.tx_max_advance_delay }}"
+
+    # Max. time in advance that an immediate downlink is scheduled. The first
+    # slot which does not collide with the queued downlinks and which is within
+    # the duty-cycle limits is used.
+    tx_immediate_max_delay="{{ concentratord.jit_queue.tx_immediate_max_delay }}"
+
 
 # LoRa gateway configuration.
 [gateway]
@@ -55,12 +143,27 @@ pub fn run(config: &config::Configuration) {
   # frequency and TX gain table.
   model="{{ gateway.model }}"
 
+  # Gateway model definition file.
+  #
+  # If set, the model configuration (device paths and pins) is loaded from
+  # this file instead of the built-in definition of the configured model. This can be used for custom boards, the built-in
+  # definitions can be used as a starting point.
+  model_file="{{ gateway.model_file }}"
+
   # Time fallback.
   #
   # In case the gateway does not have a GNSS module or is unable to aquire a
   # GNSS fix, use the system-time for setting the 'time' field on RX.
   time_fallback_enabled={{ gateway.time_fallback_enabled }}
 
+  # Configuration state file.
+  #
+  # If set, the channel configuration pushed by the network server (including
+  # the config version) is written to this file once it has been applied. On
+  # startup, the configuration from this file is used instead of the channel
+  # configuration from the configuration file.
+  config_state_file="{{ gateway.config_state_file }}"
+
   # Device and pin configuration.
   #
   # Only set this configuration if you need to override the default
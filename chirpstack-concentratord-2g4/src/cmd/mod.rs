@@ -1,2 +1,3 @@
 pub mod configfile;
 pub mod root;
+pub mod validate;
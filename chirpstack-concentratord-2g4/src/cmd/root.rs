@@ -1,36 +1,35 @@
 use std::collections::HashMap;
-use std::rc::Rc;
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Instant;
 
 use anyhow::Result;
+use chirpstack_api::gw;
+use libconcentratord::error::ConfigurationError;
 use libconcentratord::signals;
 use libconcentratord::signals::Signal;
-use libconcentratord::{commands, events, jitqueue, reset};
+use libconcentratord::{capture, commands, config_state, events, jitqueue, metrics, reset};
 use libloragw_2g4::hal;
 
 use super::super::{concentrator, config, handler, wrapper};
 
 pub fn run(
-    config: &config::Configuration,
+    config: &mut config::Configuration,
     stop_send: Sender<Signal>,
-    stop_receive: Rc<Receiver<Signal>>,
-) -> Result<Signal> {
+    stop_receive: Receiver<Signal>,
+) -> Result<()> {
     info!(
         "Starting Concentratord 2g4 (version: {}, docs: {})",
         config::VERSION,
         "https://www.chirpstack.io/docs/chirpstack-concentratord/"
     );
 
-    // reset concentrator
-    reset::reset().expect("concentrator reset failed");
+    // restore the configuration pushed by the network server
+    restore_config_state(config);
 
     // setup concentrator
-    concentrator::board_setconf(config)?;
-    concentrator::rx_setconf(config)?;
-    concentrator::tx_setconf(config)?;
-    concentrator::start()?;
+    start_concentrator(config)?;
 
     // setup static location
     handler::gps::set_static_gps_coords(
@@ -52,16 +51,244 @@ pub fn run(
     );
 
     // setup jit queue
-    let queue: jitqueue::Queue<wrapper::TxPacket> = jitqueue::Queue::new(32, None);
+    let queue: jitqueue::Queue<wrapper::TxPacket> =
+        jitqueue::Queue::new(&config.concentratord.jit_queue, None, None);
     let queue = Arc::new(Mutex::new(queue));
 
     // setup zeromq
-    events::bind_socket(&config.concentratord.api.event_bind).expect("bind event socket error");
-    let rep_sock = commands::get_socket(&config.concentratord.api.command_bind)
-        .expect("bind command socket error");
+    events::bind_socket(
+        &config.concentratord.api.event_bind,
+        config.concentratord.api.event_topics,
+    )
+    .expect("bind event socket error");
+    metrics::bind_http(&config.concentratord.api.metrics_bind)?;
+    capture::setup(
+        &config.concentratord.capture.file,
+        config.concentratord.capture.max_file_size,
+        config.concentratord.capture.max_files,
+        config.gateway.lorawan_public,
+        &gateway_id,
+    )?;
+    let rep_sock = commands::get_socket(
+        &config.concentratord.api.command_bind,
+        config.concentratord.api.command_socket_type,
+        config.concentratord.api.command_timeout,
+    )
+    .expect("bind command socket error");
+
+    // configuration used by the command thread, this is updated after the concentrator has
+    // been restarted with an updated configuration
+    let command_config = Arc::new(Mutex::new(config.clone()));
+
+    // command thread, this thread is kept running when the concentrator is restarted
+    // after a configuration update
+    let mut command_signal_pool = signals::SignalPool::default();
+    let command_thread = thread::spawn({
+        let lorawan_public = config.gateway.lorawan_public;
+        let queue = Arc::clone(&queue);
+        let config = Arc::clone(&command_config);
+        let stop_receive = command_signal_pool.new_receiver();
+        let stop_send = stop_send.clone();
+        let stop_send_err = stop_send.clone();
+
+        move || {
+            if let Err(e) = handler::command::handle_loop(
+                lorawan_public,
+                config,
+                &gateway_id,
+                queue,
+                rep_sock,
+                stop_receive,
+                stop_send,
+            ) {
+                error!("Command loop error: {}", e);
+                stop_send_err.send(Signal::Stop).unwrap();
+            }
+
+            debug!("Command loop ended");
+        }
+    });
+
+    let mut restart_error = None;
+
+    loop {
+        let mut signal_pool = signals::SignalPool::default();
+        let threads = spawn_threads(config, gateway_id, &queue, &stop_send, &mut signal_pool);
 
-    // setup threads
-    let mut signal_pool = signals::SignalPool::default();
+        let signal = wait_for_signal(config, &stop_receive);
+        signal_pool.send_signal(Signal::Stop);
+
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        let (pl, new_config, result_send) = match signal {
+            Some(v) => v,
+            None => break,
+        };
+
+        match restart_concentrator(config, new_config, &pl, &queue) {
+            Ok(result) => {
+                if result.is_ok() {
+                    *command_config.lock().unwrap() = config.clone();
+                }
+                let _ = result_send.send(result);
+            }
+            Err(e) => {
+                error!("Restart concentrator error, error: {}", e);
+                let _ = result_send.send(Err(ConfigurationError::Concentrator(e.to_string())));
+                restart_error = Some(e);
+                break;
+            }
+        }
+    }
+
+    // Configuration updates which are still pending are dropped, such that the command thread
+    // does not wait for their result.
+    drop(stop_receive);
+    command_signal_pool.send_signal(Signal::Stop);
+    command_thread.join().unwrap();
+
+    if let Some(e) = restart_error {
+        return Err(e);
+    }
+
+    concentrator::stop()?;
+
+    Ok(())
+}
+
+// Restart the concentrator with the updated configuration. Only the concentrator is
+// restarted, the sockets are kept and the queued downlinks are migrated to the new
+// concentrator counter. When the updated configuration can not be applied, the previous
+// configuration is restored. An error is returned when the concentrator can not be restarted
+// at all.
+fn restart_concentrator(
+    config: &mut config::Configuration,
+    new_config: config::Configuration,
+    pl: &gw::GatewayConfiguration,
+    queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+) -> Result<Result<(), ConfigurationError>> {
+    info!("Restarting concentrator with updated configuration");
+    let count_us = hal::get_instcnt()?;
+    let stopped_at = Instant::now();
+    concentrator::stop()?;
+
+    let result = match start_concentrator(&new_config) {
+        Ok(_) => {
+            *config = new_config;
+            save_config_state(config, pl);
+            Ok(())
+        }
+        Err(e) => {
+            error!(
+                "Start concentrator with updated configuration error, restoring previous configuration, error: {}",
+                e
+            );
+            let _ = concentrator::stop();
+            start_concentrator(config)?;
+            Err(ConfigurationError::Concentrator(e.to_string()))
+        }
+    };
+
+    queue
+        .lock()
+        .map_err(|_| anyhow!("Lock queue error"))?
+        .migrate(count_us, hal::get_instcnt()?, stopped_at.elapsed());
+
+    Ok(result)
+}
+
+fn start_concentrator(config: &config::Configuration) -> Result<()> {
+    // reset concentrator
+    reset::reset()?;
+
+    // setup concentrator
+    concentrator::board_setconf(config)?;
+    concentrator::rx_setconf(config)?;
+    concentrator::tx_setconf(config)?;
+    concentrator::start()
+}
+
+// Restore the configuration which was last pushed by the network server. When the state file
+// can not be used, the channel configuration from the configuration file is kept.
+fn restore_config_state(config: &mut config::Configuration) {
+    let path = config.gateway.config_state_file.clone();
+    if path.is_empty() {
+        return;
+    }
+
+    let pl = match config_state::load(&path) {
+        Ok(Some(v)) => v,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Load configuration state error, error: {}", e);
+            return;
+        }
+    };
+
+    match handler::config::validate_configuration(config, &pl) {
+        Ok(v) => {
+            info!(
+                "Configuration state restored, config_state_file: {}, config_version: {}",
+                path, v.gateway.config_version
+            );
+            *config = v;
+        }
+        Err(e) => warn!("Configuration state rejected, error: {}", e),
+    }
+}
+
+// Save the applied configuration, such that it can be restored on startup.
+fn save_config_state(config: &config::Configuration, pl: &gw::GatewayConfiguration) {
+    if config.gateway.config_state_file.is_empty() {
+        return;
+    }
+
+    if let Err(e) = config_state::save(&config.gateway.config_state_file, pl) {
+        error!("Save configuration state error, error: {}", e);
+    }
+}
+
+// Configuration update: the received gateway configuration, the validated configuration and
+// the sender for the result of applying it.
+type ConfigurationUpdate = (
+    gw::GatewayConfiguration,
+    config::Configuration,
+    Sender<Result<(), ConfigurationError>>,
+);
+
+// Wait for the stop signal or a configuration update. A configuration update is validated
+// before it is returned, an invalid update is rejected without interrupting the concentrator.
+fn wait_for_signal(
+    config: &config::Configuration,
+    stop_receive: &Receiver<Signal>,
+) -> Option<ConfigurationUpdate> {
+    loop {
+        match stop_receive.recv() {
+            Ok(Signal::Configuration(pl, result_send)) => {
+                let mut new_config = config.clone();
+                match handler::config::update_configuration(&mut new_config, &pl) {
+                    Ok(_) => return Some((pl, new_config, result_send)),
+                    Err(e) => {
+                        let _ = result_send.send(Err(e));
+                    }
+                }
+            }
+            Ok(Signal::Stop) | Err(_) => return None,
+        }
+    }
+}
+
+// Spawn the threads which interact with the concentrator. These are stopped and spawned
+// again when the concentrator is restarted.
+fn spawn_threads(
+    config: &config::Configuration,
+    gateway_id: [u8; 8],
+    queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+    stop_send: &Sender<Signal>,
+    signal_pool: &mut signals::SignalPool,
+) -> Vec<thread::JoinHandle<()>> {
     let mut threads: Vec<thread::JoinHandle<()>> = vec![];
 
     // uplink thread
@@ -88,13 +315,15 @@ pub fn run(
 
     // jit thread
     threads.push(thread::spawn({
-        let queue = Arc::clone(&queue);
+        let queue = Arc::clone(queue);
         let antenna_gain_dbi = config.gateway.antenna_gain;
         let stop_receive = signal_pool.new_receiver();
         let stop_send = stop_send.clone();
 
         move || {
-            if let Err(e) = handler::jit::jit_loop(queue, antenna_gain_dbi, stop_receive) {
+            if let Err(e) =
+                handler::jit::jit_loop(queue, &gateway_id, antenna_gain_dbi, stop_receive)
+            {
                 error!("JIT loop error: {}", e);
                 stop_send.send(Signal::Stop).unwrap();
             }
@@ -103,37 +332,10 @@ pub fn run(
         }
     }));
 
-    // command thread
-    threads.push(thread::spawn({
-        let queue = Arc::clone(&queue);
-        let vendor_config = config.gateway.model_config.clone();
-        let stop_receive = signal_pool.new_receiver();
-        let stop_send = stop_send.clone();
-        let stop_send_err = stop_send.clone();
-        let lorawan_public = config.gateway.lorawan_public;
-
-        move || {
-            if let Err(e) = handler::command::handle_loop(
-                lorawan_public,
-                &vendor_config,
-                &gateway_id,
-                queue,
-                rep_sock,
-                stop_receive,
-                stop_send,
-            ) {
-                error!("Command loop error: {}", e);
-                stop_send_err.send(Signal::Stop).unwrap();
-            }
-
-            debug!("Command loop ended");
-        }
-    }));
-
     // stats thead
     threads.push(thread::spawn({
         let stats_interval = config.concentratord.stats_interval;
-        let queue = Arc::clone(&queue);
+        let queue = Arc::clone(queue);
         let stop_receive = signal_pool.new_receiver();
         let stop_send = stop_send.clone();
         let mut metadata = HashMap::new();
@@ -164,14 +366,5 @@ pub fn run(
         }
     }));
 
-    let stop_signal = stop_receive.recv().unwrap();
-    signal_pool.send_signal(stop_signal.clone());
-
-    for t in threads {
-        t.join().unwrap();
-    }
-
-    concentrator::stop()?;
-
-    Ok(stop_signal)
+    threads
 }
@@ -0,0 +1,131 @@
+use libconcentratord::validate::Validator;
+
+use super::super::config;
+
+/// Validate the configuration files and print the problems found. This does not access the
+/// concentrator hardware. It returns the process exit code.
+pub fn run(filenames: &[String]) -> i32 {
+    let mut v = Validator::new(filenames);
+    if let Some(config) = v.parse::<config::Configuration>() {
+        validate(&mut v, config);
+    }
+    v.report()
+}
+
+fn validate(v: &mut Validator, mut config: config::Configuration) {
+    if config
+        .concentratord
+        .log_level
+        .parse::<log::Level>()
+        .is_err()
+    {
+        v.error("concentratord.log_level", "Invalid log level");
+    }
+
+    if !config.gateway.gateway_id.is_empty() {
+        match hex::decode(&config.gateway.gateway_id) {
+            Ok(b) if b.len() == 8 => {}
+            Ok(_) => v.error("gateway.gateway_id", "Must be exactly 8 bytes"),
+            Err(e) => v.error("gateway.gateway_id", e),
+        }
+    }
+
+    if let Err(e) = config.concentratord.jit_queue.validate() {
+        v.error("concentratord.jit_queue", e);
+    }
+
+    config.gateway.model_config = match config::vendor::new(&config) {
+        Ok(v) => v,
+        Err(e) => {
+            match config.gateway.model_file.is_empty() {
+                true => v.error("gateway.model", format!("{:#}", e)),
+                false => v.error("gateway.model_file", format!("{:#}", e)),
+            }
+            return;
+        }
+    };
+
+    validate_channels(v, &config);
+    validate_pins(v, &config);
+}
+
+fn validate_channels(v: &mut Validator, config: &config::Configuration) {
+    let tx_min_max_freqs = &config.gateway.model_config.tx_min_max_freqs;
+
+    for channel in config
+        .gateway
+        .concentrator
+        .channels
+        .iter()
+        .filter(|c| c.frequency != 0)
+    {
+        if !(5..=12).contains(&channel.spreading_factor) {
+            v.error(
+                "gateway.concentrator.channels",
+                format!(
+                    "Unsupported spreading-factor, freq: {}, spreading_factor: {}",
+                    channel.frequency, channel.spreading_factor
+                ),
+            );
+        }
+
+        let min = channel.frequency.saturating_sub(channel.bandwidth / 2);
+        let max = channel.frequency + (channel.bandwidth / 2);
+        if !tx_min_max_freqs.is_empty()
+            && !tx_min_max_freqs
+                .iter()
+                .any(|(tx_min, tx_max)| min >= *tx_min && max <= *tx_max)
+        {
+            v.error(
+                "gateway.concentrator.channels",
+                format!(
+                    "Channel is outside the TX frequency range, freq: {}",
+                    channel.frequency
+                ),
+            );
+        }
+    }
+}
+
+fn validate_pins(v: &mut Validator, config: &config::Configuration) {
+    let gw = &config.gateway;
+
+    let pins = [
+        (
+            "gateway.mcu_reset_pin",
+            &gw.model_config.reset_pin,
+            gw.mcu_reset_chip.is_some() || gw.mcu_reset_pin.is_some(),
+        ),
+        (
+            "gateway.mcu_boot0_pin",
+            &gw.model_config.boot0_pin,
+            gw.mcu_boot0_chip.is_some() || gw.mcu_boot0_pin.is_some(),
+        ),
+    ];
+
+    let mut used: Vec<(&str, &(String, u32))> = Vec::new();
+    for (key, pin, configured) in pins {
+        let pin = match pin {
+            Some(v) => v,
+            None => {
+                if configured {
+                    v.error(key, "Pin is not used by gateway model");
+                }
+                continue;
+            }
+        };
+
+        if !pin.0.starts_with("/dev/") {
+            v.error(key, format!("Invalid GPIO chip: {}", pin.0));
+        }
+
+        if let Some((other, _)) = used.iter().find(|(_, p)| *p == pin) {
+            v.error(
+                key,
+                format!("GPIO {}:{} is already used by {}", pin.0, pin.1, other),
+            );
+        }
+
+        used.push((key, pin));
+    }
+}
@@ -1,20 +1,23 @@
-use std::{env, fs};
-
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
+use libconcentratord::commands;
+use libconcentratord::config_file;
+use libconcentratord::error::ConfigFileError;
+use libconcentratord::jitqueue;
+
 pub mod vendor;
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize, Clone)]
 #[serde(default = "example_configuration")]
 pub struct Configuration {
     pub concentratord: Concentratord,
     pub gateway: Gateway,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct Concentratord {
     pub log_level: String,
@@ -23,6 +26,11 @@ pub struct Concentratord {
     pub stats_interval: Duration,
     pub disable_crc_filter: bool,
     pub api: Api,
+    pub capture: Capture,
+    pub jit_queue: jitqueue::Configuration,
+
+    #[serde(skip)]
+    pub log_level_filter: log::LevelFilter,
 }
 
 impl Default for Concentratord {
@@ -33,35 +41,67 @@ impl Default for Concentratord {
             stats_interval: Duration::from_secs(30),
             disable_crc_filter: false,
             api: Default::default(),
+            capture: Default::default(),
+            jit_queue: Default::default(),
+            log_level_filter: log::LevelFilter::Info,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Capture {
+    pub file: String,
+    pub max_file_size: u64,
+    pub max_files: usize,
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Capture {
+            file: "".into(),
+            max_file_size: 10 * 1024 * 1024,
+            max_files: 5,
         }
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct Api {
     pub event_bind: String,
+    pub event_topics: bool,
     pub command_bind: String,
+    pub command_socket_type: commands::SocketType,
+    #[serde(with = "humantime_serde")]
+    pub command_timeout: Duration,
+    pub metrics_bind: String,
 }
 
 impl Default for Api {
     fn default() -> Self {
         Api {
             event_bind: "ipc:///tmp/concentratord_event".to_string(),
+            event_topics: false,
             command_bind: "ipc:///tmp/concentratord_command".to_string(),
+            command_socket_type: commands::SocketType::Rep,
+            command_timeout: Duration::from_secs(1),
+            metrics_bind: "".to_string(),
         }
     }
 }
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct Gateway {
     pub gateway_id: String,
     pub antenna_gain: i8,
     pub lorawan_public: bool,
     pub model: String,
+    pub model_file: String,
     pub model_flags: Vec<String>,
     pub time_fallback_enabled: bool,
+    pub config_state_file: String,
     pub concentrator: Concentrator,
     pub location: Location,
 
@@ -105,12 +145,12 @@ impl Gateway {
     }
 }
 
-#[derive(Default, Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Default, Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Concentrator {
     pub channels: [Channel; 3],
 }
 
-#[derive(Default, Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Default, Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(default)]
 pub struct Channel {
     pub frequency: u32,
@@ -119,7 +159,7 @@ pub struct Channel {
     pub rssi_offset: f32,
 }
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct Location {
     pub latitude: f64,
@@ -160,39 +200,42 @@ fn example_configuration() -> Configuration {
     }
 }
 
-pub fn get(filenames: Vec<String>) -> Configuration {
-    let mut content: String = String::new();
-
-    for file_name in &filenames {
-        content.push_str(&fs::read_to_string(file_name).expect("Error reading config file"));
-    }
-
-    // Replace environment variables in config.
-    for (k, v) in env::vars() {
-        content = content.replace(&format!("${}", k), &v);
-    }
+pub fn get(filenames: Vec<String>) -> Result<Configuration, ConfigFileError> {
+    let mut config: Configuration = config_file::load(&filenames)?;
 
-    let mut config: Configuration = toml::from_str(&content).expect("Error parsing config file");
+    // parse log level
+    config.concentratord.log_level_filter = config
+        .concentratord
+        .log_level
+        .parse::<log::Level>()
+        .map_err(|_| ConfigFileError::InvalidLogLevel(config.concentratord.log_level.clone()))?
+        .to_level_filter();
 
     // decode gateway id
     if !config.gateway.gateway_id.is_empty() {
-        let bytes = hex::decode(&config.gateway.gateway_id).expect("Could not decode gateway_id");
+        let bytes = hex::decode(&config.gateway.gateway_id)
+            .map_err(|e| ConfigFileError::InvalidGatewayId(e.to_string()))?;
         if bytes.len() != 8 {
-            panic!("gateway_id must be exactly 8 bytes");
+            return Err(ConfigFileError::InvalidGatewayId(
+                "Must be exactly 8 bytes".into(),
+            ));
         }
         let id = bytes.as_slice();
         config.gateway.gateway_id_bytes = Some(id[0..8].try_into().unwrap());
     }
 
+    // validate the JIT queue configuration
+    config
+        .concentratord
+        .jit_queue
+        .validate()
+        .map_err(|e| ConfigFileError::JitQueue(e.to_string()))?;
+
     // get model configuration
-    config.gateway.model_config = match config.gateway.model.as_ref() {
-        "multitech_mtac_lora_2g4" => vendor::multitech::mtac_lora_2g4::new(&config),
-        "rak_5148" => vendor::rak::rak5148::new(&config),
-        "semtech_sx1280z3dsfgw1" => vendor::semtech::sx1280z3dsfgw1::new(&config),
-        _ => panic!("unexpected gateway model: {}", config.gateway.model),
-    };
+    config.gateway.model_config =
+        vendor::new(&config).map_err(|e| ConfigFileError::Model(format!("{:#}", e)))?;
 
     debug!("Antenna gain {} dBi", config.gateway.antenna_gain);
 
-    config
+    Ok(config)
 }
@@ -1,18 +1,42 @@
-use anyhow::Result;
+use libconcentratord::error::ConfigurationError;
 
 use super::super::config::{Channel, Concentrator, Configuration};
 
+/// Validate the gateway configuration. On success, this returns a copy of the given
+/// configuration with the gateway configuration applied. The given configuration is not
+/// modified.
+pub fn validate_configuration(
+    config: &Configuration,
+    new_config: &chirpstack_api::gw::GatewayConfiguration,
+) -> Result<Configuration, ConfigurationError> {
+    let mut config = config.clone();
+    update_configuration(&mut config, new_config)?;
+
+    for channel in config.gateway.concentrator.channels.iter() {
+        if channel.frequency != 0 && !(5..=12).contains(&channel.spreading_factor) {
+            return Err(ConfigurationError::InvalidChannel(
+                channel.frequency,
+                "unsupported spreading-factor".to_string(),
+            ));
+        }
+    }
+
+    Ok(config)
+}
+
 pub fn update_configuration(
     config: &mut Configuration,
     new_config: &chirpstack_api::gw::GatewayConfiguration,
-) -> Result<() > {
+) -> Result<(), ConfigurationError> {
     info!("Updating concentrator configuration");
 
     // empty concentrator config
     let mut concentrator = Concentrator::default();
 
     if new_config.channels.len() > concentrator.channels.len() {
-        return Err(anyhow!("configuration exceeds max number of channels"));
+        return Err(ConfigurationError::TooManyChannels(
+            concentrator.channels.len(),
+        ));
     }
 
     for (i, channel) in new_config.channels.iter().enumerate() {
@@ -23,8 +47,10 @@ pub fn update_configuration(
                 ),
             ) => {
                 if v.spreading_factors.len() != 1 {
-                    return Err(
-                        anyhow!("spreading_factors must contain a single spreading-factor"));
+                    return Err(ConfigurationError::InvalidChannel(
+                        channel.frequency,
+                        "spreading_factors must contain a single spreading-factor".to_string(),
+                    ));
                 }
 
                 concentrator.channels[i] = Channel {
@@ -35,14 +61,19 @@ pub fn update_configuration(
                 };
             }
             _ => {
-                return Err(anyhow!("channel modulation must be LORA"));
+                return Err(ConfigurationError::InvalidChannel(
+                    channel.frequency,
+                    "channel modulation must be LORA".to_string(),
+                ));
             }
         }
     }
 
     // set config
     if let Some(stats_interval) = new_config.stats_interval {
-        config.concentratord.stats_interval = stats_interval.try_into()?;
+        config.concentratord.stats_interval = stats_interval
+            .try_into()
+            .map_err(|_| ConfigurationError::InvalidStatsInterval)?;
     }
     config.gateway.config_version.clone_from(&new_config.version);
     config.gateway.concentrator = concentrator;
@@ -6,7 +6,7 @@ use anyhow::{Context, Result};
 
 use chirpstack_api::gw::DutyCycleStats;
 use libconcentratord::signals::Signal;
-use libconcentratord::{jitqueue, stats};
+use libconcentratord::{jitqueue, metrics, stats};
 use libloragw_2g4::hal;
 
 use super::gps;
@@ -38,6 +38,20 @@ pub fn stats_loop(
             ..Default::default()
         });
 
+        // update the metrics gauges
+        metrics::set_jit_queue_size(
+            queue
+                .lock()
+                .map_err(|_| anyhow!("Queue lock error"))?
+                .len(),
+        );
+        metrics::set_jit_send_latency_max(
+            queue
+                .lock()
+                .map_err(|_| anyhow!("Queue lock error"))?
+                .take_send_latency_max(),
+        );
+
         let dc_stats = get_duty_cycle_stats(&queue)?;
         stats::send_and_reset(gateway_id, loc, dc_stats, metadata).context("Send stats")?;
     }
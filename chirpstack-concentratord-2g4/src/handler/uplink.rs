@@ -5,7 +5,7 @@ use std::time::Duration;
 use anyhow::{Context, Result};
 
 use libconcentratord::signals::Signal;
-use libconcentratord::{events, stats};
+use libconcentratord::{capture, events, stats};
 use libloragw_2g4::hal;
 
 use crate::wrapper;
@@ -29,6 +29,10 @@ pub fn handle_loop(
                 for frame in frames {
                     stats::inc_rx_packets_received();
 
+                    if let Err(e) = capture::capture(&wrapper::uplink_to_capture(&frame)) {
+                        error!("Capture uplink error, error: {}", e);
+                    }
+
                     if !disable_crc_filter && frame.status != hal::CRC::CRCOk {
                         debug!(
                             "Frame received with invalid CRC, see disable_crc_filter configuration option if you want to receive these frames"
@@ -60,7 +64,7 @@ pub fn handle_loop(
                     );
 
                     if frame.status == hal::CRC::CRCOk {
-                        stats::inc_rx_counts(&proto);
+                        stats::inc_rx_counts(&proto, Some(frame.freq_offset_hz));
                     }
                     events::send_uplink(proto).context("Send uplink")?;
                 }
@@ -37,14 +37,105 @@ pub fn run(config: &config::Configuration) {
     # Event PUB socket bind.
     event_bind="{{ concentratord.api.event_bind }}"
 
-    # Command REP socket bind.
+    # Publish events with topic.
+    #
+    # When enabled, each event is published as a multipart message of which
+    # the first frame contains the topic. This makes it possible to use the
+    # ZeroMQ subscription filter. Topics:
+    #   * up/[modulation]/[frequency]: uplink (e.g. up/lora/868100000)
+    #   * stats: gateway stats
+    #   * ack: downlink tx result
+    #
+    # Note: subscribers that do not expect the topic frame will fail to decode
+    # the events.
+    event_topics={{ concentratord.api.event_topics }}
+
+    # Command socket bind.
     command_bind="{{ concentratord.api.command_bind }}"
 
+    # Command socket type.
+    #
+    # Valid options are:
+    #   * REP: handles one request at a time, in lock-step.
+    #   * ROUTER: handles clients independently. A client that does not read
+    #     its replies does not block other clients. Existing REQ clients are
+    #     supported. DEALER clients can prefix each request with a request ID
+    #     frame followed by an empty delimiter frame. These are returned with
+    #     the reply such that the client can match replies with requests.
+    command_socket_type="{{ concentratord.api.command_socket_type }}"
+
+    # Command timeout (ROUTER socket type only).
+    #
+    # When the reply can not be sent within this duration (e.g. because the
+    # client does not read its replies), the reply is discarded.
+    command_timeout="{{ concentratord.api.command_timeout }}"
+
+    # Metrics HTTP server bind (empty = disabled).
+    #
+    # When set, the (cumulative) gateway metrics are served in OpenMetrics
+    # format on /metrics, e.g. for scraping by Prometheus. Example:
+    #   metrics_bind="0.0.0.0:9100"
+    metrics_bind="{{ concentratord.api.metrics_bind }}"
+
+  # Packet capture configuration.
+  #
+  # When a file is configured, all received and sent packets are written to
+  # this file in PCAP format (LoRaTap link-type), such that it can be opened
+  # in Wireshark.
+  [concentratord.capture]
+    # Capture file (empty = disabled). Example:
+    #   file="/var/log/chirpstack-concentratord-sx1301.pcap"
+    file="{{ concentratord.capture.file }}"
+
+    # Max. file size (bytes).
+    #
+    # When the capture file exceeds this size, it is rotated (e.g. file.pcap
+    # is renamed to file.pcap.1) and a new capture file is created. Set this
+    # to 0 to disable rotation.
+    max_file_size={{ concentratord.capture.max_file_size }}
+
+    # Max. number of capture files to keep (including the current file).
+    # When set to 1, the capture file is truncated instead of rotated.
+    max_files={{ concentratord.capture.max_files }}
+
+  # JIT (just-in-time) queue configuration.
+  #
+  # Downlinks are kept in the JIT queue until they must be handed to the
+  # concentrator. On slow hosts (e.g. SPI over USB or heavily loaded systems),
+  # downlinks might be dropped because they are too late. In this case, the
+  # tx_jit_delay can be increased. The max. latency between popping a downlink
+  # from the queue and handing it to the concentrator is exposed by the
+  # concentratord_jit_send_latency_max_seconds metric.
+  [concentratord.jit_queue]
+    # Max. number of downlinks in the queue.
+    capacity={{ concentratord.jit_queue.capacity }}
+
+    # Time needed by the concentrator to start the transmission.
+    tx_start_delay="{{ concentratord.jit_queue.tx_start_delay }}"
+
+    # Min. margin between two transmissions.
+    tx_margin_delay="{{ concentratord.jit_queue.tx_margin_delay }}"
+
+    # Time before the transmission at which the downlink is handed to the
+    # concentrator (in addition to the tx_start_delay).
+    tx_jit_delay="{{ concentratord.jit_queue.tx_jit_delay }}"
+
+    # Max. time in advance that a downlink can be enqueued.
+    tx_max_advance_delay="{{ concentratord.jit_queue.tx_max_advance_delay }}"
+
+    # Max. time in advance that an immediate downlink is scheduled. The first
+    # slot which does not collide with the queued downlinks and which is within
+    # the duty-cycle limits is used.
+    tx_immediate_max_delay="{{ concentratord.jit_queue.tx_immediate_max_delay }}"
+
 
 # LoRa gateway configuration.
 [gateway]
 
   # Antenna gain (dBi).
+  #
+  # Some gateway models use a TX gain table for their antenna gain, e.g. the
+  # wifx_lorix_one supports 2 (indoor) and 4 (outdoor) dBi.
   antenna_gain={{ gateway.antenna_gain }}
 
   # Public LoRaWAN network.
@@ -59,11 +150,26 @@ pub fn run(config: &config::Configuration) {
   # Not not all the gateway models implement all regions.
   region="{{ gateway.region }}"
 
+  # Sub-band (US915, AU915 and CN470).
+  #
+  # When the [gateway.concentrator] channels are not configured, these are
+  # generated from the region channel plan. Sub-bands are numbered from 0,
+  # e.g. sub-band 1 of US915 holds channels 8 - 15 and 65.
+  # sub_band=1
+
   # Gateway vendor / model.
   #
   # This configures various vendor and model specific settings.
   model="{{ gateway.model }}"
 
+  # Gateway model definition file.
+  #
+  # If set, the model configuration (radios, TX gain tables, device paths and
+  # pins) is loaded from this file instead of the built-in definition of the
+  # configured model. This can be used for custom boards, the built-in
+  # definitions can be used as a starting point.
+  model_file="{{ gateway.model_file }}"
+
   # Gateway vendor / model flags.
   #
   # Flag can be used to configure additional vendor / model features. The
@@ -86,6 +192,14 @@ pub fn run(config: &config::Configuration) {
   # GNSS fix, use the system-time for setting the 'time' field on RX.
   time_fallback_enabled={{ gateway.time_fallback_enabled }}
 
+  # Configuration state file.
+  #
+  # If set, the channel configuration pushed by the network server (including
+  # the config version) is written to this file once it has been applied. On
+  # startup, the configuration from this file is used instead of the channel
+  # configuration from the configuration file.
+  config_state_file="{{ gateway.config_state_file }}"
+
   # Device and pin configuration.
   #
   # Only set this configuration if you need to override the default
@@ -155,6 +269,97 @@ pub fn run(config: &config::Configuration) {
     latitude={{ gateway.location.latitude }}
     longitude={{ gateway.location.longitude }}
     altitude={{ gateway.location.altitude }}
+
+  # Regulation (duty-cycle) configuration.
+  #
+  # By default, the regulation standard is derived from the region:
+  #   EU868, EU433 - ETSI_EN_300_220
+  #   RU864        - SCRF_07_20_03_001
+  #   IN865        - WPC_GSR_1048
+  #
+  # For other regions, no duty-cycle is tracked unless a standard or a custom
+  # band table is configured. Whether the duty-cycle is enforced depends on the
+  # gateway model.
+  [gateway.regulation]
+
+    # Regulation standard.
+    #
+    # Use this to override the standard derived from the region. Options:
+    #   ETSI_EN_300_220, SCRF_07_20_03_001, WPC_GSR_1048, ARIB_STD_T108
+    # standard="ARIB_STD_T108"
+
+    # Duty-cycle window (only used for the custom band table).
+    window_time="{{ gateway.regulation.window_time }}"
+
+    # # Custom band table (can be repeated).
+    # #
+    # # When set, this band table is used instead of the band table of the
+    # # regulation standard.
+    # [[gateway.regulation.bands]]
+    #   label="K"
+    #   frequency_min=863000000
+    #   frequency_max=865000000
+    #   duty_cycle_permille_max=1
+    #   tx_power_max_eirp=16
+
+    {{#each gateway.regulation.bands}}
+    [[gateway.regulation.bands]]
+      label="{{this.label}}"
+      frequency_min={{this.frequency_min}}
+      frequency_max={{this.frequency_max}}
+      duty_cycle_permille_max={{this.duty_cycle_permille_max}}
+      tx_power_max_eirp={{this.tx_power_max_eirp}}
+
+    {{/each}}
+
+    # Duty-cycle state file.
+    #
+    # When set, the tracked duty-cycle is periodically (every stats interval)
+    # saved to this file, and when Concentratord stops or restarts after a
+    # configuration update. On start, it is restored from this file such that
+    # a restart does not reset the tracked duty-cycle. Example:
+    #   state_file="/var/lib/chirpstack-concentratord-sx1301/duty_cycle.json"
+    state_file="{{ gateway.regulation.state_file }}"
+
+    # TX power policy.
+    #
+    # This defines what happens with downlinks requesting a TX power (EIRP)
+    # which exceeds the max. EIRP of the band. Options:
+    #   REJECT - Reject the downlink with the TX_POWER status
+    #   CLAMP  - Clamp the TX power to the max. EIRP of the band
+    #
+    # The applied TX power is reported in the downlink TX acknowledgement.
+    tx_power_policy="{{ gateway.regulation.tx_power_policy }}"
+
+    # Enforce dwell-time.
+    #
+    # When enabled, downlinks of which the time-on-air exceeds the max. dwell-time
+    # are rejected with the DWELL_TIME status. This is disabled by default. For
+    # AS923, it should only be enabled when the network server is configured with
+    # the downlink dwell-time setting. By default, the dwell-time limits are
+    # derived from the region:
+    #   US915                             - 400ms for 125 kHz channels
+    #   AS923, AS923_2, AS923_3, AS923_4 - 400ms
+    enforce_dwell_time={{ gateway.regulation.enforce_dwell_time }}
+
+    # # Custom dwell-time limits (can be repeated).
+    # #
+    # # When set, these limits are used instead of the limits derived from the
+    # # region. When bandwidth is set to 0, the limit applies to all bandwidths.
+    # [[gateway.regulation.dwell_time_limits]]
+    #   frequency_min=902000000
+    #   frequency_max=928000000
+    #   bandwidth=125000
+    #   dwell_time_max="400ms"
+
+    {{#each gateway.regulation.dwell_time_limits}}
+    [[gateway.regulation.dwell_time_limits]]
+      frequency_min={{this.frequency_min}}
+      frequency_max={{this.frequency_max}}
+      bandwidth={{this.bandwidth}}
+      dwell_time_max="{{this.dwell_time_max}}"
+
+    {{/each}}
 "#;
 
     let reg = Handlebars::new();
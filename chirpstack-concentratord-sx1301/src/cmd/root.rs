@@ -1,38 +1,36 @@
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::path::Path;
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Instant;
 
 use anyhow::Result;
+use chirpstack_api::gw;
+use libconcentratord::error::ConfigurationError;
 use libconcentratord::signals;
 use libconcentratord::signals::Signal;
-use libconcentratord::{commands, events, gnss, jitqueue, reset};
+use libconcentratord::{capture, commands, config_state, events, gnss, jitqueue, metrics, reset};
 use libloragw_sx1301::hal;
 
 use super::super::{concentrator, config, handler, wrapper};
 
 pub fn run(
-    config: &config::Configuration,
+    config: &mut config::Configuration,
     stop_send: Sender<Signal>,
-    stop_receive: Rc<Receiver<Signal>>,
-) -> Result<Signal> {
+    stop_receive: Receiver<Signal>,
+) -> Result<()> {
     info!(
         "Starting Concentratord SX1301 (version: {}, docs: {})",
         config::VERSION,
         "https://www.chirpstack.io/docs/chirpstack-concentratord/"
     );
 
-    // reset concentrator
-    reset::reset().expect("concentrator reset failed");
+    // restore the configuration pushed by the network server
+    restore_config_state(config);
 
     // setup concentrator
-    concentrator::set_spidev_path(config)?;
-    concentrator::board_setconf(config)?;
-    concentrator::txgain_setconf(config)?;
-    concentrator::rxrf_setconf(config)?;
-    concentrator::rxif_setconf(config)?;
-    concentrator::start(config)?;
+    start_concentrator(config)?;
 
     // setup static location
     gnss::set_static_location(
@@ -42,17 +40,295 @@ pub fn run(
     );
 
     // setup sockets
-    events::bind_socket(&config.concentratord.api.event_bind).expect("bind event socket error");
-    let rep_sock = commands::get_socket(&config.concentratord.api.command_bind)
-        .expect("bind command socket error");
+    events::bind_socket(
+        &config.concentratord.api.event_bind,
+        config.concentratord.api.event_topics,
+    )
+    .expect("bind event socket error");
+    metrics::bind_http(&config.concentratord.api.metrics_bind)?;
+    capture::setup(
+        &config.concentratord.capture.file,
+        config.concentratord.capture.max_file_size,
+        config.concentratord.capture.max_files,
+        config.gateway.lorawan_public,
+        &config.gateway.gateway_id_bytes,
+    )?;
+    let rep_sock = commands::get_socket(
+        &config.concentratord.api.command_bind,
+        config.concentratord.api.command_socket_type,
+        config.concentratord.api.command_timeout,
+    )
+    .expect("bind command socket error");
 
     // setup jit queue
-    let queue: jitqueue::Queue<wrapper::TxPacket> =
-        jitqueue::Queue::new(32, config.get_duty_cycle_tracker());
+    let mut queue: jitqueue::Queue<wrapper::TxPacket> = jitqueue::Queue::new(
+        &config.concentratord.jit_queue,
+        config.get_duty_cycle_tracker()?,
+        config.get_dwell_time(),
+    );
+
+    // restore duty-cycle state
+    let state_file = &config.gateway.regulation.state_file;
+    if !state_file.is_empty() && Path::new(state_file).exists() {
+        info!("Restoring duty-cycle state, state_file: {}", state_file);
+        if let Err(e) =
+            queue.load_duty_cycle_state(handler::timersync::get_concentrator_count(), state_file)
+        {
+            warn!("Restore duty-cycle state error, error: {}", e);
+        }
+    }
+
     let queue = Arc::new(Mutex::new(queue));
 
-    // setup threads
-    let mut signal_pool = signals::SignalPool::default();
+    // configuration used by the command thread, this is updated after the concentrator has
+    // been restarted with an updated configuration
+    let command_config = Arc::new(Mutex::new(config.clone()));
+
+    // gateway command thread, this thread is kept running when the concentrator is restarted
+    // after a configuration update
+    let mut command_signal_pool = signals::SignalPool::default();
+    let command_thread = thread::spawn({
+        let gateway_id = config.gateway.gateway_id_bytes.clone();
+        let config = Arc::clone(&command_config);
+        let queue = Arc::clone(&queue);
+        let stop_receive = command_signal_pool.new_receiver();
+        let stop_send = stop_send.clone();
+        let stop_send_err = stop_send.clone();
+
+        move || {
+            if let Err(e) = handler::command::handle_loop(
+                config,
+                &gateway_id,
+                queue,
+                rep_sock,
+                stop_receive,
+                stop_send,
+            ) {
+                error!("Command handler loop error: {}", e);
+                stop_send_err.send(Signal::Stop).unwrap();
+            }
+
+            debug!("Command handler lopp ended");
+        }
+    });
+
+    let mut restart_error = None;
+
+    loop {
+        let mut signal_pool = signals::SignalPool::default();
+        let threads = spawn_threads(config, &queue, &stop_send, &mut signal_pool);
+
+        let signal = wait_for_signal(config, &stop_receive);
+        signal_pool.send_signal(Signal::Stop);
+
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        let (pl, new_config, result_send) = match signal {
+            Some(v) => v,
+            None => break,
+        };
+
+        match restart_concentrator(config, new_config, &pl, &queue) {
+            Ok(result) => {
+                if result.is_ok() {
+                    *command_config.lock().unwrap() = config.clone();
+                }
+                let _ = result_send.send(result);
+            }
+            Err(e) => {
+                error!("Restart concentrator error, error: {}", e);
+                let _ = result_send.send(Err(ConfigurationError::Concentrator(e.to_string())));
+                restart_error = Some(e);
+                break;
+            }
+        }
+    }
+
+    // Configuration updates which are still pending are dropped, such that the command thread
+    // does not wait for their result.
+    drop(stop_receive);
+    command_signal_pool.send_signal(Signal::Stop);
+    command_thread.join().unwrap();
+
+    // The duty-cycle state has already been saved when the concentrator could not be
+    // restarted, as the concentrator is not running at this point.
+    if let Some(e) = restart_error {
+        return Err(e);
+    }
+
+    // save duty-cycle state
+    if let Err(e) = save_duty_cycle_state(config, &queue) {
+        error!("Save duty-cycle state error, error: {}", e);
+    }
+
+    concentrator::stop(config)?;
+
+    Ok(())
+}
+
+// Restart the concentrator with the updated configuration. Only the concentrator is
+// restarted, the sockets are kept and the queued downlinks are migrated to the new
+// concentrator counter. When the updated configuration can not be applied, the previous
+// configuration is restored. An error is returned when the concentrator can not be restarted
+// at all.
+fn restart_concentrator(
+    config: &mut config::Configuration,
+    new_config: config::Configuration,
+    pl: &gw::GatewayConfiguration,
+    queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+) -> Result<Result<(), ConfigurationError>> {
+    info!("Restarting concentrator with updated configuration");
+
+    // Save the duty-cycle state while the concentrator is still running, as the state can not
+    // be saved afterwards when the concentrator can not be restarted.
+    if let Err(e) = save_duty_cycle_state(config, queue) {
+        error!("Save duty-cycle state error, error: {}", e);
+    }
+
+    let count_us = handler::timersync::get_concentrator_count();
+    let stopped_at = Instant::now();
+    concentrator::stop(config)?;
+
+    let result = match start_concentrator(&new_config) {
+        Ok(_) => {
+            *config = new_config;
+            save_config_state(config, pl);
+            Ok(())
+        }
+        Err(e) => {
+            error!(
+                "Start concentrator with updated configuration error, restoring previous configuration, error: {}",
+                e
+            );
+            let _ = concentrator::stop(config);
+            start_concentrator(config)?;
+            Err(ConfigurationError::Concentrator(e.to_string()))
+        }
+    };
+
+    // re-sync the concentrator counter, as it was reset by the restart
+    handler::timersync::timesync()?;
+
+    queue
+        .lock()
+        .map_err(|_| anyhow!("Lock queue error"))?
+        .migrate(
+            count_us,
+            handler::timersync::get_concentrator_count(),
+            stopped_at.elapsed(),
+        );
+
+    Ok(result)
+}
+
+fn save_duty_cycle_state(
+    config: &config::Configuration,
+    queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+) -> Result<()> {
+    let state_file = &config.gateway.regulation.state_file;
+    if state_file.is_empty() {
+        return Ok(());
+    }
+
+    queue
+        .lock()
+        .map_err(|_| anyhow!("Lock queue error"))?
+        .save_duty_cycle_state(handler::timersync::get_concentrator_count(), state_file)
+}
+
+fn start_concentrator(config: &config::Configuration) -> Result<()> {
+    // reset concentrator
+    reset::reset()?;
+
+    // setup concentrator
+    concentrator::set_spidev_path(config)?;
+    concentrator::board_setconf(config)?;
+    concentrator::txgain_setconf(config)?;
+    concentrator::rxrf_setconf(config)?;
+    concentrator::rxif_setconf(config)?;
+    concentrator::start(config)
+}
+
+// Restore the configuration which was last pushed by the network server. When the state file
+// can not be used, the channel configuration from the configuration file is kept.
+fn restore_config_state(config: &mut config::Configuration) {
+    let path = config.gateway.config_state_file.clone();
+    if path.is_empty() {
+        return;
+    }
+
+    let pl = match config_state::load(&path) {
+        Ok(Some(v)) => v,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Load configuration state error, error: {}", e);
+            return;
+        }
+    };
+
+    match handler::config::validate_configuration(config, &pl) {
+        Ok(v) => {
+            info!(
+                "Configuration state restored, config_state_file: {}, config_version: {}",
+                path, v.gateway.config_version
+            );
+            *config = v;
+        }
+        Err(e) => warn!("Configuration state rejected, error: {}", e),
+    }
+}
+
+// Save the applied configuration, such that it can be restored on startup.
+fn save_config_state(config: &config::Configuration, pl: &gw::GatewayConfiguration) {
+    if config.gateway.config_state_file.is_empty() {
+        return;
+    }
+
+    if let Err(e) = config_state::save(&config.gateway.config_state_file, pl) {
+        error!("Save configuration state error, error: {}", e);
+    }
+}
+
+// Configuration update: the received gateway configuration, the validated configuration and
+// the sender for the result of applying it.
+type ConfigurationUpdate = (
+    gw::GatewayConfiguration,
+    config::Configuration,
+    Sender<Result<(), ConfigurationError>>,
+);
+
+// Wait for the stop signal or a configuration update. A configuration update is validated
+// before it is returned, an invalid update is rejected without interrupting the concentrator.
+fn wait_for_signal(
+    config: &config::Configuration,
+    stop_receive: &Receiver<Signal>,
+) -> Option<ConfigurationUpdate> {
+    loop {
+        match stop_receive.recv() {
+            Ok(Signal::Configuration(pl, result_send)) => {
+                let mut new_config = config.clone();
+                match handler::config::update_configuration(&mut new_config, &pl) {
+                    Ok(_) => return Some((pl, new_config, result_send)),
+                    Err(e) => {
+                        let _ = result_send.send(Err(e));
+                    }
+                }
+            }
+            Ok(Signal::Stop) | Err(_) => return None,
+        }
+    }
+}
+
+// Spawn the threads which interact with the concentrator. These are stopped and spawned
+// again when the concentrator is restarted.
+fn spawn_threads(
+    config: &config::Configuration,
+    queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+    stop_send: &Sender<Signal>,
+    signal_pool: &mut signals::SignalPool,
+) -> Vec<thread::JoinHandle<()>> {
     let mut threads: Vec<thread::JoinHandle<()>> = vec![];
 
     // uplink thread
@@ -95,13 +371,16 @@ pub fn run(
 
     // jit thread
     threads.push(thread::spawn({
-        let queue = Arc::clone(&queue);
+        let queue = Arc::clone(queue);
+        let gateway_id = config.gateway.gateway_id_bytes.clone();
         let antenna_gain_dbi = config.gateway.antenna_gain;
         let stop_receive = signal_pool.new_receiver();
         let stop_send = stop_send.clone();
 
         move || {
-            if let Err(e) = handler::jit::jit_loop(queue, antenna_gain_dbi, stop_receive) {
+            if let Err(e) =
+                handler::jit::jit_loop(queue, &gateway_id, antenna_gain_dbi, stop_receive)
+            {
                 error!("JIT loop error: {}", e);
                 stop_send.send(Signal::Stop).unwrap();
             }
@@ -110,37 +389,12 @@ pub fn run(
         }
     }));
 
-    // gateway command thread
-    threads.push(thread::spawn({
-        let vendor_config = config.gateway.model_config.clone();
-        let gateway_id = config.gateway.gateway_id_bytes.clone();
-        let queue = Arc::clone(&queue);
-        let stop_receive = signal_pool.new_receiver();
-        let stop_send = stop_send.clone();
-        let stop_send_err = stop_send.clone();
-
-        move || {
-            if let Err(e) = handler::command::handle_loop(
-                &vendor_config,
-                &gateway_id,
-                queue,
-                rep_sock,
-                stop_receive,
-                stop_send,
-            ) {
-                error!("Command handler loop error: {}", e);
-                stop_send_err.send(Signal::Stop).unwrap();
-            }
-
-            debug!("Command handler lopp ended");
-        }
-    }));
-
     // stats thread
     threads.push(thread::spawn({
         let gateway_id = config.gateway.gateway_id_bytes.clone();
-        let queue = Arc::clone(&queue);
+        let queue = Arc::clone(queue);
         let stats_interval = config.concentratord.stats_interval;
+        let state_file = config.gateway.regulation.state_file.clone();
         let stop_receive = signal_pool.new_receiver();
         let stop_send = stop_send.clone();
         let mut metadata = HashMap::new();
@@ -162,6 +416,7 @@ pub fn run(
                 stop_receive,
                 &metadata,
                 queue,
+                &state_file,
             ) {
                 error!("Stats loop error: {}", e);
                 stop_send.send(Signal::Stop).unwrap();
@@ -192,7 +447,7 @@ pub fn run(
         if !config.gateway.beacon.frequencies.is_empty() {
             threads.push(thread::spawn({
                 let beacon_config = config.gateway.beacon.clone();
-                let queue = Arc::clone(&queue);
+                let queue = Arc::clone(queue);
                 let stop_receive = signal_pool.new_receiver();
                 let stop_send = stop_send.clone();
 
@@ -210,14 +465,5 @@ pub fn run(
         }
     }
 
-    let stop_signal = stop_receive.recv().unwrap();
-    signal_pool.send_signal(stop_signal.clone());
-
-    for t in threads {
-        t.join().unwrap();
-    }
-
-    concentrator::stop(config)?;
-
-    Ok(stop_signal)
+    threads
 }
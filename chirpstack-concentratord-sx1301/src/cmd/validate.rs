@@ -0,0 +1,172 @@
+use libconcentratord::error::ConfigurationError;
+use libconcentratord::validate::Validator;
+
+use super::super::config::{self, helpers};
+
+/// Validate the configuration files and print the problems found. This does not access the
+/// concentrator hardware. It returns the process exit code.
+pub fn run(filenames: &[String]) -> i32 {
+    let mut v = Validator::new(filenames);
+    if let Some(config) = v.parse::<config::Configuration>() {
+        validate(&mut v, config);
+    }
+    v.report()
+}
+
+fn validate(v: &mut Validator, mut config: config::Configuration) {
+    if config
+        .concentratord
+        .log_level
+        .parse::<log::Level>()
+        .is_err()
+    {
+        v.error("concentratord.log_level", "Invalid log level");
+    }
+
+    match hex::decode(&config.gateway.gateway_id) {
+        Ok(b) if b.len() == 8 => {}
+        Ok(_) => v.error("gateway.gateway_id", "Must be exactly 8 bytes"),
+        Err(e) => v.error("gateway.gateway_id", e),
+    }
+
+    if let Err(e) = helpers::set_channel_plan(&mut config) {
+        match config.gateway.sub_band {
+            Some(_) => v.error("gateway.sub_band", e),
+            None => v.error("gateway.region", e),
+        }
+    }
+
+    if let Err(e) = config.concentratord.jit_queue.validate() {
+        v.error("concentratord.jit_queue", e);
+    }
+
+    config.gateway.model_config = match config::vendor::new(&config) {
+        Ok(v) => v,
+        Err(e) => {
+            match config.gateway.model_file.is_empty() {
+                true => v.error("gateway.model", format!("{:#}", e)),
+                false => v.error("gateway.model_file", format!("{:#}", e)),
+            }
+            return;
+        }
+    };
+
+    validate_channels(v, &config);
+    validate_pins(v, &config);
+}
+
+fn validate_channels(v: &mut Validator, config: &config::Configuration) {
+    let concentrator = &config.gateway.concentrator;
+    let model_config = &config.gateway.model_config;
+
+    let mut channels: Vec<(&str, u32, u32)> = concentrator
+        .multi_sf_channels
+        .iter()
+        .filter(|freq| **freq != 0)
+        .map(|freq| {
+            (
+                "gateway.concentrator.multi_sf_channels",
+                *freq,
+                model_config.lora_multi_sf_bandwidth,
+            )
+        })
+        .collect();
+    if concentrator.lora_std.frequency != 0 {
+        if !(7..=12).contains(&concentrator.lora_std.spreading_factor) {
+            v.error(
+                "gateway.concentrator.lora_std.spreading_factor",
+                format!(
+                    "Unsupported spreading-factor: {}",
+                    concentrator.lora_std.spreading_factor
+                ),
+            );
+        }
+        channels.push((
+            "gateway.concentrator.lora_std.frequency",
+            concentrator.lora_std.frequency,
+            concentrator.lora_std.bandwidth,
+        ));
+    }
+    if concentrator.fsk.frequency != 0 {
+        channels.push((
+            "gateway.concentrator.fsk.frequency",
+            concentrator.fsk.frequency,
+            concentrator.fsk.bandwidth,
+        ));
+    }
+
+    // all channels must fit within the bandwidth of the radios
+    match helpers::get_radio_frequencies(config) {
+        Ok(radio_freqs) => {
+            for (key, freq, bandwidth) in &channels {
+                if let Err(e) = helpers::get_radio_for_channel(&radio_freqs, *freq, *bandwidth) {
+                    v.error(key, e);
+                }
+            }
+        }
+        Err(e) => {
+            let key = match &e {
+                ConfigurationError::ChannelOutsideRadioBandwidth(freq) => channels
+                    .iter()
+                    .find(|(_, f, _)| f == freq)
+                    .map(|(key, _, _)| *key),
+                _ => None,
+            };
+            v.error(key.unwrap_or("gateway.concentrator"), e);
+        }
+    }
+
+    // all channels must be within the TX frequency range of the region
+    for (key, freq, bandwidth) in &channels {
+        if !in_tx_range(model_config, *freq, *bandwidth) {
+            v.error(
+                key,
+                format!("Channel is outside the TX frequency range, freq: {}", freq),
+            );
+        }
+    }
+
+    for freq in &config.gateway.beacon.frequencies {
+        if !in_tx_range(model_config, *freq, config.gateway.beacon.bandwidth) {
+            v.error(
+                "gateway.beacon.frequencies",
+                format!("Beacon is outside the TX frequency range, freq: {}", freq),
+            );
+        }
+    }
+}
+
+fn validate_pins(v: &mut Validator, config: &config::Configuration) {
+    let gw = &config.gateway;
+
+    match &gw.model_config.reset_pin {
+        Some(pin) => {
+            if !pin.0.starts_with("/dev/") {
+                v.error(
+                    "gateway.sx1301_reset_pin",
+                    format!("Invalid GPIO chip: {}", pin.0),
+                );
+            }
+        }
+        None => {
+            if gw.sx1301_reset_chip.is_some() || gw.sx1301_reset_pin.is_some() {
+                v.error(
+                    "gateway.sx1301_reset_pin",
+                    "Pin is not used by gateway model",
+                );
+            }
+        }
+    }
+}
+
+fn in_tx_range(model_config: &config::vendor::Configuration, freq: u32, bandwidth: u32) -> bool {
+    let min = freq.saturating_sub(bandwidth / 2);
+    let max = freq + (bandwidth / 2);
+
+    // without TX ranges there is nothing to check against
+    model_config.tx_min_max_freqs.is_empty()
+        || model_config
+            .tx_min_max_freqs
+            .iter()
+            .any(|(tx_min, tx_max)| min >= *tx_min && max <= *tx_max)
+}
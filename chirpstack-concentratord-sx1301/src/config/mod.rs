@@ -1,10 +1,12 @@
-use std::{env, fmt, fs};
+use std::fmt;
 
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
-use libconcentratord::gnss;
-use libconcentratord::regulation::{standard, tracker::Tracker};
+use libconcentratord::error::ConfigFileError;
+use libconcentratord::regulation::{dwelltime, standard, tracker::Tracker};
+use libconcentratord::{commands, config_file, gnss, jitqueue};
 
 pub mod helpers;
 pub mod vendor;
@@ -34,7 +36,7 @@ impl fmt::Display for Region {
     }
 }
 
-#[derive(Default, Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Default, Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Concentrator {
     pub multi_sf_channels: [u32; 8],
     #[serde(default)]
@@ -43,14 +45,14 @@ pub struct Concentrator {
     pub fsk: FSKChannel,
 }
 
-#[derive(Default, Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Default, Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct LoRaStdChannel {
     pub frequency: u32,
     pub bandwidth: u32,
     pub spreading_factor: u8,
 }
 
-#[derive(Default, Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Default, Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct FSKChannel {
     pub frequency: u32,
     pub datarate: u32,
@@ -73,19 +75,23 @@ pub struct Location {
     pub altitude: f32,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct Gateway {
     pub antenna_gain: i8,
     pub lorawan_public: bool,
     pub region: Option<Region>,
+    pub sub_band: Option<u8>,
     pub model: String,
+    pub model_file: String,
     pub model_flags: Vec<String>,
     pub gateway_id: String,
     pub time_fallback_enabled: bool,
+    pub config_state_file: String,
     pub concentrator: Concentrator,
     pub beacon: Beacon,
     pub location: Location,
+    pub regulation: Regulation,
 
     pub gnss_dev_path: Option<gnss::Device>,
     pub com_dev_path: Option<String>,
@@ -106,13 +112,17 @@ impl Default for Gateway {
             antenna_gain: 2,
             lorawan_public: true,
             region: None,
+            sub_band: None,
             model: "".into(),
+            model_file: "".into(),
             model_flags: vec![],
             gateway_id: "".into(),
             time_fallback_enabled: false,
+            config_state_file: "".into(),
             concentrator: Concentrator::default(),
             beacon: Beacon::default(),
             location: Location::default(),
+            regulation: Regulation::default(),
             gnss_dev_path: None,
             com_dev_path: None,
             sx1301_reset_chip: None,
@@ -149,7 +159,7 @@ impl Gateway {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct Concentratord {
     pub log_level: String,
@@ -158,6 +168,11 @@ pub struct Concentratord {
     pub stats_interval: Duration,
     pub disable_crc_filter: bool,
     pub api: Api,
+    pub capture: Capture,
+    pub jit_queue: jitqueue::Configuration,
+
+    #[serde(skip)]
+    pub log_level_filter: log::LevelFilter,
 }
 
 impl Default for Concentratord {
@@ -168,27 +183,84 @@ impl Default for Concentratord {
             stats_interval: Duration::from_secs(30),
             disable_crc_filter: false,
             api: Default::default(),
+            capture: Default::default(),
+            jit_queue: Default::default(),
+            log_level_filter: log::LevelFilter::Info,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Capture {
+    pub file: String,
+    pub max_file_size: u64,
+    pub max_files: usize,
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Capture {
+            file: "".into(),
+            max_file_size: 10 * 1024 * 1024,
+            max_files: 5,
         }
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct Api {
     pub event_bind: String,
+    pub event_topics: bool,
     pub command_bind: String,
+    pub command_socket_type: commands::SocketType,
+    #[serde(with = "humantime_serde")]
+    pub command_timeout: Duration,
+    pub metrics_bind: String,
 }
 
 impl Default for Api {
     fn default() -> Self {
         Api {
             event_bind: "ipc:///tmp/concentratord_event".to_string(),
+            event_topics: false,
             command_bind: "ipc:///tmp/concentratord_command".to_string(),
+            command_socket_type: commands::SocketType::Rep,
+            command_timeout: Duration::from_secs(1),
+            metrics_bind: "".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Regulation {
+    pub standard: Option<standard::Standard>,
+    #[serde(with = "humantime_serde")]
+    pub window_time: Duration,
+    pub bands: Vec<standard::Band>,
+    pub tx_power_policy: standard::TxPowerPolicy,
+    pub enforce_dwell_time: bool,
+    pub dwell_time_limits: Vec<dwelltime::Limit>,
+    pub state_file: String,
+}
+
+impl Default for Regulation {
+    fn default() -> Self {
+        Regulation {
+            standard: None,
+            window_time: Duration::from_secs(60 * 60),
+            bands: vec![],
+            tx_power_policy: standard::TxPowerPolicy::default(),
+            enforce_dwell_time: false,
+            dwell_time_limits: vec![],
+            state_file: "".into(),
         }
     }
 }
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize, Clone)]
 #[serde(default = "example_configuration")]
 pub struct Configuration {
     pub concentratord: Concentratord,
@@ -196,12 +268,55 @@ pub struct Configuration {
 }
 
 impl Configuration {
-    pub fn get_duty_cycle_tracker(&self) -> Option<Tracker> {
+    pub fn get_duty_cycle_tracker(&self) -> Result<Option<Tracker>> {
+        let regulation = &self.gateway.regulation;
+
+        // A custom band table overrides the band table of the standard.
+        let config = if !regulation.bands.is_empty() {
+            standard::Configuration::new_custom(regulation.bands.clone(), regulation.window_time)?
+        } else {
+            let standard = match regulation.standard {
+                Some(v) => v,
+                None => match self.gateway.region {
+                    Some(Region::EU868) | Some(Region::EU433) => {
+                        standard::Standard::ETSI_EN_300_220
+                    }
+                    Some(Region::RU864) => standard::Standard::SCRF_07_20_03_001,
+                    Some(Region::IN865) => standard::Standard::WPC_GSR_1048,
+                    _ => return Ok(None),
+                },
+            };
+
+            standard::get(standard)
+        };
+
+        Ok(Some(Tracker::new(
+            config,
+            self.gateway.model_config.enforce_duty_cycle,
+            regulation.tx_power_policy,
+        )))
+    }
+
+    pub fn get_dwell_time(&self) -> Option<dwelltime::Configuration> {
+        let regulation = &self.gateway.regulation;
+
+        if !regulation.enforce_dwell_time {
+            return None;
+        }
+
+        // Custom dwell-time limits override the limits of the region.
+        if !regulation.dwell_time_limits.is_empty() {
+            return Some(dwelltime::Configuration::new(
+                regulation.dwell_time_limits.clone(),
+            ));
+        }
+
         match self.gateway.region {
-            Some(Region::EU868) => Some(Tracker::new(
-                standard::get(standard::Standard::ETSI_EN_300_220),
-                self.gateway.model_config.enforce_duty_cycle,
-            )),
+            Some(Region::US915) => Some(dwelltime::Configuration::us915()),
+            Some(Region::AS923)
+            | Some(Region::AS923_2)
+            | Some(Region::AS923_3)
+            | Some(Region::AS923_4) => Some(dwelltime::Configuration::as923()),
             _ => None,
         }
     }
@@ -244,46 +359,43 @@ fn example_configuration() -> Configuration {
     }
 }
 
-pub fn get(filenames: Vec<String>) -> Configuration {
-    let mut content: String = String::new();
-
-    for file_name in &filenames {
-        content.push_str(&fs::read_to_string(file_name).expect("Error reading config file"));
-    }
-
-    // Replace environment variables in config.
-    for (k, v) in env::vars() {
-        content = content.replace(&format!("${}", k), &v);
-    }
+pub fn get(filenames: Vec<String>) -> Result<Configuration, ConfigFileError> {
+    let mut config: Configuration = config_file::load(&filenames)?;
 
-    let mut config: Configuration = toml::from_str(&content).expect("Error parsing config file");
+    // parse log level
+    config.concentratord.log_level_filter = config
+        .concentratord
+        .log_level
+        .parse::<log::Level>()
+        .map_err(|_| ConfigFileError::InvalidLogLevel(config.concentratord.log_level.clone()))?
+        .to_level_filter();
 
     // decode gateway id
-    let bytes = hex::decode(&config.gateway.gateway_id).expect("Could not decode gateway_id");
+    let bytes = hex::decode(&config.gateway.gateway_id)
+        .map_err(|e| ConfigFileError::InvalidGatewayId(e.to_string()))?;
     if bytes.len() != 8 {
-        panic!("gateway_id must be exactly 8 bytes");
+        return Err(ConfigFileError::InvalidGatewayId(
+            "Must be exactly 8 bytes".into(),
+        ));
     }
     config.gateway.gateway_id_bytes = bytes;
 
+    // generate the channels from the region channel plan
+    helpers::set_channel_plan(&mut config)
+        .map_err(|e| ConfigFileError::ChannelPlan(e.to_string()))?;
+
+    // validate the JIT queue configuration
+    config
+        .concentratord
+        .jit_queue
+        .validate()
+        .map_err(|e| ConfigFileError::JitQueue(e.to_string()))?;
+
     // get model configuration
-    config.gateway.model_config = match config.gateway.model.as_ref() {
-        "imst_ic880a" => vendor::imst::ic880a::new(&config).unwrap(),
-        "kerlink_ifemtocell" => vendor::kerlink::ifemtocell::new(&config).unwrap(),
-        "multitech_mtac_lora_h_868" => vendor::multitech::mtac_lora_h_868::new(&config).unwrap(),
-        "multitech_mtac_lora_h_915" => vendor::multitech::mtac_lora_h_915::new(&config).unwrap(),
-        "multitech_mtcap_lora_868" => vendor::multitech::mtcap_lora_868::new(&config).unwrap(),
-        "multitech_mtcap_lora_915" => vendor::multitech::mtcap_lora_915::new(&config).unwrap(),
-        "pi_supply_lora_gateway_hat" => vendor::pi_supply::lora_gateway_hat::new(&config).unwrap(),
-        "rak_2245" => vendor::rak::rak2245::new(&config).unwrap(),
-        "rak_2246" => vendor::rak::rak2246::new(&config).unwrap(),
-        "rak_2247" => vendor::rak::rak2247::new(&config).unwrap(),
-        "risinghf_rhf0m301" => vendor::risinghf::rhf0m301::new(&config).unwrap(),
-        "sandbox_lorago_port" => vendor::sandbox::lorago_port::new(&config).unwrap(),
-        "wifx_lorix_one" => vendor::wifx::lorix_one::new(&config).unwrap(),
-        _ => panic!("unexpected gateway model: {}", config.gateway.model),
-    };
+    config.gateway.model_config =
+        vendor::new(&config).map_err(|e| ConfigFileError::Model(format!("{:#}", e)))?;
 
     debug!("Antenna gain {} dBi", config.gateway.antenna_gain);
 
-    config
+    Ok(config)
 }
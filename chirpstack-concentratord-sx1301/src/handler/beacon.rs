@@ -107,12 +107,14 @@ fn send_beacon(
         size: data_size as u16,
         payload: data,
     };
-    let tx_packet = wrapper::TxPacket::new(getrandom::u32()?, tx_packet);
+    let tx_packet =
+        wrapper::TxPacket::new(getrandom::u32()?, tx_packet, jitqueue::Priority::Beacon);
 
     queue
         .lock()
         .map_err(|_| anyhow!("Queue lock error"))?
         .enqueue(timersync::get_concentrator_count(), tx_packet)
+        .map(|_| ())
         .map_err(|e| anyhow!("{:?}", e))
 }
 
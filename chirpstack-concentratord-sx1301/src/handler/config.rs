@@ -1,13 +1,60 @@
 use std::cmp::Ordering;
 
-use anyhow::Result;
+use libconcentratord::error::ConfigurationError;
 
-use super::super::config::{Concentrator, Configuration};
+use super::super::config::{helpers, Concentrator, Configuration};
+
+/// Validate the gateway configuration. On success, this returns a copy of the given
+/// configuration with the gateway configuration applied. The given configuration is not
+/// modified.
+pub fn validate_configuration(
+    config: &Configuration,
+    new_config: &chirpstack_api::gw::GatewayConfiguration,
+) -> Result<Configuration, ConfigurationError> {
+    let mut config = config.clone();
+    update_configuration(&mut config, new_config)?;
+
+    let concentrator = &config.gateway.concentrator;
+    if concentrator.lora_std.frequency != 0
+        && !(7..=12).contains(&concentrator.lora_std.spreading_factor)
+    {
+        return Err(ConfigurationError::InvalidChannel(
+            concentrator.lora_std.frequency,
+            "unsupported spreading-factor".to_string(),
+        ));
+    }
+
+    // all channels must fit within the bandwidth of the radios
+    let radio_freqs = helpers::get_radio_frequencies(&config)?;
+    for freq in concentrator.multi_sf_channels.iter().filter(|v| **v != 0) {
+        helpers::get_radio_for_channel(
+            &radio_freqs,
+            *freq,
+            config.gateway.model_config.lora_multi_sf_bandwidth,
+        )?;
+    }
+    if concentrator.lora_std.frequency != 0 {
+        helpers::get_radio_for_channel(
+            &radio_freqs,
+            concentrator.lora_std.frequency,
+            concentrator.lora_std.bandwidth,
+        )?;
+    }
+    if concentrator.fsk.frequency != 0 {
+        helpers::get_radio_for_channel(
+            &radio_freqs,
+            concentrator.fsk.frequency,
+            concentrator.fsk.bandwidth,
+        )?;
+    }
+
+    Ok(config)
+}
 
 pub fn update_configuration(
     config: &mut Configuration,
     new_config: &chirpstack_api::gw::GatewayConfiguration,
-) -> Result<()> {
+) -> Result<(), ConfigurationError> {
     info!("Updating concentrator configuration");
 
     // empty concentrator config
@@ -28,7 +75,9 @@ pub fn update_configuration(
                 }
                 Ordering::Greater => {
                     if multi_sf_count > concentrator.multi_sf_channels.len() - 1 {
-                        return Err(anyhow!("too many multi-SF channels in configuration"));
+                        return Err(ConfigurationError::TooManyMultiSfChannels(
+                            concentrator.multi_sf_channels.len(),
+                        ));
                     }
 
                     concentrator.multi_sf_channels[multi_sf_count] = channel.frequency;
@@ -49,7 +98,9 @@ pub fn update_configuration(
 
     // set config
     if let Some(stats_interval) = new_config.stats_interval {
-        config.concentratord.stats_interval = stats_interval.try_into()?;
+        config.concentratord.stats_interval = stats_interval
+            .try_into()
+            .map_err(|_| ConfigurationError::InvalidStatsInterval)?;
     }
     config
         .gateway
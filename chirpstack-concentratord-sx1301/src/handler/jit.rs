@@ -1,42 +1,68 @@
 use std::sync::mpsc::Receiver;
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 
 use libconcentratord::jitqueue::TxPacket;
 use libconcentratord::signals::Signal;
-use libconcentratord::{jitqueue, stats};
+use libconcentratord::{api, capture, events, jitqueue, stats};
 use libloragw_sx1301::hal;
 
 use super::super::wrapper;
 use super::timersync;
 
+// Downlink which has been handed to the concentrator, but for which the TX is not yet
+// completed.
+static PENDING: LazyLock<Mutex<Option<u32>>> = LazyLock::new(|| Mutex::new(None));
+
 pub fn jit_loop(
     queue: Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+    gateway_id: &[u8],
     antenna_gain_dbi: i8,
     stop_receive: Receiver<Signal>,
 ) -> Result<()> {
     debug!("Starting JIT queue loop");
 
+    // Downlinks which were pending before a restart of the concentrator.
+    flush_pending(gateway_id)?;
+
     loop {
         // Instead of a 10ms sleep, we receive from the stop channel with a
         // timeout of 10ms.
         if let Ok(v) = stop_receive.recv_timeout(Duration::from_millis(10)) {
             debug!("Received stop signal, signal: {}", v);
-            return Ok(());
+            return flush_pending(gateway_id);
         }
 
-        let tx_packet = match get_tx_packet(&queue)? {
+        // The lock is held until the popped packet has been set as the pending downlink, such
+        // that abort_tx does not miss a downlink that was popped from the queue.
+        let mut pending = PENDING.lock().unwrap();
+
+        if let Some(downlink_id) = *pending
+            && let Some(result) = get_tx_result()?
+        {
+            send_tx_result(gateway_id, downlink_id, result)?;
+            *pending = None;
+        }
+
+        let tx_packet = match get_tx_packet(gateway_id, &queue)? {
             Some(v) => v,
             None => continue,
         };
+        let popped_at = Instant::now();
 
         let downlink_id = tx_packet.get_id();
         let mut tx_packet = tx_packet.tx_packet();
         tx_packet.rf_power -= antenna_gain_dbi;
 
-        match hal::send(&tx_packet) {
+        let res = hal::send(&tx_packet);
+        queue
+            .lock()
+            .map_err(|_| anyhow!("Lock queue error"))?
+            .track_send_latency(downlink_id, popped_at.elapsed());
+
+        match res {
             Ok(_) => {
                 info!(
                     "Scheduled packet for TX, downlink_id: {}, count_us: {}, freq: {}, bw: {}, mod: {:?}, dr: {:?}",
@@ -51,18 +77,94 @@ pub fn jit_loop(
                 if let Ok(tx_info) = wrapper::downlink_to_tx_info_proto(&tx_packet) {
                     stats::inc_tx_counts(&tx_info);
                 }
+
+                if let Err(e) = capture::capture(&wrapper::downlink_to_capture(&tx_packet)) {
+                    error!("Capture downlink error, error: {}", e);
+                }
+
+                // The concentrator only holds a single packet, in which case the previous
+                // packet has been replaced.
+                if let Some(downlink_id) = pending.replace(downlink_id) {
+                    send_tx_result(gateway_id, downlink_id, api::TxResult::Unknown)?;
+                }
             }
             Err(err) => {
-                error!("Schedule packet for tx error, error: {}", err);
+                let result = match err.downcast_ref::<hal::Error>() {
+                    Some(hal::Error::LbtNotAllowed) => {
+                        warn!(
+                            "Packet not sent because of LBT, downlink_id: {}",
+                            downlink_id
+                        );
+                        api::TxResult::LbtBusy
+                    }
+                    None => {
+                        error!("Schedule packet for tx error, error: {}", err);
+                        api::TxResult::SendError
+                    }
+                };
+
+                send_tx_result(gateway_id, downlink_id, result)?;
             }
         }
     }
 }
 
+/// Abort the transmission of the given downlink, when it has been handed to the concentrator.
+/// This returns false when the downlink is not pending.
+pub fn abort_tx(gateway_id: &[u8], downlink_id: u32) -> Result<bool> {
+    let mut pending = PENDING.lock().unwrap();
+    if *pending != Some(downlink_id) {
+        return Ok(false);
+    }
+
+    hal::abort_tx()?;
+    *pending = None;
+
+    info!("TX aborted, downlink_id: {}", downlink_id);
+    send_tx_result(gateway_id, downlink_id, api::TxResult::Aborted)?;
+
+    Ok(true)
+}
+
 fn get_tx_packet(
+    gateway_id: &[u8],
     queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
 ) -> Result<Option<wrapper::TxPacket>> {
     let mut queue = queue.lock().map_err(|_| anyhow!("Lock queue error"))?;
     let concentrator_count = timersync::get_concentrator_count();
-    Ok(queue.pop(concentrator_count))
+    let tx_packet = queue.pop(concentrator_count);
+
+    for (tx_packet, result) in queue.take_dropped() {
+        send_tx_result(gateway_id, tx_packet.get_id(), result)?;
+    }
+
+    Ok(tx_packet)
+}
+
+fn get_tx_result() -> Result<Option<api::TxResult>> {
+    Ok(match hal::status(hal::StatusSelect::Tx)? {
+        hal::StatusReturn::Tx(hal::TxStatus::Scheduled)
+        | hal::StatusReturn::Tx(hal::TxStatus::Emitting) => None,
+        hal::StatusReturn::Tx(hal::TxStatus::Free) => Some(api::TxResult::Emitted),
+        _ => Some(api::TxResult::Unknown),
+    })
+}
+
+// The concentrator does not keep the pending downlink when it is stopped, the TX result of
+// this downlink is unknown.
+fn flush_pending(gateway_id: &[u8]) -> Result<()> {
+    let pending = PENDING.lock().unwrap().take();
+    if let Some(downlink_id) = pending {
+        send_tx_result(gateway_id, downlink_id, api::TxResult::Unknown)?;
+    }
+
+    Ok(())
+}
+
+fn send_tx_result(gateway_id: &[u8], downlink_id: u32, result: api::TxResult) -> Result<()> {
+    events::send_tx_result(api::DownlinkTxResult {
+        gateway_id: hex::encode(gateway_id),
+        downlink_id,
+        result: result.into(),
+    })
 }
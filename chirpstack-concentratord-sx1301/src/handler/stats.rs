@@ -6,7 +6,7 @@ use anyhow::Result;
 
 use chirpstack_api::gw::DutyCycleStats;
 use libconcentratord::signals::Signal;
-use libconcentratord::{gnss, jitqueue, stats};
+use libconcentratord::{gnss, jitqueue, metrics, stats};
 
 use super::timersync;
 use crate::wrapper;
@@ -17,6 +17,7 @@ pub fn stats_loop(
     stop_receive: Receiver<Signal>,
     metadata: &HashMap<String, String>,
     queue: Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+    duty_cycle_state_file: &str,
 ) -> Result<()> {
     debug!("Starting stats loop, stats_interval: {:?}", stats_interval);
 
@@ -39,7 +40,24 @@ pub fn stats_loop(
             }
         });
 
+        // update the metrics gauges
+        metrics::set_jit_queue_size(queue.lock().map_err(|_| anyhow!("Queue lock error"))?.len());
+        metrics::set_jit_send_latency_max(
+            queue
+                .lock()
+                .map_err(|_| anyhow!("Queue lock error"))?
+                .take_send_latency_max(),
+        );
+        metrics::set_gnss_locked(
+            gnss::count_to_epoch(timersync::get_concentrator_count()).is_some(),
+        );
+
         let dc_stats = get_duty_cycle_stats(&queue)?;
+
+        if let Err(e) = save_duty_cycle_state(&queue, duty_cycle_state_file) {
+            error!("Save duty-cycle state error, error: {}", e);
+        }
+
         stats::send_and_reset(gateway_id, loc, dc_stats, metadata).expect("sending stats failed");
     }
 }
@@ -51,3 +69,16 @@ fn get_duty_cycle_stats(
     let concentrator_count = timersync::get_concentrator_count();
     Ok(queue.get_duty_cycle_stats(concentrator_count))
 }
+
+fn save_duty_cycle_state(
+    queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+    path: &str,
+) -> Result<()> {
+    if path.is_empty() {
+        return Ok(());
+    }
+
+    let mut queue = queue.lock().map_err(|_| anyhow!("Lock queue error"))?;
+    let concentrator_count = timersync::get_concentrator_count();
+    queue.save_duty_cycle_state(concentrator_count, path)
+}
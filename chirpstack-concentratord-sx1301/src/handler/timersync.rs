@@ -46,7 +46,7 @@ pub fn get_concentrator_count() -> u32 {
     prev_concentrator_count.wrapping_add(unix_diff.as_micros() as u32)
 }
 
-fn timesync() -> Result<()> {
+pub fn timesync() -> Result<()> {
     debug!("Disabling GPS mode for concentrator counter");
     reg::reg_w(wrapper::LGW_GPS_EN, 0)?;
 
@@ -0,0 +1,80 @@
+use anyhow::Result;
+use libconcentratord::reset;
+use libloragw_sx1302::hal;
+
+use super::Backend;
+use crate::concentrator;
+use crate::config::Configuration;
+
+/// Hardware backend, using the libloragw HAL.
+pub struct Hardware {}
+
+impl Backend for Hardware {
+    fn start(&mut self, config: &Configuration) -> Result<()> {
+        // reset concentrator
+        reset::reset()?;
+
+        // setup concentrator
+        concentrator::set_i2c_device_path(config)?;
+        concentrator::set_i2c_temp_sensor_addr(config)?;
+        concentrator::board_setconf(config)?;
+        concentrator::timestamp_setconf(config)?;
+        concentrator::txgain_setconf(config)?;
+        concentrator::sx1261_setconf(config)?;
+        concentrator::rxrf_setconf(config)?;
+        concentrator::rxif_setconf(config)?;
+        concentrator::start()
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        concentrator::stop()
+    }
+
+    fn get_eui(&mut self) -> Result<[u8; 8]> {
+        concentrator::get_eui()
+    }
+
+    fn receive(&mut self) -> Result<Vec<hal::RxPacket>> {
+        hal::receive()
+    }
+
+    fn send(&mut self, pkt: &hal::TxPacket) -> Result<()> {
+        hal::send(pkt)
+    }
+
+    fn abort_tx(&mut self, rf_chain: u8) -> Result<()> {
+        hal::abort_tx(rf_chain)
+    }
+
+    fn status(&mut self, rf_chain: u8, select: hal::StatusSelect) -> Result<hal::StatusReturn> {
+        hal::status(rf_chain, select)
+    }
+
+    fn get_instcnt(&mut self) -> Result<u32> {
+        hal::get_instcnt()
+    }
+
+    fn get_trigcnt(&mut self) -> Result<u32> {
+        hal::get_trigcnt()
+    }
+
+    fn get_temperature(&mut self) -> Result<f32> {
+        hal::get_temperature()
+    }
+
+    fn spectral_scan_start(&mut self, freq_hz: u32, nb_scan: u16) -> Result<()> {
+        hal::spectral_scan_start(freq_hz, nb_scan)
+    }
+
+    fn spectral_scan_get_status(&mut self) -> Result<hal::SpectralScanStatus> {
+        hal::spectral_scan_get_status()
+    }
+
+    fn spectral_scan_get_results(&mut self) -> Result<Vec<hal::SpectralScanResult>> {
+        hal::spectral_scan_get_results()
+    }
+
+    fn spectral_scan_abort(&mut self) -> Result<()> {
+        hal::spectral_scan_abort()
+    }
+}
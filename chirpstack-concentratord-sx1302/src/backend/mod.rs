@@ -0,0 +1,156 @@
+use std::sync::{LazyLock, Mutex};
+
+use anyhow::Result;
+use libloragw_sx1302::hal;
+
+use crate::config::Configuration;
+
+mod hardware;
+#[cfg(feature = "simulator")]
+mod simulator;
+
+/// Backend abstracts the concentrator HAL surface used by the handlers.
+///
+/// The hardware backend calls the libloragw HAL. The simulator backend (which
+/// requires the simulator feature) makes it possible to run Concentratord
+/// without a concentrator attached.
+pub trait Backend: Send {
+    /// Reset, configure and start the concentrator.
+    fn start(&mut self, config: &Configuration) -> Result<()>;
+
+    /// Stop the concentrator.
+    fn stop(&mut self) -> Result<()>;
+
+    /// Return the concentrator EUI.
+    fn get_eui(&mut self) -> Result<[u8; 8]>;
+
+    /// Return the received packets.
+    fn receive(&mut self) -> Result<Vec<hal::RxPacket>>;
+
+    /// Schedule the given packet for transmission.
+    fn send(&mut self, pkt: &hal::TxPacket) -> Result<()>;
+
+    /// Abort the scheduled or on-going transmission of the given RF chain.
+    fn abort_tx(&mut self, rf_chain: u8) -> Result<()>;
+
+    /// Return the TX or RX status of the given RF chain.
+    fn status(&mut self, rf_chain: u8, select: hal::StatusSelect) -> Result<hal::StatusReturn>;
+
+    /// Return the internal concentrator counter (in microseconds).
+    fn get_instcnt(&mut self) -> Result<u32>;
+
+    /// Return the internal concentrator counter value of the last PPS pulse.
+    fn get_trigcnt(&mut self) -> Result<u32>;
+
+    /// Return the concentrator temperature.
+    fn get_temperature(&mut self) -> Result<f32>;
+
+    /// Start a spectral scan of the given frequency (using the SX1261 radio).
+    fn spectral_scan_start(&mut self, freq_hz: u32, nb_scan: u16) -> Result<()>;
+
+    /// Return the status of the spectral scan.
+    fn spectral_scan_get_status(&mut self) -> Result<hal::SpectralScanStatus>;
+
+    /// Return the results of the completed spectral scan.
+    fn spectral_scan_get_results(&mut self) -> Result<Vec<hal::SpectralScanResult>>;
+
+    /// Abort the on-going spectral scan.
+    fn spectral_scan_abort(&mut self) -> Result<()>;
+}
+
+static BACKEND: LazyLock<Mutex<Option<Box<dyn Backend>>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Setup the backend.
+///
+/// The backend is only created once, it is kept when the concentrator is
+/// restarted after a configuration update.
+pub fn setup(config: &Configuration) -> Result<()> {
+    let mut backend = BACKEND.lock().unwrap();
+    if backend.is_some() {
+        return Ok(());
+    }
+
+    if config.simulator.enabled {
+        #[cfg(feature = "simulator")]
+        {
+            info!("Using simulated concentrator backend");
+            *backend = Some(Box::new(simulator::Simulator::new(config)?));
+        }
+
+        #[cfg(not(feature = "simulator"))]
+        return Err(anyhow!(
+            "Simulator is enabled, but Concentratord was compiled without the simulator feature"
+        ));
+    } else {
+        *backend = Some(Box::new(hardware::Hardware {}));
+    }
+
+    Ok(())
+}
+
+fn with_backend<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce(&mut dyn Backend) -> Result<T>,
+{
+    let mut backend = BACKEND.lock().unwrap();
+    match backend.as_mut() {
+        Some(v) => f(v.as_mut()),
+        None => Err(anyhow!("Backend has not been setup")),
+    }
+}
+
+pub fn start(config: &Configuration) -> Result<()> {
+    with_backend(|b| b.start(config))
+}
+
+pub fn stop() -> Result<()> {
+    with_backend(|b| b.stop())
+}
+
+pub fn get_eui() -> Result<[u8; 8]> {
+    with_backend(|b| b.get_eui())
+}
+
+pub fn receive() -> Result<Vec<hal::RxPacket>> {
+    with_backend(|b| b.receive())
+}
+
+pub fn send(pkt: &hal::TxPacket) -> Result<()> {
+    with_backend(|b| b.send(pkt))
+}
+
+pub fn abort_tx(rf_chain: u8) -> Result<()> {
+    with_backend(|b| b.abort_tx(rf_chain))
+}
+
+pub fn status(rf_chain: u8, select: hal::StatusSelect) -> Result<hal::StatusReturn> {
+    with_backend(|b| b.status(rf_chain, select))
+}
+
+pub fn get_instcnt() -> Result<u32> {
+    with_backend(|b| b.get_instcnt())
+}
+
+pub fn get_trigcnt() -> Result<u32> {
+    with_backend(|b| b.get_trigcnt())
+}
+
+pub fn get_temperature() -> Result<f32> {
+    with_backend(|b| b.get_temperature())
+}
+
+pub fn spectral_scan_start(freq_hz: u32, nb_scan: u16) -> Result<()> {
+    with_backend(|b| b.spectral_scan_start(freq_hz, nb_scan))
+}
+
+pub fn spectral_scan_get_status() -> Result<hal::SpectralScanStatus> {
+    with_backend(|b| b.spectral_scan_get_status())
+}
+
+pub fn spectral_scan_get_results() -> Result<Vec<hal::SpectralScanResult>> {
+    with_backend(|b| b.spectral_scan_get_results())
+}
+
+pub fn spectral_scan_abort() -> Result<()> {
+    with_backend(|b| b.spectral_scan_abort())
+}
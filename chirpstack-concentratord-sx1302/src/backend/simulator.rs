@@ -0,0 +1,532 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::net::UdpSocket;
+use std::os::unix::fs::FileTypeExt;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use libloragw_sx1302::hal;
+use serde::{Deserialize, Serialize};
+
+use super::Backend;
+use crate::config::Configuration;
+
+/// Simulated concentrator.
+///
+/// The simulator implements a virtual (wrapping) microsecond counter, starting
+/// at zero when the simulator is created. Uplinks are injected as JSON objects,
+/// either line by line from a file (or FIFO), or one object per UDP datagram.
+/// Transmissions are appended as JSON objects (one per line) to the downlink
+/// file.
+pub struct Simulator {
+    gateway_id: Option<[u8; 8]>,
+    epoch: Instant,
+    uplink_rx: Receiver<Uplink>,
+    downlink_file: Option<File>,
+    // TX start and end counter, by rf_chain.
+    tx: HashMap<u8, (u32, u32)>,
+    // Number of measurements of the last spectral scan.
+    spectral_scan: Option<u16>,
+}
+
+impl Simulator {
+    pub fn new(config: &Configuration) -> Result<Self> {
+        let (uplink_tx, uplink_rx) = channel();
+
+        if !config.simulator.uplink_file.is_empty() {
+            let path = config.simulator.uplink_file.clone();
+            let uplink_tx = uplink_tx.clone();
+
+            info!("Reading simulated uplinks from file, path: {}", path);
+            thread::spawn(move || read_uplink_file(&path, uplink_tx));
+        }
+
+        if !config.simulator.uplink_bind.is_empty() {
+            let sock = UdpSocket::bind(&config.simulator.uplink_bind)
+                .context("Bind simulator uplink socket")?;
+
+            info!(
+                "Receiving simulated uplinks over UDP, bind: {}",
+                config.simulator.uplink_bind
+            );
+            thread::spawn(move || read_uplink_socket(sock, uplink_tx));
+        }
+
+        let downlink_file = if config.simulator.downlink_file.is_empty() {
+            None
+        } else {
+            Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&config.simulator.downlink_file)
+                    .context("Open simulator downlink file")?,
+            )
+        };
+
+        Ok(Simulator {
+            gateway_id: config.gateway.gateway_id_bytes,
+            epoch: Instant::now(),
+            uplink_rx,
+            downlink_file,
+            tx: HashMap::new(),
+            spectral_scan: None,
+        })
+    }
+
+    fn counter(&self) -> u32 {
+        // Truncating to u32 results in the same wrapping behavior as the
+        // concentrator counter.
+        self.epoch.elapsed().as_micros() as u32
+    }
+
+    fn pps_counter(&self) -> u32 {
+        (self.epoch.elapsed().as_secs() * 1_000_000) as u32
+    }
+}
+
+impl Backend for Simulator {
+    fn start(&mut self, _config: &Configuration) -> Result<()> {
+        info!("Starting the simulated concentrator");
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        info!("Stopping the simulated concentrator");
+        self.tx.clear();
+        Ok(())
+    }
+
+    fn get_eui(&mut self) -> Result<[u8; 8]> {
+        self.gateway_id
+            .ok_or_else(|| anyhow!("gateway_id must be configured when using the simulator"))
+    }
+
+    fn receive(&mut self) -> Result<Vec<hal::RxPacket>> {
+        let count_us = self.counter();
+        Ok(self
+            .uplink_rx
+            .try_iter()
+            .map(|v| v.to_rx_packet(count_us))
+            .collect())
+    }
+
+    fn send(&mut self, pkt: &hal::TxPacket) -> Result<()> {
+        let start = match pkt.tx_mode {
+            hal::TxMode::Immediate => self.counter(),
+            hal::TxMode::Timestamped => pkt.count_us,
+            hal::TxMode::OnGPS => self.pps_counter().wrapping_add(1_000_000),
+        };
+        let time_on_air = hal::time_on_air(pkt)?;
+        let end = start.wrapping_add(time_on_air.as_micros() as u32);
+
+        if let Some(f) = self.downlink_file.as_mut() {
+            let line = serde_json::to_string(&Downlink::from_tx_packet(pkt, start))?;
+            writeln!(f, "{}", line)?;
+            f.flush()?;
+        }
+
+        self.tx.insert(pkt.rf_chain, (start, end));
+
+        Ok(())
+    }
+
+    fn abort_tx(&mut self, rf_chain: u8) -> Result<()> {
+        self.tx.remove(&rf_chain);
+        Ok(())
+    }
+
+    fn status(&mut self, rf_chain: u8, select: hal::StatusSelect) -> Result<hal::StatusReturn> {
+        Ok(match select {
+            hal::StatusSelect::Rx => hal::StatusReturn::Rx(hal::RxStatus::On),
+            hal::StatusSelect::Tx => {
+                let count_us = self.counter();
+
+                hal::StatusReturn::Tx(match self.tx.get(&rf_chain) {
+                    Some((start, _)) if (count_us.wrapping_sub(*start) as i32) < 0 => {
+                        hal::TxStatus::Scheduled
+                    }
+                    Some((_, end)) if (count_us.wrapping_sub(*end) as i32) < 0 => {
+                        hal::TxStatus::Emitting
+                    }
+                    _ => hal::TxStatus::Free,
+                })
+            }
+        })
+    }
+
+    fn get_instcnt(&mut self) -> Result<u32> {
+        Ok(self.counter())
+    }
+
+    fn get_trigcnt(&mut self) -> Result<u32> {
+        Ok(self.pps_counter())
+    }
+
+    fn get_temperature(&mut self) -> Result<f32> {
+        Ok(25.0)
+    }
+
+    fn spectral_scan_start(&mut self, _freq_hz: u32, nb_scan: u16) -> Result<()> {
+        self.spectral_scan = Some(nb_scan);
+        Ok(())
+    }
+
+    fn spectral_scan_get_status(&mut self) -> Result<hal::SpectralScanStatus> {
+        // The simulated scan completes immediately.
+        Ok(match self.spectral_scan {
+            Some(_) => hal::SpectralScanStatus::Completed,
+            None => hal::SpectralScanStatus::None,
+        })
+    }
+
+    fn spectral_scan_get_results(&mut self) -> Result<Vec<hal::SpectralScanResult>> {
+        // All measurements are reported at the noise floor.
+        Ok(vec![hal::SpectralScanResult {
+            dbm_level: -120,
+            result: self.spectral_scan.unwrap_or_default(),
+        }])
+    }
+
+    fn spectral_scan_abort(&mut self) -> Result<()> {
+        self.spectral_scan = None;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+enum Modulation {
+    Lora,
+    Fsk,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+enum CrcStatus {
+    Ok,
+    Bad,
+    No,
+}
+
+/// Simulated uplink.
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+struct Uplink {
+    frequency: u32,
+    modulation: Modulation,
+    bandwidth: u32,
+    spreading_factor: u32,
+    code_rate: String,
+    datarate: u32,
+    rssi: f32,
+    snr: f32,
+    rf_chain: u8,
+    if_chain: u8,
+    crc_status: CrcStatus,
+    // Hex encoded payload.
+    payload: String,
+}
+
+impl Default for Uplink {
+    fn default() -> Self {
+        Uplink {
+            frequency: 0,
+            modulation: Modulation::Lora,
+            bandwidth: 125000,
+            spreading_factor: 7,
+            code_rate: "4/5".to_string(),
+            datarate: 0,
+            rssi: -50.0,
+            snr: 10.0,
+            rf_chain: 0,
+            if_chain: 0,
+            crc_status: CrcStatus::Ok,
+            payload: "".to_string(),
+        }
+    }
+}
+
+impl Uplink {
+    fn parse(s: &str) -> Result<Self> {
+        let uplink: Uplink = serde_json::from_str(s)?;
+        let size = hex::decode(&uplink.payload)?.len();
+        if size > 255 {
+            return Err(anyhow!("Max payload size is 255 bytes, size: {}", size));
+        }
+
+        Ok(uplink)
+    }
+
+    fn to_rx_packet(&self, count_us: u32) -> hal::RxPacket {
+        // The payload has been validated by Uplink::parse.
+        let payload = hex::decode(&self.payload).unwrap_or_default();
+        let mut payload_buf: [u8; 256] = [0; 256];
+        payload_buf[..payload.len()].copy_from_slice(&payload);
+
+        hal::RxPacket {
+            freq_hz: self.frequency,
+            freq_offset: 0,
+            if_chain: self.if_chain,
+            status: match self.crc_status {
+                CrcStatus::Ok => hal::CRC::CRCOk,
+                CrcStatus::Bad => hal::CRC::BadCRC,
+                CrcStatus::No => hal::CRC::NoCRC,
+            },
+            count_us,
+            rf_chain: self.rf_chain,
+            modem_id: 0,
+            modulation: match self.modulation {
+                Modulation::Lora => hal::Modulation::LoRa,
+                Modulation::Fsk => hal::Modulation::FSK,
+            },
+            bandwidth: self.bandwidth,
+            datarate: match self.modulation {
+                Modulation::Lora => match self.spreading_factor {
+                    5 => hal::DataRate::SF5,
+                    6 => hal::DataRate::SF6,
+                    7 => hal::DataRate::SF7,
+                    8 => hal::DataRate::SF8,
+                    9 => hal::DataRate::SF9,
+                    10 => hal::DataRate::SF10,
+                    11 => hal::DataRate::SF11,
+                    12 => hal::DataRate::SF12,
+                    _ => hal::DataRate::Undefined,
+                },
+                Modulation::Fsk => hal::DataRate::FSK(self.datarate),
+            },
+            coderate: match self.code_rate.as_ref() {
+                "4/5" => hal::CodeRate::LoRa4_5,
+                "4/6" => hal::CodeRate::LoRa4_6,
+                "4/7" => hal::CodeRate::LoRa4_7,
+                "4/8" => hal::CodeRate::LoRa4_8,
+                _ => hal::CodeRate::Undefined,
+            },
+            rssic: self.rssi,
+            rssis: self.rssi,
+            snr: self.snr,
+            snr_min: self.snr,
+            snr_max: self.snr,
+            crc: 0,
+            size: payload.len() as u16,
+            payload: payload_buf,
+            ftime_received: false,
+            ftime: 0,
+        }
+    }
+}
+
+/// Captured transmission.
+#[derive(Serialize, Debug)]
+struct Downlink {
+    // Concentrator counter at which the transmission starts.
+    count_us: u32,
+    frequency: u32,
+    power: i8,
+    modulation: Option<Modulation>,
+    bandwidth: u32,
+    spreading_factor: Option<u32>,
+    datarate: Option<u32>,
+    code_rate: Option<String>,
+    polarization_inversion: bool,
+    preamble: u16,
+    // Hex encoded payload.
+    payload: String,
+}
+
+impl Downlink {
+    fn from_tx_packet(pkt: &hal::TxPacket, count_us: u32) -> Self {
+        Downlink {
+            count_us,
+            frequency: pkt.freq_hz,
+            power: pkt.rf_power,
+            modulation: match pkt.modulation {
+                hal::Modulation::LoRa => Some(Modulation::Lora),
+                hal::Modulation::FSK => Some(Modulation::Fsk),
+                hal::Modulation::Undefined => None,
+            },
+            bandwidth: pkt.bandwidth,
+            spreading_factor: match pkt.datarate {
+                hal::DataRate::SF5 => Some(5),
+                hal::DataRate::SF6 => Some(6),
+                hal::DataRate::SF7 => Some(7),
+                hal::DataRate::SF8 => Some(8),
+                hal::DataRate::SF9 => Some(9),
+                hal::DataRate::SF10 => Some(10),
+                hal::DataRate::SF11 => Some(11),
+                hal::DataRate::SF12 => Some(12),
+                _ => None,
+            },
+            datarate: match pkt.datarate {
+                hal::DataRate::FSK(v) => Some(v),
+                _ => None,
+            },
+            code_rate: match pkt.coderate {
+                hal::CodeRate::LoRa4_5 => Some("4/5".to_string()),
+                hal::CodeRate::LoRa4_6 => Some("4/6".to_string()),
+                hal::CodeRate::LoRa4_7 => Some("4/7".to_string()),
+                hal::CodeRate::LoRa4_8 => Some("4/8".to_string()),
+                hal::CodeRate::Undefined => None,
+            },
+            polarization_inversion: pkt.invert_pol,
+            preamble: pkt.preamble,
+            payload: hex::encode(&pkt.payload[..pkt.size as usize]),
+        }
+    }
+}
+
+fn read_uplink_file(path: &str, uplink_tx: Sender<Uplink>) {
+    loop {
+        // In case of a FIFO, this blocks until there is a writer.
+        let f = match File::open(path) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Open simulator uplink file error, error: {}", e);
+                return;
+            }
+        };
+        let is_fifo = f
+            .metadata()
+            .map(|m| m.file_type().is_fifo())
+            .unwrap_or_default();
+
+        for line in BufReader::new(f).lines() {
+            let line = match line {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("Read simulator uplink file error, error: {}", e);
+                    break;
+                }
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match Uplink::parse(&line) {
+                Ok(v) => {
+                    if uplink_tx.send(v).is_err() {
+                        return;
+                    }
+                }
+                Err(e) => warn!("Parse simulated uplink error, error: {}", e),
+            }
+        }
+
+        // A regular file is only read once, a FIFO is re-opened once the
+        // writer has closed it.
+        if !is_fifo {
+            return;
+        }
+    }
+}
+
+fn read_uplink_socket(sock: UdpSocket, uplink_tx: Sender<Uplink>) {
+    let mut buf = [0; 65535];
+
+    loop {
+        let size = match sock.recv(&mut buf) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Receive simulated uplink error, error: {}", e);
+                continue;
+            }
+        };
+
+        let uplink = match std::str::from_utf8(&buf[..size])
+            .map_err(anyhow::Error::from)
+            .and_then(Uplink::parse)
+        {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Parse simulated uplink error, error: {}", e);
+                continue;
+            }
+        };
+
+        if uplink_tx.send(uplink).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uplink_to_rx_packet() {
+        let uplink = Uplink::parse(
+            r#"{"frequency": 868100000, "spreading_factor": 9, "rssi": -80, "snr": 2.5, "payload": "01020304"}"#,
+        )
+        .unwrap();
+        let pkt = uplink.to_rx_packet(1234);
+
+        assert_eq!(868100000, pkt.freq_hz);
+        assert_eq!(1234, pkt.count_us);
+        assert_eq!(125000, pkt.bandwidth);
+        assert!(matches!(pkt.modulation, hal::Modulation::LoRa));
+        assert!(matches!(pkt.datarate, hal::DataRate::SF9));
+        assert!(matches!(pkt.coderate, hal::CodeRate::LoRa4_5));
+        assert_eq!(hal::CRC::CRCOk, pkt.status);
+        assert_eq!(-80.0, pkt.rssis);
+        assert_eq!(2.5, pkt.snr);
+        assert_eq!(4, pkt.size);
+        assert_eq!(&[1, 2, 3, 4], &pkt.payload[..4]);
+    }
+
+    #[test]
+    fn test_uplink_parse_invalid() {
+        assert!(Uplink::parse(r#"{"payload": "zz"}"#).is_err());
+        assert!(Uplink::parse(&format!(r#"{{"payload": "{}"}}"#, "00".repeat(256))).is_err());
+        assert!(Uplink::parse(r#"{"modulation": "FOO"}"#).is_err());
+    }
+
+    #[test]
+    fn test_tx_status() {
+        let mut sim = Simulator::new(&Configuration::default()).unwrap();
+
+        assert!(matches!(
+            sim.status(0, hal::StatusSelect::Tx).unwrap(),
+            hal::StatusReturn::Tx(hal::TxStatus::Free)
+        ));
+
+        let count_us = sim.get_instcnt().unwrap();
+        sim.send(&hal::TxPacket {
+            tx_mode: hal::TxMode::Timestamped,
+            count_us: count_us.wrapping_add(10_000_000),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert!(matches!(
+            sim.status(0, hal::StatusSelect::Tx).unwrap(),
+            hal::StatusReturn::Tx(hal::TxStatus::Scheduled)
+        ));
+        assert!(matches!(
+            sim.status(1, hal::StatusSelect::Tx).unwrap(),
+            hal::StatusReturn::Tx(hal::TxStatus::Free)
+        ));
+
+        sim.abort_tx(0).unwrap();
+        assert!(matches!(
+            sim.status(0, hal::StatusSelect::Tx).unwrap(),
+            hal::StatusReturn::Tx(hal::TxStatus::Free)
+        ));
+
+        sim.send(&hal::TxPacket {
+            tx_mode: hal::TxMode::Timestamped,
+            count_us: count_us.wrapping_add(10_000_000),
+            ..Default::default()
+        })
+        .unwrap();
+        sim.stop().unwrap();
+        assert!(matches!(
+            sim.status(0, hal::StatusSelect::Tx).unwrap(),
+            hal::StatusReturn::Tx(hal::TxStatus::Free)
+        ));
+    }
+}
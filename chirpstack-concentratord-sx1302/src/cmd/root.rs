@@ -1,42 +1,38 @@
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::path::Path;
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Instant;
 
 use anyhow::Result;
+use chirpstack_api::gw;
+use libconcentratord::error::ConfigurationError;
 use libconcentratord::signals;
 use libconcentratord::signals::Signal;
-use libconcentratord::{commands, events, gnss, jitqueue, reset};
+use libconcentratord::{capture, commands, config_state, events, gnss, jitqueue, metrics};
 use libloragw_sx1302::hal;
 
 use crate::config::vendor::ComType;
-use crate::{concentrator, config, handler, wrapper};
+use crate::{backend, config, handler, wrapper};
 
 pub fn run(
-    config: &config::Configuration,
+    config: &mut config::Configuration,
     stop_send: Sender<Signal>,
-    stop_receive: Rc<Receiver<Signal>>,
-) -> Result<Signal> {
+    stop_receive: Receiver<Signal>,
+) -> Result<()> {
     info!(
         "Starting Concentratord SX1302 (version: {}, docs: {})",
         config::VERSION,
         "https://www.chirpstack.io/docs/chirpstack-concentratord/"
     );
 
-    // reset concentrator
-    reset::reset().expect("concentrator reset failed");
+    // restore the configuration pushed by the network server
+    restore_config_state(config);
 
     // setup concentrator
-    concentrator::set_i2c_device_path(config)?;
-    concentrator::set_i2c_temp_sensor_addr(config)?;
-    concentrator::board_setconf(config)?;
-    concentrator::timestamp_setconf(config)?;
-    concentrator::txgain_setconf(config)?;
-    concentrator::sx1261_setconf(config)?;
-    concentrator::rxrf_setconf(config)?;
-    concentrator::rxif_setconf(config)?;
-    concentrator::start()?;
+    backend::setup(config)?;
+    backend::start(config)?;
 
     // setup static location
     gnss::set_static_location(
@@ -49,7 +45,7 @@ pub fn run(
     let gateway_id = if let Some(gateway_id) = config.gateway.gateway_id_bytes {
         gateway_id
     } else {
-        concentrator::get_eui().unwrap()
+        backend::get_eui().unwrap()
     };
 
     info!(
@@ -58,17 +54,274 @@ pub fn run(
     );
 
     // setup jit queue
-    let queue: jitqueue::Queue<wrapper::TxPacket> =
-        jitqueue::Queue::new(32, config.get_duty_cycle_tracker());
+    let mut queue: jitqueue::Queue<wrapper::TxPacket> = jitqueue::Queue::new(
+        &config.concentratord.jit_queue,
+        config.get_duty_cycle_tracker()?,
+        config.get_dwell_time(),
+    )
+    .with_rf_chains(config.get_tx_rf_chains());
+
+    // restore duty-cycle state
+    let state_file = &config.gateway.regulation.state_file;
+    if !state_file.is_empty() && Path::new(state_file).exists() {
+        info!("Restoring duty-cycle state, state_file: {}", state_file);
+        if let Err(e) = queue.load_duty_cycle_state(backend::get_instcnt()?, state_file) {
+            warn!("Restore duty-cycle state error, error: {}", e);
+        }
+    }
+
     let queue = Arc::new(Mutex::new(queue));
 
     // setup zeromq
-    events::bind_socket(&config.concentratord.api.event_bind).expect("bind event socket error");
-    let rep_sock = commands::get_socket(&config.concentratord.api.command_bind)
-        .expect("bind command socket error");
+    events::bind_socket(
+        &config.concentratord.api.event_bind,
+        config.concentratord.api.event_topics,
+    )
+    .expect("bind event socket error");
+    metrics::bind_http(&config.concentratord.api.metrics_bind)?;
+    capture::setup(
+        &config.concentratord.capture.file,
+        config.concentratord.capture.max_file_size,
+        config.concentratord.capture.max_files,
+        config.gateway.lorawan_public,
+        &gateway_id,
+    )?;
+    let rep_sock = commands::get_socket(
+        &config.concentratord.api.command_bind,
+        config.concentratord.api.command_socket_type,
+        config.concentratord.api.command_timeout,
+    )
+    .expect("bind command socket error");
+
+    // configuration used by the command thread, this is updated after the concentrator has
+    // been restarted with an updated configuration
+    let command_config = Arc::new(Mutex::new(config.clone()));
+
+    // command thread, this thread is kept running when the concentrator is restarted after a
+    // configuration update
+    let mut command_signal_pool = signals::SignalPool::default();
+    let command_thread = thread::spawn({
+        let config = Arc::clone(&command_config);
+        let queue = Arc::clone(&queue);
+        let stop_receive = command_signal_pool.new_receiver();
+        let stop_send = stop_send.clone();
+        let stop_send_err = stop_send.clone();
 
-    // setup threads
-    let mut signal_pool = signals::SignalPool::default();
+        move || {
+            if let Err(e) = handler::command::handle_loop(
+                config,
+                &gateway_id,
+                queue,
+                rep_sock,
+                stop_receive,
+                stop_send,
+            ) {
+                error!("Command handle loop error: {}", e);
+                stop_send_err.send(Signal::Stop).unwrap();
+            }
+
+            debug!("Command handle loop ended");
+        }
+    });
+
+    let mut restart_error = None;
+
+    loop {
+        let mut signal_pool = signals::SignalPool::default();
+        let threads = spawn_threads(config, gateway_id, &queue, &stop_send, &mut signal_pool);
+
+        let signal = wait_for_signal(config, &stop_receive);
+        signal_pool.send_signal(Signal::Stop);
+
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        let (pl, new_config, result_send) = match signal {
+            Some(v) => v,
+            None => break,
+        };
+
+        match restart_concentrator(config, new_config, &pl, &queue) {
+            Ok(result) => {
+                if result.is_ok() {
+                    *command_config.lock().unwrap() = config.clone();
+                }
+                let _ = result_send.send(result);
+            }
+            Err(e) => {
+                error!("Restart concentrator error, error: {}", e);
+                let _ = result_send.send(Err(ConfigurationError::Concentrator(e.to_string())));
+                restart_error = Some(e);
+                break;
+            }
+        }
+    }
+
+    // Configuration updates which are still pending are dropped, such that the command thread
+    // does not wait for their result.
+    drop(stop_receive);
+    command_signal_pool.send_signal(Signal::Stop);
+    command_thread.join().unwrap();
+
+    // The duty-cycle state has already been saved when the concentrator could not be
+    // restarted, as the concentrator is not running at this point.
+    if let Some(e) = restart_error {
+        return Err(e);
+    }
+
+    // save duty-cycle state
+    if let Err(e) = save_duty_cycle_state(config, &queue) {
+        error!("Save duty-cycle state error, error: {}", e);
+    }
+
+    backend::stop()?;
+
+    Ok(())
+}
+
+// Restart the concentrator with the updated configuration. Only the concentrator is
+// restarted, the sockets are kept and the queued downlinks are migrated to the new
+// concentrator counter. When the updated configuration can not be applied, the previous
+// configuration is restored. An error is returned when the concentrator can not be restarted
+// at all.
+fn restart_concentrator(
+    config: &mut config::Configuration,
+    new_config: config::Configuration,
+    pl: &gw::GatewayConfiguration,
+    queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+) -> Result<Result<(), ConfigurationError>> {
+    info!("Restarting concentrator with updated configuration");
+
+    // Save the duty-cycle state while the concentrator is still running, as the state can not
+    // be saved afterwards when the concentrator can not be restarted.
+    if let Err(e) = save_duty_cycle_state(config, queue) {
+        error!("Save duty-cycle state error, error: {}", e);
+    }
+
+    let count_us = backend::get_instcnt()?;
+    let stopped_at = Instant::now();
+    backend::stop()?;
+
+    let result = match backend::start(&new_config) {
+        Ok(_) => {
+            *config = new_config;
+            save_config_state(config, pl);
+            Ok(())
+        }
+        Err(e) => {
+            error!(
+                "Start concentrator with updated configuration error, restoring previous configuration, error: {}",
+                e
+            );
+            let _ = backend::stop();
+            backend::start(config)?;
+            Err(ConfigurationError::Concentrator(e.to_string()))
+        }
+    };
+
+    queue
+        .lock()
+        .map_err(|_| anyhow!("Lock queue error"))?
+        .migrate(count_us, backend::get_instcnt()?, stopped_at.elapsed());
+
+    Ok(result)
+}
+
+fn save_duty_cycle_state(
+    config: &config::Configuration,
+    queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+) -> Result<()> {
+    let state_file = &config.gateway.regulation.state_file;
+    if state_file.is_empty() {
+        return Ok(());
+    }
+
+    queue
+        .lock()
+        .map_err(|_| anyhow!("Lock queue error"))?
+        .save_duty_cycle_state(backend::get_instcnt()?, state_file)
+}
+
+// Restore the configuration which was last pushed by the network server. When the state file
+// can not be used, the channel configuration from the configuration file is kept.
+fn restore_config_state(config: &mut config::Configuration) {
+    let path = config.gateway.config_state_file.clone();
+    if path.is_empty() {
+        return;
+    }
+
+    let pl = match config_state::load(&path) {
+        Ok(Some(v)) => v,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Load configuration state error, error: {}", e);
+            return;
+        }
+    };
+
+    match handler::config::validate_configuration(config, &pl) {
+        Ok(v) => {
+            info!(
+                "Configuration state restored, config_state_file: {}, config_version: {}",
+                path, v.gateway.config_version
+            );
+            *config = v;
+        }
+        Err(e) => warn!("Configuration state rejected, error: {}", e),
+    }
+}
+
+// Save the applied configuration, such that it can be restored on startup.
+fn save_config_state(config: &config::Configuration, pl: &gw::GatewayConfiguration) {
+    if config.gateway.config_state_file.is_empty() {
+        return;
+    }
+
+    if let Err(e) = config_state::save(&config.gateway.config_state_file, pl) {
+        error!("Save configuration state error, error: {}", e);
+    }
+}
+
+// Configuration update: the received gateway configuration, the validated configuration and
+// the sender for the result of applying it.
+type ConfigurationUpdate = (
+    gw::GatewayConfiguration,
+    config::Configuration,
+    Sender<Result<(), ConfigurationError>>,
+);
+
+// Wait for the stop signal or a configuration update. A configuration update is validated
+// before it is returned, an invalid update is rejected without interrupting the concentrator.
+fn wait_for_signal(
+    config: &config::Configuration,
+    stop_receive: &Receiver<Signal>,
+) -> Option<ConfigurationUpdate> {
+    loop {
+        match stop_receive.recv() {
+            Ok(Signal::Configuration(pl, result_send)) => {
+                let mut new_config = config.clone();
+                match handler::config::update_configuration(&mut new_config, &pl) {
+                    Ok(_) => return Some((pl, new_config, result_send)),
+                    Err(e) => {
+                        let _ = result_send.send(Err(e));
+                    }
+                }
+            }
+            Ok(Signal::Stop) | Err(_) => return None,
+        }
+    }
+}
+
+// Spawn the threads which interact with the concentrator. These are stopped and spawned
+// again when the concentrator is restarted.
+fn spawn_threads(
+    config: &config::Configuration,
+    gateway_id: [u8; 8],
+    queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+    stop_send: &Sender<Signal>,
+    signal_pool: &mut signals::SignalPool,
+) -> Vec<thread::JoinHandle<()>> {
     let mut threads: Vec<thread::JoinHandle<()>> = vec![];
 
     // uplink thread
@@ -95,13 +348,15 @@ pub fn run(
 
     // jit thread
     threads.push(thread::spawn({
-        let queue = Arc::clone(&queue);
+        let queue = Arc::clone(queue);
         let antenna_gain_dbi = config.gateway.antenna_gain;
         let stop_receive = signal_pool.new_receiver();
         let stop_send = stop_send.clone();
 
         move || {
-            if let Err(e) = handler::jit::jit_loop(queue, antenna_gain_dbi, stop_receive) {
+            if let Err(e) =
+                handler::jit::jit_loop(queue, &gateway_id, antenna_gain_dbi, stop_receive)
+            {
                 error!("JIT loop error: {}", e);
                 stop_send.send(Signal::Stop).unwrap();
             }
@@ -110,35 +365,11 @@ pub fn run(
         }
     }));
 
-    // command thread
-    threads.push(thread::spawn({
-        let vendor_config = config.gateway.model_config.clone();
-        let queue = Arc::clone(&queue);
-        let stop_receive = signal_pool.new_receiver();
-        let stop_send = stop_send.clone();
-        let stop_send_err = stop_send.clone();
-
-        move || {
-            if let Err(e) = handler::command::handle_loop(
-                &vendor_config,
-                &gateway_id,
-                queue,
-                rep_sock,
-                stop_receive,
-                stop_send,
-            ) {
-                error!("Command handle loop error: {}", e);
-                stop_send_err.send(Signal::Stop).unwrap();
-            }
-
-            debug!("Command handle loop ended");
-        }
-    }));
-
     // stats thread
     threads.push(thread::spawn({
         let stats_interval = config.concentratord.stats_interval;
-        let queue = Arc::clone(&queue);
+        let state_file = config.gateway.regulation.state_file.clone();
+        let queue = Arc::clone(queue);
 
         // In case of USB, there is no I2C configuration.
         let get_temperature = config.gateway.model_config.com_type == ComType::Usb
@@ -167,6 +398,7 @@ pub fn run(
                 stop_receive,
                 metadata,
                 queue,
+                &state_file,
             ) {
                 error!("Stats loop error: {}", e);
                 stop_send.send(Signal::Stop).unwrap();
@@ -176,6 +408,32 @@ pub fn run(
         }
     }));
 
+    // spectral scan thread
+    if config.gateway.spectral_scan.enable {
+        if config.gateway.model_config.sx1261_config.enable {
+            threads.push(thread::spawn({
+                let spectral_scan_config = config.gateway.spectral_scan.clone();
+                let stop_receive = signal_pool.new_receiver();
+                let stop_send = stop_send.clone();
+
+                move || {
+                    if let Err(e) = handler::spectral_scan::scan_loop(
+                        &gateway_id,
+                        &spectral_scan_config,
+                        stop_receive,
+                    ) {
+                        error!("Spectral scan loop error: {}", e);
+                        stop_send.send(Signal::Stop).unwrap();
+                    }
+
+                    debug!("Spectral scan loop ended");
+                }
+            }));
+        } else {
+            warn!("Spectral scan is enabled, but is not supported by gateway model");
+        }
+    }
+
     if config.gateway.model_config.gnss != gnss::Device::None {
         // gps thread
         threads.push(thread::spawn({
@@ -197,7 +455,7 @@ pub fn run(
         if !config.gateway.beacon.frequencies.is_empty() {
             threads.push(thread::spawn({
                 let beacon_config = config.gateway.beacon.clone();
-                let queue = Arc::clone(&queue);
+                let queue = Arc::clone(queue);
                 let stop_receive = signal_pool.new_receiver();
                 let stop_send = stop_send.clone();
 
@@ -215,14 +473,5 @@ pub fn run(
         }
     }
 
-    let stop_signal = stop_receive.recv().unwrap();
-    signal_pool.send_signal(stop_signal.clone());
-
-    for t in threads {
-        t.join().unwrap();
-    }
-
-    concentrator::stop()?;
-
-    Ok(stop_signal)
+    threads
 }
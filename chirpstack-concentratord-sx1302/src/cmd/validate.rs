@@ -0,0 +1,323 @@
+use libconcentratord::error::ConfigurationError;
+use libconcentratord::validate::Validator;
+
+use super::super::config::{self, helpers, vendor::ComType};
+use super::super::handler::spectral_scan;
+
+const MAX_LBT_CHANNELS: usize = 16;
+
+/// Validate the configuration files and print the problems found. This does not access the
+/// concentrator hardware. It returns the process exit code.
+pub fn run(filenames: &[String]) -> i32 {
+    let mut v = Validator::new(filenames);
+    if let Some(config) = v.parse::<config::Configuration>() {
+        validate(&mut v, config);
+    }
+    v.report()
+}
+
+fn validate(v: &mut Validator, mut config: config::Configuration) {
+    if config
+        .concentratord
+        .log_level
+        .parse::<log::Level>()
+        .is_err()
+    {
+        v.error("concentratord.log_level", "Invalid log level");
+    }
+
+    if !config.gateway.gateway_id.is_empty() {
+        match hex::decode(&config.gateway.gateway_id) {
+            Ok(b) if b.len() == 8 => {}
+            Ok(_) => v.error("gateway.gateway_id", "Must be exactly 8 bytes"),
+            Err(e) => v.error("gateway.gateway_id", e),
+        }
+    }
+
+    if let Err(e) = helpers::set_channel_plan(&mut config) {
+        match config.gateway.sub_band {
+            Some(_) => v.error("gateway.sub_band", e),
+            None => v.error("gateway.region", e),
+        }
+    }
+
+    if let Err(e) = config.concentratord.jit_queue.validate() {
+        v.error("concentratord.jit_queue", e);
+    }
+
+    config.gateway.model_config = match config::vendor::new(&config) {
+        Ok(v) => v,
+        Err(e) => {
+            match config.gateway.model_file.is_empty() {
+                true => v.error("gateway.model", format!("{:#}", e)),
+                false => v.error("gateway.model_file", format!("{:#}", e)),
+            }
+            return;
+        }
+    };
+
+    validate_channels(v, &config);
+    validate_lbt(v, &config);
+    validate_spectral_scan(v, &config);
+    validate_pins(v, &config);
+}
+
+fn validate_channels(v: &mut Validator, config: &config::Configuration) {
+    let concentrator = &config.gateway.concentrator;
+    let model_config = &config.gateway.model_config;
+
+    let mut channels: Vec<(&str, u32, u32)> = concentrator
+        .multi_sf_channels
+        .iter()
+        .filter(|freq| **freq != 0)
+        .map(|freq| {
+            (
+                "gateway.concentrator.multi_sf_channels",
+                *freq,
+                model_config.lora_multi_sf_bandwidth,
+            )
+        })
+        .collect();
+    if concentrator.lora_std.frequency != 0 {
+        if !(5..=12).contains(&concentrator.lora_std.spreading_factor) {
+            v.error(
+                "gateway.concentrator.lora_std.spreading_factor",
+                format!(
+                    "Unsupported spreading-factor: {}",
+                    concentrator.lora_std.spreading_factor
+                ),
+            );
+        }
+        channels.push((
+            "gateway.concentrator.lora_std.frequency",
+            concentrator.lora_std.frequency,
+            concentrator.lora_std.bandwidth,
+        ));
+    }
+    if concentrator.fsk.frequency != 0 {
+        channels.push((
+            "gateway.concentrator.fsk.frequency",
+            concentrator.fsk.frequency,
+            concentrator.fsk.bandwidth,
+        ));
+    }
+
+    // all channels must fit within the bandwidth of the radios
+    match helpers::get_radio_frequencies(config) {
+        Ok(radio_freqs) => {
+            for (key, freq, bandwidth) in &channels {
+                if let Err(e) = helpers::get_radio_for_channel(&radio_freqs, *freq, *bandwidth) {
+                    v.error(key, e);
+                }
+            }
+        }
+        Err(e) => {
+            let key = match &e {
+                ConfigurationError::ChannelOutsideRadioBandwidth(freq) => channels
+                    .iter()
+                    .find(|(_, f, _)| f == freq)
+                    .map(|(key, _, _)| *key),
+                _ => None,
+            };
+            v.error(key.unwrap_or("gateway.concentrator"), e);
+        }
+    }
+
+    // all channels must be within the TX frequency range of the region
+    for (key, freq, bandwidth) in &channels {
+        if !in_tx_range(model_config, *freq, *bandwidth) {
+            v.error(
+                key,
+                format!("Channel is outside the TX frequency range, freq: {}", freq),
+            );
+        }
+    }
+
+    for freq in &config.gateway.beacon.frequencies {
+        if !in_tx_range(model_config, *freq, config.gateway.beacon.bandwidth) {
+            v.error(
+                "gateway.beacon.frequencies",
+                format!("Beacon is outside the TX frequency range, freq: {}", freq),
+            );
+        }
+    }
+}
+
+fn validate_lbt(v: &mut Validator, config: &config::Configuration) {
+    let lbt = &config.gateway.lbt;
+    if !lbt.enable || lbt.channels.is_empty() {
+        return;
+    }
+
+    if !config.gateway.model_config.sx1261_config.enable {
+        v.error(
+            "gateway.lbt.enable",
+            "LBT is not supported by gateway model",
+        );
+    }
+
+    if lbt.channels.len() > MAX_LBT_CHANNELS {
+        v.error(
+            "gateway.lbt.channels",
+            format!("Max. number of LBT channels is {}", MAX_LBT_CHANNELS),
+        );
+    }
+
+    for c in &lbt.channels {
+        if c.scan_time_us != 128 && c.scan_time_us != 5000 {
+            v.error(
+                "gateway.lbt.channels",
+                format!(
+                    "Invalid scan_time_us, freq: {}, scan_time_us: {} (expected 128 or 5000)",
+                    c.frequency, c.scan_time_us
+                ),
+            );
+        }
+
+        if !in_tx_range(&config.gateway.model_config, c.frequency, c.bandwidth) {
+            v.error(
+                "gateway.lbt.channels",
+                format!(
+                    "LBT channel is outside the TX frequency range, freq: {}",
+                    c.frequency
+                ),
+            );
+        }
+    }
+}
+
+fn validate_spectral_scan(v: &mut Validator, config: &config::Configuration) {
+    let conf = &config.gateway.spectral_scan;
+    if !conf.enable {
+        return;
+    }
+
+    if !config.gateway.model_config.sx1261_config.enable {
+        v.error(
+            "gateway.spectral_scan.enable",
+            "Spectral scan is not supported by gateway model",
+        );
+    }
+
+    if let Err(e) =
+        spectral_scan::get_frequencies(conf.frequency_start, conf.frequency_step, conf.channels)
+    {
+        v.error("gateway.spectral_scan", e);
+    }
+}
+
+fn validate_pins(v: &mut Validator, config: &config::Configuration) {
+    let gw = &config.gateway;
+    let model_config = &gw.model_config;
+
+    // The pins are only used for SPI concentrators.
+    if config.simulator.enabled || model_config.com_type != ComType::Spi {
+        return;
+    }
+
+    let pins = [
+        (
+            "gateway.sx1302_reset_pin",
+            &model_config.sx1302_reset_pin,
+            gw.sx1302_reset_chip.is_some() || gw.sx1302_reset_pin.is_some(),
+        ),
+        (
+            "gateway.sx1302_power_en_pin",
+            &model_config.sx1302_power_en_pin,
+            gw.sx1302_power_en_chip.is_some() || gw.sx1302_power_en_pin.is_some(),
+        ),
+        (
+            "gateway.sx1261_reset_pin",
+            &model_config.sx1261_reset_pin,
+            gw.sx1261_reset_chip.is_some() || gw.sx1261_reset_pin.is_some(),
+        ),
+        ("gateway.model", &model_config.ad5338r_reset_pin, false),
+    ];
+
+    let mut used: Vec<(&str, &(String, u32))> = Vec::new();
+    for (key, pin, configured) in pins {
+        let pin = match pin {
+            Some(v) => v,
+            None => {
+                if configured {
+                    v.error(key, "Pin is not used by gateway model");
+                }
+                continue;
+            }
+        };
+
+        if !pin.0.starts_with("/dev/") {
+            v.error(key, format!("Invalid GPIO chip: {}", pin.0));
+        }
+
+        if let Some((other, _)) = used.iter().find(|(_, p)| *p == pin) {
+            v.error(
+                key,
+                format!("GPIO {}:{} is already used by {}", pin.0, pin.1, other),
+            );
+        }
+
+        used.push((key, pin));
+    }
+}
+
+fn in_tx_range(model_config: &config::vendor::Configuration, freq: u32, bandwidth: u32) -> bool {
+    let min = freq.saturating_sub(bandwidth / 2);
+    let max = freq + (bandwidth / 2);
+
+    let mut ranges = model_config
+        .radio_config
+        .iter()
+        .filter(|r| r.tx_enable)
+        .flat_map(|r| r.tx_min_max_freqs.iter())
+        .peekable();
+
+    // without TX ranges there is nothing to check against
+    ranges.peek().is_none() || ranges.any(|(tx_min, tx_max)| min >= *tx_min && max <= *tx_max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let mut config = config::Configuration::default();
+        config.gateway.region = Some(config::Region::EU868);
+        config.gateway.model = "semtech_sx1302c868gw1".into();
+        config.gateway.concentrator.multi_sf_channels = [868100000, 868300000, 0, 0, 0, 0, 0, 0];
+
+        let mut v = Validator::new(&[]);
+        validate(&mut v, config.clone());
+        assert!(v.diagnostics().is_empty());
+
+        config.gateway.concentrator.multi_sf_channels[2] = 915000000;
+        config.gateway.lbt.enable = true;
+        config.gateway.lbt.channels = vec![config::LbtChannel {
+            frequency: 868100000,
+            bandwidth: 125000,
+            scan_time_us: 100,
+            transmit_time_ms: 4000,
+        }];
+        config.gateway.sx1261_reset_pin = Some(23);
+        config.gateway.spectral_scan.enable = true;
+        config.gateway.spectral_scan.channels = 0;
+
+        let mut v = Validator::new(&[]);
+        validate(&mut v, config);
+        assert_eq!(
+            vec![
+                "gateway.concentrator.multi_sf_channels: Channel is outside the TX frequency range, freq: 915000000",
+                "gateway.lbt.enable: LBT is not supported by gateway model",
+                "gateway.lbt.channels: Invalid scan_time_us, freq: 868100000, scan_time_us: 100 (expected 128 or 5000)",
+                "gateway.spectral_scan.enable: Spectral scan is not supported by gateway model",
+                "gateway.spectral_scan: Number of channels must be between 1 and 64, channels: 0",
+                "gateway.sx1261_reset_pin: Pin is not used by gateway model",
+            ],
+            v.diagnostics()
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<String>>()
+        );
+    }
+}
@@ -225,13 +225,16 @@ pub fn rxif_setconf(config: &Configuration) -> Result<()> {
 }
 
 pub fn sx1261_setconf(config: &Configuration) -> Result<()> {
-    if !config.gateway.lbt.enable || config.gateway.lbt.channels.is_empty() {
-        info!("LBT is disabled");
+    let lbt_enabled = config.gateway.lbt.enable && !config.gateway.lbt.channels.is_empty();
+    let spectral_scan_enabled = config.gateway.spectral_scan.enable;
+
+    if !lbt_enabled && !spectral_scan_enabled {
+        info!("LBT and spectral scan are disabled");
         return Ok(());
     }
 
     if !config.gateway.model_config.sx1261_config.enable {
-        warn!("LBT is enabled, but is not supported by gateway model");
+        warn!("LBT or spectral scan is enabled, but is not supported by gateway model");
         return Ok(());
     }
 
@@ -239,7 +242,10 @@ pub fn sx1261_setconf(config: &Configuration) -> Result<()> {
         return Err(anyhow!("Max. number of LBT channels is 16"));
     }
 
-    info!("Configuring LBT");
+    info!(
+        "Configuring SX1261, lbt: {}, spectral_scan: {}",
+        lbt_enabled, spectral_scan_enabled
+    );
 
     let sx1261_conf = hal::SX1261Config {
         enable: config.gateway.model_config.sx1261_config.enable,
@@ -251,7 +257,7 @@ pub fn sx1261_setconf(config: &Configuration) -> Result<()> {
             .unwrap_or_default(),
         rssi_offset: config.gateway.model_config.sx1261_config.rssi_offset,
         lbt_config: hal::LbtConfig {
-            enable: config.gateway.lbt.enable,
+            enable: lbt_enabled,
             rssi_target: config.gateway.lbt.rssi_target,
             channels: config
                 .gateway
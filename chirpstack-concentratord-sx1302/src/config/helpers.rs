@@ -1,4 +1,6 @@
 use anyhow::Result;
+use libconcentratord::error::ConfigurationError;
+use libconcentratord::region::{self, ChannelPlan};
 
 use super::Configuration;
 
@@ -25,7 +27,7 @@ fn get_radio_bandwidth(channel_bw: u32) -> u32 {
     }
 }
 
-pub fn get_radio_frequencies(config: &Configuration) -> Result<Vec<u32>> {
+pub fn get_radio_frequencies(config: &Configuration) -> Result<Vec<u32>, ConfigurationError> {
     let mut channels: Vec<ChannelConfiguration> = Vec::new();
     let mut radios = vec![0; config.gateway.model_config.radio_count];
 
@@ -77,9 +79,7 @@ pub fn get_radio_frequencies(config: &Configuration) -> Result<Vec<u32>> {
 
             // the channel does not fit
             if i == (config.gateway.model_config.radio_count - 1) {
-                return Err(anyhow!(
-                    "the channels do not fit within the bandwidth of the two radios"
-                ));
+                return Err(ConfigurationError::ChannelOutsideRadioBandwidth(c.freq_hz));
             }
         }
     }
@@ -87,7 +87,11 @@ pub fn get_radio_frequencies(config: &Configuration) -> Result<Vec<u32>> {
     Ok(radios)
 }
 
-pub fn get_radio_for_channel(radios: &[u32], freq_hz: u32, bandwidth: u32) -> Result<usize> {
+pub fn get_radio_for_channel(
+    radios: &[u32],
+    freq_hz: u32,
+    bandwidth: u32,
+) -> Result<usize, ConfigurationError> {
     let chan_min = freq_hz - (bandwidth / 2);
     let chan_max = freq_hz + (bandwidth / 2);
 
@@ -100,12 +104,81 @@ pub fn get_radio_for_channel(radios: &[u32], freq_hz: u32, bandwidth: u32) -> Re
         }
     }
 
-    Err(anyhow!("channel does not fit in radio bandwidth"))
+    Err(ConfigurationError::ChannelOutsideRadioBandwidth(freq_hz))
+}
+
+/// Set the concentrator channels from the channel plan of the region (and sub-band), unless
+/// the channels are configured explicitly.
+pub fn set_channel_plan(config: &mut super::Configuration) -> Result<()> {
+    if config.gateway.concentrator != super::Concentrator::default() {
+        if config.gateway.sub_band.is_some() {
+            return Err(anyhow!(
+                "sub_band can not be combined with explicitly configured channels"
+            ));
+        }
+        return Ok(());
+    }
+
+    let region = match config.gateway.region {
+        Some(v) => v,
+        None => {
+            if config.gateway.sub_band.is_some() {
+                return Err(anyhow!("sub_band requires a region"));
+            }
+            return Ok(());
+        }
+    };
+
+    let plan = get_channel_plan(region, config.gateway.sub_band)?;
+    let concentrator = &mut config.gateway.concentrator;
+
+    concentrator.multi_sf_channels = plan.multi_sf_channels;
+    if let Some(v) = plan.lora_std {
+        concentrator.lora_std = super::LoRaStdChannel {
+            frequency: v.frequency,
+            bandwidth: v.bandwidth,
+            spreading_factor: v.spreading_factor,
+            ..Default::default()
+        };
+    }
+    if let Some(v) = plan.fsk {
+        concentrator.fsk = super::FSKChannel {
+            frequency: v.frequency,
+            bandwidth: v.bandwidth,
+            datarate: v.datarate,
+        };
+    }
+
+    Ok(())
+}
+
+fn get_channel_plan(region: super::Region, sub_band: Option<u8>) -> Result<ChannelPlan> {
+    use super::Region;
+
+    match (region, sub_band) {
+        (Region::US915, Some(v)) => region::us915::channel_plan(v),
+        (Region::AU915, Some(v)) => region::au915::channel_plan(v),
+        (Region::CN470, Some(v)) => region::cn470::channel_plan(v),
+        (Region::US915 | Region::AU915 | Region::CN470, None) => {
+            Err(anyhow!("Region {} requires a sub_band", region))
+        }
+        (_, Some(_)) => Err(anyhow!("Region {} does not have sub-bands", region)),
+        (Region::EU868, None) => Ok(region::eu868::CHANNEL_PLAN),
+        (Region::EU433, None) => Ok(region::eu433::CHANNEL_PLAN),
+        (Region::AS923, None) => Ok(region::as923::CHANNEL_PLAN),
+        (Region::AS923_2, None) => Ok(region::as923_2::CHANNEL_PLAN),
+        (Region::AS923_3, None) => Ok(region::as923_3::CHANNEL_PLAN),
+        (Region::AS923_4, None) => Ok(region::as923_4::CHANNEL_PLAN),
+        (Region::KR920, None) => Ok(region::kr920::CHANNEL_PLAN),
+        (Region::IN865, None) => Ok(region::in865::CHANNEL_PLAN),
+        (Region::RU864, None) => Ok(region::ru864::CHANNEL_PLAN),
+        (Region::CN779, None) => Err(anyhow!("No channel plan for region: {}", region)),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::super::vendor::semtech;
+    use super::super::vendor;
 
     #[test]
     fn test_get_radio_frequencies() {
@@ -164,7 +237,8 @@ mod tests {
             config.gateway.concentrator.lora_std.bandwidth = lora_std_bw;
             config.gateway.concentrator.fsk.frequency = fsk_freq;
             config.gateway.concentrator.fsk.bandwidth = fsk_bw;
-            config.gateway.model_config = semtech::sx1302c868gw1::new(&config).unwrap();
+            config.gateway.model = "semtech_sx1302c868gw1".into();
+            config.gateway.model_config = vendor::new(&config).unwrap();
 
             let radios = super::get_radio_frequencies(&config).unwrap();
             assert_eq!(radios, expected);
@@ -180,4 +254,35 @@ mod tests {
             assert_eq!(radio, expected);
         }
     }
+
+    #[test]
+    fn test_set_channel_plan() {
+        let mut config: super::super::Configuration = Default::default();
+        config.gateway.region = Some(super::super::Region::US915);
+        config.gateway.sub_band = Some(1);
+        super::set_channel_plan(&mut config).unwrap();
+
+        assert_eq!(
+            [
+                903900000, 904100000, 904300000, 904500000, 904700000, 904900000, 905100000,
+                905300000,
+            ],
+            config.gateway.concentrator.multi_sf_channels
+        );
+        assert_eq!(904600000, config.gateway.concentrator.lora_std.frequency);
+        assert_eq!(500000, config.gateway.concentrator.lora_std.bandwidth);
+        assert_eq!(8, config.gateway.concentrator.lora_std.spreading_factor);
+
+        // explicitly configured channels are not overwritten
+        let mut config: super::super::Configuration = Default::default();
+        config.gateway.region = Some(super::super::Region::EU868);
+        config.gateway.concentrator.multi_sf_channels[0] = 868100000;
+        super::set_channel_plan(&mut config).unwrap();
+        assert_eq!(0, config.gateway.concentrator.multi_sf_channels[1]);
+
+        // the sub-band is required for US915
+        let mut config: super::super::Configuration = Default::default();
+        config.gateway.region = Some(super::super::Region::US915);
+        assert!(super::set_channel_plan(&mut config).is_err());
+    }
 }
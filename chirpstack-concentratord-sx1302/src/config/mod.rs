@@ -1,10 +1,12 @@
-use std::{env, fmt, fs};
+use std::fmt;
 
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
-use libconcentratord::gnss;
-use libconcentratord::regulation::{standard, tracker::Tracker};
+use libconcentratord::error::ConfigFileError;
+use libconcentratord::regulation::{dwelltime, standard, tracker::Tracker};
+use libconcentratord::{commands, config_file, gnss, jitqueue};
 
 pub mod helpers;
 pub mod vendor;
@@ -34,7 +36,7 @@ impl fmt::Display for Region {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct Concentratord {
     pub log_level: String,
@@ -43,6 +45,11 @@ pub struct Concentratord {
     pub stats_interval: Duration,
     pub disable_crc_filter: bool,
     pub api: Api,
+    pub capture: Capture,
+    pub jit_queue: jitqueue::Configuration,
+
+    #[serde(skip)]
+    pub log_level_filter: log::LevelFilter,
 }
 
 impl Default for Concentratord {
@@ -53,27 +60,57 @@ impl Default for Concentratord {
             stats_interval: Duration::from_secs(30),
             disable_crc_filter: false,
             api: Default::default(),
+            capture: Default::default(),
+            jit_queue: Default::default(),
+            log_level_filter: log::LevelFilter::Info,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Capture {
+    pub file: String,
+    pub max_file_size: u64,
+    pub max_files: usize,
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Capture {
+            file: "".into(),
+            max_file_size: 10 * 1024 * 1024,
+            max_files: 5,
         }
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct Api {
     pub event_bind: String,
+    pub event_topics: bool,
     pub command_bind: String,
+    pub command_socket_type: commands::SocketType,
+    #[serde(with = "humantime_serde")]
+    pub command_timeout: Duration,
+    pub metrics_bind: String,
 }
 
 impl Default for Api {
     fn default() -> Self {
         Api {
             event_bind: "ipc:///tmp/concentratord_event".to_string(),
+            event_topics: false,
             command_bind: "ipc:///tmp/concentratord_command".to_string(),
+            command_socket_type: commands::SocketType::Rep,
+            command_timeout: Duration::from_secs(1),
+            metrics_bind: "".to_string(),
         }
     }
 }
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize, Clone)]
 pub struct Location {
     pub latitude: f64,
     pub longitude: f64,
@@ -81,21 +118,26 @@ pub struct Location {
     pub altitude: f32,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct Gateway {
     pub antenna_gain: i8,
     pub lorawan_public: bool,
 
     pub region: Option<Region>,
+    pub sub_band: Option<u8>,
     pub model: String,
+    pub model_file: String,
     pub model_flags: Vec<String>,
     pub gateway_id: String,
 
     pub time_fallback_enabled: bool,
+    pub config_state_file: String,
     pub concentrator: Concentrator,
     pub beacon: Beacon,
     pub lbt: Lbt,
+    pub spectral_scan: SpectralScan,
+    pub regulation: Regulation,
     pub location: Location,
 
     pub fine_timestamp: FineTimestamp,
@@ -128,13 +170,18 @@ impl Default for Gateway {
             antenna_gain: 2,
             lorawan_public: true,
             region: None,
+            sub_band: None,
             model: "".into(),
+            model_file: "".into(),
             model_flags: vec![],
             gateway_id: "".into(),
             time_fallback_enabled: false,
+            config_state_file: "".into(),
             concentrator: Concentrator::default(),
             beacon: Beacon::default(),
             lbt: Lbt::default(),
+            spectral_scan: SpectralScan::default(),
+            regulation: Regulation::default(),
             location: Location::default(),
             fine_timestamp: FineTimestamp::default(),
             sx1302_reset_chip: None,
@@ -217,7 +264,7 @@ impl Gateway {
     }
 }
 
-#[derive(Default, Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Default, Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Concentrator {
     pub multi_sf_channels: [u32; 8],
     #[serde(default)]
@@ -226,7 +273,7 @@ pub struct Concentrator {
     pub fsk: FSKChannel,
 }
 
-#[derive(Default, Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Default, Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct LoRaStdChannel {
     pub frequency: u32,
     pub bandwidth: u32,
@@ -241,7 +288,7 @@ pub struct LoRaStdChannel {
     pub implicit_coderate: String,
 }
 
-#[derive(Default, Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Default, Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct FSKChannel {
     pub frequency: u32,
     pub bandwidth: u32,
@@ -273,7 +320,32 @@ pub struct LbtChannel {
     pub transmit_time_ms: u16,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct SpectralScan {
+    pub enable: bool,
+    pub frequency_start: u32,
+    pub frequency_step: u32,
+    pub channels: u32,
+    pub nb_scan: u16,
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+}
+
+impl Default for SpectralScan {
+    fn default() -> Self {
+        SpectralScan {
+            enable: false,
+            frequency_start: 867100000,
+            frequency_step: 200000,
+            channels: 8,
+            nb_scan: 2000,
+            interval: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct FineTimestamp {
     pub enable: bool,
@@ -289,23 +361,130 @@ impl Default for FineTimestamp {
     }
 }
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Simulator {
+    pub enabled: bool,
+    pub uplink_file: String,
+    pub uplink_bind: String,
+    pub downlink_file: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Regulation {
+    pub standard: Option<standard::Standard>,
+    #[serde(with = "humantime_serde")]
+    pub window_time: Duration,
+    pub bands: Vec<standard::Band>,
+    pub tx_power_policy: standard::TxPowerPolicy,
+    pub enforce_dwell_time: bool,
+    pub dwell_time_limits: Vec<dwelltime::Limit>,
+    pub state_file: String,
+}
+
+impl Default for Regulation {
+    fn default() -> Self {
+        Regulation {
+            standard: None,
+            window_time: Duration::from_secs(60 * 60),
+            bands: vec![],
+            tx_power_policy: standard::TxPowerPolicy::default(),
+            enforce_dwell_time: false,
+            dwell_time_limits: vec![],
+            state_file: "".into(),
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize, Clone)]
 #[serde(default = "example_configuration")]
 pub struct Configuration {
     pub concentratord: Concentratord,
     pub gateway: Gateway,
+    pub simulator: Simulator,
 }
 
 impl Configuration {
-    pub fn get_duty_cycle_tracker(&self) -> Option<Tracker> {
+    pub fn get_duty_cycle_tracker(&self) -> Result<Option<Tracker>> {
+        let regulation = &self.gateway.regulation;
+
+        // A custom band table overrides the band table of the standard.
+        let config = if !regulation.bands.is_empty() {
+            standard::Configuration::new_custom(regulation.bands.clone(), regulation.window_time)?
+        } else {
+            let standard = match regulation.standard {
+                Some(v) => v,
+                None => match self.gateway.region {
+                    Some(Region::EU868) | Some(Region::EU433) => {
+                        standard::Standard::ETSI_EN_300_220
+                    }
+                    Some(Region::RU864) => standard::Standard::SCRF_07_20_03_001,
+                    Some(Region::IN865) => standard::Standard::WPC_GSR_1048,
+                    _ => return Ok(None),
+                },
+            };
+
+            standard::get(standard)
+        };
+
+        Ok(Some(Tracker::new(
+            config,
+            self.gateway.model_config.enforce_duty_cycle,
+            regulation.tx_power_policy,
+        )))
+    }
+
+    pub fn get_dwell_time(&self) -> Option<dwelltime::Configuration> {
+        let regulation = &self.gateway.regulation;
+
+        if !regulation.enforce_dwell_time {
+            return None;
+        }
+
+        // Custom dwell-time limits override the limits of the region.
+        if !regulation.dwell_time_limits.is_empty() {
+            return Some(dwelltime::Configuration::new(
+                regulation.dwell_time_limits.clone(),
+            ));
+        }
+
         match self.gateway.region {
-            Some(Region::EU868) => Some(Tracker::new(
-                standard::get(standard::Standard::ETSI_EN_300_220),
-                self.gateway.model_config.enforce_duty_cycle,
-            )),
+            Some(Region::US915) => Some(dwelltime::Configuration::us915()),
+            Some(Region::AS923)
+            | Some(Region::AS923_2)
+            | Some(Region::AS923_3)
+            | Some(Region::AS923_4) => Some(dwelltime::Configuration::as923()),
             _ => None,
         }
     }
+
+    /// Returns the TX capable RF chains which can transmit at the same time. This is empty
+    /// unless the gateway model supports concurrent TX (concurrent_tx_rf_chains) and has
+    /// multiple TX capable RF chains.
+    pub fn get_tx_rf_chains(&self) -> Vec<jitqueue::RfChain> {
+        let model_config = &self.gateway.model_config;
+        if !model_config.concurrent_tx_rf_chains {
+            return Vec::new();
+        }
+
+        let rf_chains: Vec<jitqueue::RfChain> = model_config
+            .radio_config
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.tx_enable)
+            .map(|(i, r)| jitqueue::RfChain {
+                rf_chain: i as u8,
+                tx_min_max_freqs: r.tx_min_max_freqs.clone(),
+            })
+            .collect();
+
+        if rf_chains.len() < 2 {
+            return Vec::new();
+        }
+
+        rf_chains
+    }
 }
 
 fn example_configuration() -> Configuration {
@@ -340,57 +519,140 @@ fn example_configuration() -> Configuration {
     }
 }
 
-pub fn get(filenames: Vec<String>) -> Configuration {
-    let mut content: String = String::new();
-
-    for file_name in &filenames {
-        content.push_str(&fs::read_to_string(file_name).expect("Error reading config file"));
-    }
-
-    // Replace environment variables in config.
-    for (k, v) in env::vars() {
-        content = content.replace(&format!("${}", k), &v);
-    }
+pub fn get(filenames: Vec<String>) -> Result<Configuration, ConfigFileError> {
+    let mut config: Configuration = config_file::load(&filenames)?;
 
-    let mut config: Configuration = toml::from_str(&content).expect("Error parsing config file");
+    // parse log level
+    config.concentratord.log_level_filter = config
+        .concentratord
+        .log_level
+        .parse::<log::Level>()
+        .map_err(|_| ConfigFileError::InvalidLogLevel(config.concentratord.log_level.clone()))?
+        .to_level_filter();
 
     // decode gateway id
     if !config.gateway.gateway_id.is_empty() {
-        let bytes = hex::decode(&config.gateway.gateway_id).expect("Could not decode gateway_id");
+        let bytes = hex::decode(&config.gateway.gateway_id)
+            .map_err(|e| ConfigFileError::InvalidGatewayId(e.to_string()))?;
         if bytes.len() != 8 {
-            panic!("gateway_id must be exactly 8 bytes");
+            return Err(ConfigFileError::InvalidGatewayId(
+                "Must be exactly 8 bytes".into(),
+            ));
         }
         let id = bytes.as_slice();
         config.gateway.gateway_id_bytes = Some(id[0..8].try_into().unwrap());
     }
 
+    // generate the channels from the region channel plan
+    helpers::set_channel_plan(&mut config)
+        .map_err(|e| ConfigFileError::ChannelPlan(e.to_string()))?;
+
+    // validate the JIT queue configuration
+    config
+        .concentratord
+        .jit_queue
+        .validate()
+        .map_err(|e| ConfigFileError::JitQueue(e.to_string()))?;
+
     // get model configuration
-    config.gateway.model_config = match config.gateway.model.as_ref() {
-        "dragino_pg1302" => vendor::dragino::pg1302::new(&config).unwrap(),
-        "elecrow_lr1302" => vendor::elecrow::lr1302::new(&config).unwrap(),
-        "embit_emb_lr1302_mpcie" => vendor::embit::emb_lr1302_mpcie::new(&config).unwrap(),
-        "miromico_gwc_02_lw_868" => vendor::miromico::gwc_02_lw_868::new(&config).unwrap(),
-        "miromico_gwc_02_lw_915" => vendor::miromico::gwc_02_lw_915::new(&config).unwrap(),
-        "multitech_mtac_003e00" => vendor::multitech::mtac_003e00::new(&config).unwrap(),
-        "multitech_mtac_003u00" => vendor::multitech::mtac_003u00::new(&config).unwrap(),
-        "multitech_mtcap3_003e00" => vendor::multitech::mtcap3_003e00::new(&config).unwrap(),
-        "multitech_mtcap3_003u00" => vendor::multitech::mtcap3_003u00::new(&config).unwrap(),
-        "rak_2287" => vendor::rak::rak2287::new(&config).unwrap(),
-        "rak_5146" => vendor::rak::rak5146::new(&config).unwrap(),
-        "seeed_wm1302" => vendor::seeed::wm1302::new(&config).unwrap(),
-        "semtech_sx1302c490gw1" => vendor::semtech::sx1302c490gw1::new(&config).unwrap(),
-        "semtech_sx1302c868gw1" => vendor::semtech::sx1302c868gw1::new(&config).unwrap(),
-        "semtech_sx1302c915gw1" => vendor::semtech::sx1302c915gw1::new(&config).unwrap(),
-        "semtech_sx1302css868gw1" => vendor::semtech::sx1302css868gw1::new(&config).unwrap(),
-        "semtech_sx1302css915gw1" => vendor::semtech::sx1302css915gw1::new(&config).unwrap(),
-        "semtech_sx1302css923gw1" => vendor::semtech::sx1302css923gw1::new(&config).unwrap(),
-        "waveshare_sx1302_lorawan_gateway_hat" => {
-            vendor::waveshare::sx1302_lorawan_gateway_hat::new(&config).unwrap()
-        }
-        _ => panic!("unexpected gateway model: {}", config.gateway.model),
-    };
+    config.gateway.model_config =
+        vendor::new(&config).map_err(|e| ConfigFileError::Model(format!("{:#}", e)))?;
 
     debug!("Antenna gain {} dBi", config.gateway.antenna_gain);
 
-    config
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use libloragw_sx1302::hal;
+
+    use super::*;
+
+    #[test]
+    fn test_get_invalid_log_level() {
+        let path = env::temp_dir().join(format!("config_test_{}.toml", std::process::id()));
+        fs::write(&path, "[concentratord]\n  log_level=\"VERBOSE\"\n").unwrap();
+
+        let res = get(vec![path.to_str().unwrap().to_string()]);
+        fs::remove_file(&path).unwrap();
+
+        let e = res.err().unwrap();
+        assert!(matches!(e, ConfigFileError::InvalidLogLevel(ref v) if v == "VERBOSE"));
+        assert_eq!(78, e.exit_code());
+    }
+
+    #[test]
+    fn test_get_tx_rf_chains() {
+        let radio = |tx_enable: bool, tx_min_max_freqs: Vec<(u32, u32)>| vendor::RadioConfig {
+            radio_type: hal::RadioType::SX1250,
+            single_input_mode: false,
+            rssi_offset: -215.4,
+            rssi_temp_compensation: hal::RssiTempCompensationConfig {
+                coeff_a: 0.0,
+                coeff_b: 0.0,
+                coeff_c: 0.0,
+                coeff_d: 0.0,
+                coeff_e: 0.0,
+            },
+            tx_enable,
+            tx_min_max_freqs,
+            tx_gain_table: vec![],
+        };
+        let config = |concurrent_tx_rf_chains: bool, radio_config: Vec<vendor::RadioConfig>| {
+            let mut config = Configuration::default();
+            config.gateway.model_config = vendor::Configuration {
+                radio_count: radio_config.len(),
+                concurrent_tx_rf_chains,
+                radio_config,
+                ..Default::default()
+            };
+            config
+        };
+
+        // Concurrent TX is not supported by the model.
+        let c = config(
+            false,
+            vec![
+                radio(true, vec![(863000000, 870000000)]),
+                radio(true, vec![(863000000, 870000000)]),
+            ],
+        );
+        assert!(c.get_tx_rf_chains().is_empty());
+
+        // A single TX capable RF chain.
+        let c = config(
+            true,
+            vec![
+                radio(true, vec![(863000000, 870000000)]),
+                radio(false, vec![]),
+            ],
+        );
+        assert!(c.get_tx_rf_chains().is_empty());
+
+        // Multiple TX capable RF chains.
+        let c = config(
+            true,
+            vec![
+                radio(true, vec![(863000000, 865000000)]),
+                radio(false, vec![]),
+                radio(true, vec![(865000000, 870000000)]),
+            ],
+        );
+        assert_eq!(
+            vec![
+                jitqueue::RfChain {
+                    rf_chain: 0,
+                    tx_min_max_freqs: vec![(863000000, 865000000)],
+                },
+                jitqueue::RfChain {
+                    rf_chain: 2,
+                    tx_min_max_freqs: vec![(865000000, 870000000)],
+                },
+            ],
+            c.get_tx_rf_chains()
+        );
+    }
 }
@@ -8,7 +8,7 @@ use libconcentratord::signals::Signal;
 use libconcentratord::{gnss, jitqueue};
 use libloragw_sx1302::hal;
 
-use super::super::{config, wrapper};
+use super::super::{backend, config, wrapper};
 
 const PERIOD: u64 = 128;
 const MARGIN: Duration = Duration::from_secs(5);
@@ -28,7 +28,7 @@ pub fn beacon_loop(
             return Ok(());
         }
 
-        let gps_epoch = match gnss::count_to_epoch(hal::get_instcnt()?) {
+        let gps_epoch = match gnss::count_to_epoch(backend::get_instcnt()?) {
             Some(v) => v,
             None => {
                 debug!("GPS epoch is not available");
@@ -105,12 +105,14 @@ fn send_beacon(
         payload: data,
         ..Default::default()
     };
-    let tx_packet = wrapper::TxPacket::new(getrandom::u32()?, tx_packet);
+    let tx_packet =
+        wrapper::TxPacket::new(getrandom::u32()?, tx_packet, jitqueue::Priority::Beacon);
 
     queue
         .lock()
         .map_err(|_| anyhow!("Lock error"))?
-        .enqueue(hal::get_instcnt()?, tx_packet)
+        .enqueue(backend::get_instcnt()?, tx_packet)
+        .map(|_| ())
         .map_err(|e| anyhow!("{:?}", e))
 }
 
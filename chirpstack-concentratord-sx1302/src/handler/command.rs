@@ -1,17 +1,22 @@
-use std::sync::mpsc::{Receiver, Sender};
+use std::sync::mpsc::{Receiver, Sender, channel};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
 
 use anyhow::Result;
 use chirpstack_api::{common, gw, prost::Message};
+use libconcentratord::jitqueue::TxPacket;
 use libconcentratord::signals::Signal;
-use libconcentratord::{commands, gnss, jitqueue, stats};
-use libloragw_sx1302::hal;
+use libconcentratord::{api, commands, gnss, jitqueue, stats};
 
-use crate::{config::vendor, wrapper};
+use super::{jit, spectral_scan};
+use crate::{
+    backend,
+    config::{self, vendor},
+    wrapper,
+};
 
 pub fn handle_loop(
-    vendor_config: &vendor::Configuration,
+    config: Arc<Mutex<config::Configuration>>,
     gateway_id: &[u8],
     queue: Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
     rep_sock: zmq::Socket,
@@ -29,52 +34,86 @@ pub fn handle_loop(
             return Ok(());
         }
 
-        let count_us = hal::get_instcnt()?;
+        let count_us = backend::get_instcnt()?;
+
+        let (cmd, envelope) = match cmd {
+            Ok(v) => v,
+            Err(libconcentratord::error::Error::Timeout) => continue,
+            Err(e) => {
+                warn!("Read command error, error: {}", e);
+                continue;
+            }
+        };
+
+        let cmd = match cmd {
+            commands::Request::Gw(v) => v,
+            commands::Request::Extension(v) => {
+                let resp = match v.command {
+                    Some(api::command::Command::SpectralScan(v)) => {
+                        handle_spectral_scan(&v).encode_to_vec()
+                    }
+                    Some(api::command::Command::GetQueue(_)) => handle_get_queue(&queue, count_us)
+                        .unwrap_or_else(|e| {
+                            error!("Handle get queue error, error: {}", e);
+                            Vec::new()
+                        }),
+                    Some(api::command::Command::CancelDownlink(v)) => {
+                        handle_cancel_downlink(gateway_id, &queue, &v).unwrap_or_else(|e| {
+                            error!("Handle cancel downlink error, error: {}", e);
+                            Vec::new()
+                        })
+                    }
+                    None => Vec::new(),
+                };
 
-        let resp = match cmd {
-            Ok(v) => match v.command {
-                Some(gw::command::Command::SendDownlinkFrame(v)) => {
-                    handle_downlink(vendor_config, gateway_id, &queue, &v).unwrap_or_else(|e| {
+                commands::send_reply(&rep_sock, envelope, resp)?;
+                continue;
+            }
+        };
+
+        let resp = match cmd.command {
+            Some(gw::command::Command::SendDownlinkFrame(v)) => {
+                let config = config.lock().map_err(|_| anyhow!("Lock config error"))?;
+                handle_downlink(&config.gateway.model_config, gateway_id, &queue, &v)
+                    .unwrap_or_else(|e| {
                         error!("Handle downlink error, error: {}", e);
                         Vec::new()
                     })
-                }
-                Some(gw::command::Command::SetGatewayConfiguration(v)) => {
-                    handle_configuration(stop_send.clone(), v).unwrap_or_else(|e| {
-                        error!("Handle configuration error, error: {}", e);
-                        Vec::new()
-                    })
-                }
-                Some(gw::command::Command::GetGatewayId(_)) => {
-                    let resp = gw::GetGatewayIdResponse {
-                        gateway_id: hex::encode(gateway_id),
-                    };
-                    resp.encode_to_vec()
-                }
-                Some(gw::command::Command::GetLocation(_)) => gw::GetLocationResponse {
-                    location: gnss::get_location(count_us).map(|v| common::Location {
-                        latitude: v.lat,
-                        longitude: v.lon,
-                        altitude: v.alt.into(),
-                        source: common::LocationSource::Gps.into(),
-                        ..Default::default()
-                    }),
-                    updated_at: gnss::get_location_last_updated_at()
-                        .map(|v| Into::<SystemTime>::into(v).into()),
-                }
-                .encode_to_vec(),
-                _ => Vec::new(),
-            },
-            Err(e) => match e {
-                libconcentratord::error::Error::Timeout => continue,
-                _ => {
-                    warn!("Read command error, error: {}", e);
+            }
+            Some(gw::command::Command::SetGatewayConfiguration(v)) => {
+                // The lock is released before the configuration is handed to the root, as the
+                // root updates the configuration once it has been applied.
+                let config = config
+                    .lock()
+                    .map_err(|_| anyhow!("Lock config error"))?
+                    .clone();
+                handle_configuration(&config, stop_send.clone(), v).unwrap_or_else(|e| {
+                    error!("Handle configuration error, error: {}", e);
                     Vec::new()
-                }
-            },
+                })
+            }
+            Some(gw::command::Command::GetGatewayId(_)) => {
+                let resp = gw::GetGatewayIdResponse {
+                    gateway_id: hex::encode(gateway_id),
+                };
+                resp.encode_to_vec()
+            }
+            Some(gw::command::Command::GetLocation(_)) => gw::GetLocationResponse {
+                location: gnss::get_location(count_us).map(|v| common::Location {
+                    latitude: v.lat,
+                    longitude: v.lon,
+                    altitude: v.alt.into(),
+                    source: common::LocationSource::Gps.into(),
+                    ..Default::default()
+                }),
+                updated_at: gnss::get_location_last_updated_at()
+                    .map(|v| Into::<SystemTime>::into(v).into()),
+            }
+            .encode_to_vec(),
+            _ => Vec::new(),
         };
 
-        rep_sock.send(resp, 0)?;
+        commands::send_reply(&rep_sock, envelope, resp)?;
     }
 
     Ok(())
@@ -88,17 +127,16 @@ fn handle_downlink(
 ) -> Result<Vec<u8>> {
     stats::inc_tx_packets_received();
 
-    let mut tx_ack = chirpstack_api::gw::DownlinkTxAck {
+    let mut tx_ack = api::DownlinkTxAck {
         gateway_id: hex::encode(gateway_id),
         downlink_id: pl.downlink_id,
         items: vec![Default::default(); pl.items.len()],
-        ..Default::default()
     };
-    let mut stats_tx_status = chirpstack_api::gw::TxAckStatus::Ignored;
+    let mut stats_tx_status = api::TxAckStatus::Ignored;
 
     for (i, item) in pl.items.iter().enumerate() {
         // convert protobuf to hal struct
-        let tx_packet = match wrapper::downlink_from_proto(item) {
+        let (tx_packet, priority) = match wrapper::downlink_from_proto(item) {
             Ok(v) => v,
             Err(err) => {
                 error!(
@@ -125,14 +163,14 @@ fn handle_downlink(
                         "Frequency is not within min / max gateway frequencies, downlink_id: {}, freq: {}",
                         pl.downlink_id, tx_packet.freq_hz
                     );
-                    tx_ack.items[i].set_status(chirpstack_api::gw::TxAckStatus::TxFreq);
+                    tx_ack.items[i].set_status(api::TxAckStatus::TxFreq);
 
                     // try next
                     continue;
                 }
             }
             None => {
-                tx_ack.items[i].set_status(chirpstack_api::gw::TxAckStatus::TxFreq);
+                tx_ack.items[i].set_status(api::TxAckStatus::TxFreq);
 
                 // try next
                 continue;
@@ -144,12 +182,14 @@ fn handle_downlink(
             .lock()
             .map_err(|_| anyhow!("Queue lock error"))?
             .enqueue(
-                hal::get_instcnt()?,
-                wrapper::TxPacket::new(pl.downlink_id, tx_packet),
+                backend::get_instcnt()?,
+                wrapper::TxPacket::new(pl.downlink_id, tx_packet, priority),
             ) {
-            Ok(_) => {
-                tx_ack.items[i].set_status(chirpstack_api::gw::TxAckStatus::Ok);
-                stats_tx_status = chirpstack_api::gw::TxAckStatus::Ok;
+            Ok(tx_packet) => {
+                tx_ack.items[i].set_status(api::TxAckStatus::Ok);
+                tx_ack.items[i].tx_power = Some(tx_packet.get_tx_power().into());
+                tx_ack.items[i].count_us = Some(tx_packet.get_count_us());
+                stats_tx_status = api::TxAckStatus::Ok;
 
                 // break out of loop
                 break;
@@ -167,9 +207,99 @@ fn handle_downlink(
 }
 
 fn handle_configuration(
+    config: &config::Configuration,
     stop_send: Sender<Signal>,
     pl: chirpstack_api::gw::GatewayConfiguration,
 ) -> Result<Vec<u8>> {
-    stop_send.send(Signal::Configuration(pl)).unwrap();
-    Ok(Vec::new())
+    // The configuration is validated before it is handed to the root, such that an invalid
+    // configuration does not interrupt the concentrator.
+    let result = match super::config::validate_configuration(config, &pl) {
+        Ok(_) => {
+            let (result_send, result_receive) = channel();
+            stop_send
+                .send(Signal::Configuration(pl, result_send))
+                .map_err(|_| anyhow!("Send configuration signal error"))?;
+
+            // Wait until the configuration has been applied.
+            result_receive.recv()?
+        }
+        Err(e) => Err(e),
+    };
+
+    match &result {
+        Ok(_) => info!("Configuration update applied"),
+        Err(e) => warn!("Configuration update rejected, error: {}", e),
+    }
+
+    Ok(api::SetGatewayConfigurationResponse::from(result).encode_to_vec())
+}
+
+fn handle_spectral_scan(pl: &api::SpectralScanRequest) -> api::SpectralScanResponse {
+    let frequencies =
+        spectral_scan::get_frequencies(pl.frequency_start, pl.frequency_step, pl.channels);
+    let frequencies = match frequencies {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Spectral scan request rejected, error: {}", e);
+            return api::SpectralScanResponse {
+                error: e.to_string(),
+                ..Default::default()
+            };
+        }
+    };
+
+    let result = spectral_scan::schedule(&frequencies);
+    api::SpectralScanResponse {
+        scheduled: result.is_ok(),
+        results: spectral_scan::get_results(&frequencies),
+        error: result.err().map(|e| e.to_string()).unwrap_or_default(),
+    }
+}
+
+fn handle_get_queue(
+    queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+    count_us: u32,
+) -> Result<Vec<u8>> {
+    let items = queue
+        .lock()
+        .map_err(|_| anyhow!("Queue lock error"))?
+        .get_items(count_us);
+
+    Ok(api::GetQueueResponse { items }.encode_to_vec())
+}
+
+fn handle_cancel_downlink(
+    gateway_id: &[u8],
+    queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+    pl: &api::CancelDownlinkRequest,
+) -> Result<Vec<u8>> {
+    let removed = queue
+        .lock()
+        .map_err(|_| anyhow!("Queue lock error"))?
+        .remove(pl.downlink_id);
+
+    // When the downlink is no longer in the queue, it might have been handed to the
+    // concentrator already.
+    let status = if removed {
+        api::CancelStatus::Removed
+    } else if pl.abort_tx {
+        match jit::abort_tx(gateway_id, pl.downlink_id) {
+            Ok(true) => api::CancelStatus::Aborted,
+            Ok(false) => api::CancelStatus::NotFound,
+            Err(e) => {
+                error!(
+                    "Abort TX error, downlink_id: {}, error: {}",
+                    pl.downlink_id, e
+                );
+                api::CancelStatus::AbortError
+            }
+        }
+    } else {
+        api::CancelStatus::NotFound
+    };
+
+    Ok(api::CancelDownlinkResponse {
+        status: status.into(),
+    }
+    .encode_to_vec())
 }
@@ -1,13 +1,60 @@
 use std::cmp::Ordering;
 
-use anyhow::Result;
+use libconcentratord::error::ConfigurationError;
 
-use super::super::config::{Concentrator, Configuration};
+use super::super::config::{helpers, Concentrator, Configuration};
+
+/// Validate the gateway configuration. On success, this returns a copy of the given
+/// configuration with the gateway configuration applied. The given configuration is not
+/// modified.
+pub fn validate_configuration(
+    config: &Configuration,
+    new_config: &chirpstack_api::gw::GatewayConfiguration,
+) -> Result<Configuration, ConfigurationError> {
+    let mut config = config.clone();
+    update_configuration(&mut config, new_config)?;
+
+    let concentrator = &config.gateway.concentrator;
+    if concentrator.lora_std.frequency != 0
+        && !(5..=12).contains(&concentrator.lora_std.spreading_factor)
+    {
+        return Err(ConfigurationError::InvalidChannel(
+            concentrator.lora_std.frequency,
+            "unsupported spreading-factor".to_string(),
+        ));
+    }
+
+    // all channels must fit within the bandwidth of the radios
+    let radio_freqs = helpers::get_radio_frequencies(&config)?;
+    for freq in concentrator.multi_sf_channels.iter().filter(|v| **v != 0) {
+        helpers::get_radio_for_channel(
+            &radio_freqs,
+            *freq,
+            config.gateway.model_config.lora_multi_sf_bandwidth,
+        )?;
+    }
+    if concentrator.lora_std.frequency != 0 {
+        helpers::get_radio_for_channel(
+            &radio_freqs,
+            concentrator.lora_std.frequency,
+            concentrator.lora_std.bandwidth,
+        )?;
+    }
+    if concentrator.fsk.frequency != 0 {
+        helpers::get_radio_for_channel(
+            &radio_freqs,
+            concentrator.fsk.frequency,
+            concentrator.fsk.bandwidth,
+        )?;
+    }
+
+    Ok(config)
+}
 
 pub fn update_configuration(
     config: &mut Configuration,
     new_config: &chirpstack_api::gw::GatewayConfiguration,
-) -> Result<()> {
+) -> Result<(), ConfigurationError> {
     info!("Updating concentrator configuration");
 
     // empty concentrator config
@@ -28,7 +75,9 @@ pub fn update_configuration(
                 }
                 Ordering::Greater => {
                     if multi_sf_count > concentrator.multi_sf_channels.len() - 1 {
-                        return Err(anyhow!("too many multi-SF channels in configuration"));
+                        return Err(ConfigurationError::TooManyMultiSfChannels(
+                            concentrator.multi_sf_channels.len(),
+                        ));
                     }
 
                     concentrator.multi_sf_channels[multi_sf_count] = channel.frequency;
@@ -49,7 +98,9 @@ pub fn update_configuration(
 
     // set config
     if let Some(stats_interval) = new_config.stats_interval {
-        config.concentratord.stats_interval = stats_interval.try_into()?;
+        config.concentratord.stats_interval = stats_interval
+            .try_into()
+            .map_err(|_| ConfigurationError::InvalidStatsInterval)?;
     }
     config
         .gateway
@@ -62,8 +113,67 @@ pub fn update_configuration(
 
 #[cfg(test)]
 mod tests {
+    use super::super::super::config::vendor;
     use super::super::super::config::{FSKChannel, LoRaStdChannel};
-    use super::{update_configuration, Concentrator, Configuration};
+    use super::{update_configuration, validate_configuration, Concentrator, Configuration};
+    use libconcentratord::error::ConfigurationError;
+
+    fn multi_sf_channel(frequency: u32) -> chirpstack_api::gw::ChannelConfiguration {
+        chirpstack_api::gw::ChannelConfiguration {
+            frequency,
+            modulation_config: Some(
+                chirpstack_api::gw::channel_configuration::ModulationConfig::LoraModulationConfig(
+                    chirpstack_api::gw::LoraModulationConfig {
+                        bandwidth: 125000,
+                        spreading_factors: vec![7, 8, 9, 10, 11, 12],
+                        ..Default::default()
+                    },
+                ),
+            ),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_validate_configuration() {
+        let tests = vec![
+            (
+                "Valid",
+                vec![868100000, 868300000, 868500000],
+                Ok(()),
+            ),
+            (
+                "Too many multi-SF channels",
+                vec![
+                    868100000, 868300000, 868500000, 867100000, 867300000, 867500000, 867700000,
+                    867900000, 868700000,
+                ],
+                Err(ConfigurationError::TooManyMultiSfChannels(8)),
+            ),
+            (
+                "Channel outside radio bandwidth",
+                vec![868100000, 868300000, 869900000, 871900000],
+                Err(ConfigurationError::ChannelOutsideRadioBandwidth(871900000)),
+            ),
+        ];
+
+        for (name, frequencies, expected) in tests {
+            let mut config = Configuration::default();
+            config.gateway.model = "semtech_sx1302c868gw1".into();
+            config.gateway.model_config = vendor::new(&config).unwrap();
+
+            let gw_config = chirpstack_api::gw::GatewayConfiguration {
+                channels: frequencies.into_iter().map(multi_sf_channel).collect(),
+                ..Default::default()
+            };
+
+            let res = validate_configuration(&config, &gw_config).map(|_| ());
+            assert_eq!(expected, res, "{}", name);
+
+            // the given configuration is not modified
+            assert_eq!(Concentrator::default(), config.gateway.concentrator);
+        }
+    }
 
     #[test]
     fn test_update_configuration() {
@@ -5,7 +5,9 @@ use std::time::Duration;
 use anyhow::Result;
 
 use libconcentratord::{gnss, gpsd, signals::Signal};
-use libloragw_sx1302::{gps, hal};
+use libloragw_sx1302::gps;
+
+use crate::backend;
 
 pub fn gps_loop(gps_device: gnss::Device, stop_receive: Receiver<Signal>) -> Result<()> {
     debug!("Starting GPS loop");
@@ -35,7 +37,7 @@ pub fn gps_loop(gps_device: gnss::Device, stop_receive: Receiver<Signal>) -> Res
         if let Ok(v) = gnss::read(&mut gps_reader)
             && let Some(v) = v
         {
-            gnss::sync(&v, hal::get_trigcnt()?)?;
+            gnss::sync(&v, backend::get_trigcnt()?)?;
         }
     }
 }
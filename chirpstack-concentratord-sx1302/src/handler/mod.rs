@@ -3,5 +3,6 @@ pub mod command;
 pub mod config;
 pub mod gps;
 pub mod jit;
+pub mod spectral_scan;
 pub mod stats;
 pub mod uplink;
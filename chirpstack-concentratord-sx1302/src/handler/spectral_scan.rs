@@ -0,0 +1,335 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::sync::mpsc::Receiver;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::Result;
+use libconcentratord::signals::Signal;
+use libconcentratord::{api, events};
+use libloragw_sx1302::hal;
+
+use super::super::{backend, config};
+
+// Interval at which the spectral scan status is polled.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Max. duration of a single spectral scan.
+const SCAN_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Max. number of channels of a single spectral scan request.
+const MAX_CHANNELS: u32 = 64;
+
+// Max. number of requested frequencies waiting to be scanned.
+const MAX_REQUESTED: usize = 4 * MAX_CHANNELS as usize;
+
+// Frequency range of the SX1261 radio (Hz).
+const FREQUENCY_MIN: u32 = 150000000;
+const FREQUENCY_MAX: u32 = 960000000;
+
+static STATE: LazyLock<Mutex<State>> = LazyLock::new(|| Mutex::new(State::default()));
+
+#[derive(Default)]
+struct State {
+    // Set when the scan loop is running.
+    enabled: bool,
+    // Frequencies requested through the spectral scan command, in order of the request.
+    requested: VecDeque<u32>,
+    // Set of the requested frequencies, to skip duplicates.
+    requested_set: HashSet<u32>,
+    // Most recent result by frequency.
+    results: BTreeMap<u32, api::SpectralScanResult>,
+}
+
+impl State {
+    fn pop_requested(&mut self) -> Option<u32> {
+        let frequency = self.requested.pop_front()?;
+        self.requested_set.remove(&frequency);
+        Some(frequency)
+    }
+
+    fn push_requested(&mut self, frequency: u32, front: bool) {
+        if !self.requested_set.insert(frequency) {
+            return;
+        }
+
+        if front {
+            self.requested.push_front(frequency);
+        } else {
+            self.requested.push_back(frequency);
+        }
+    }
+}
+
+/// Returns the frequencies to scan, starting at frequency_start with frequency_step
+/// increments. This returns an error when the number of channels is invalid or when a
+/// frequency is outside the frequency range of the SX1261 radio.
+pub fn get_frequencies(
+    frequency_start: u32,
+    frequency_step: u32,
+    channels: u32,
+) -> Result<Vec<u32>> {
+    if channels == 0 || channels > MAX_CHANNELS {
+        return Err(anyhow!(
+            "Number of channels must be between 1 and {}, channels: {}",
+            MAX_CHANNELS,
+            channels
+        ));
+    }
+
+    (0..channels)
+        .map(|i| {
+            i.checked_mul(frequency_step)
+                .and_then(|v| v.checked_add(frequency_start))
+                .filter(|v| (FREQUENCY_MIN..=FREQUENCY_MAX).contains(v))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Frequency is outside the SX1261 frequency range, frequency_start: {}, frequency_step: {}, channel: {}",
+                        frequency_start,
+                        frequency_step,
+                        i
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Schedule the given frequencies for scanning. Requested frequencies have priority over the
+/// periodic scan. This returns an error when the spectral scan is not enabled or when too
+/// many frequencies are waiting to be scanned.
+pub fn schedule(frequencies: &[u32]) -> Result<()> {
+    let mut state = STATE.lock().unwrap();
+    if !state.enabled {
+        return Err(anyhow!("Spectral scan is not enabled"));
+    }
+
+    let new = frequencies
+        .iter()
+        .filter(|f| !state.requested_set.contains(f))
+        .collect::<HashSet<_>>();
+    if state.requested.len() + new.len() > MAX_REQUESTED {
+        return Err(anyhow!("Spectral scan queue is full"));
+    }
+
+    for f in frequencies {
+        state.push_requested(*f, false);
+    }
+
+    Ok(())
+}
+
+/// Return the most recent results for the given frequencies.
+pub fn get_results(frequencies: &[u32]) -> Vec<api::SpectralScanResult> {
+    let state = STATE.lock().unwrap();
+    frequencies
+        .iter()
+        .filter_map(|f| state.results.get(f).cloned())
+        .collect()
+}
+
+pub fn scan_loop(
+    gateway_id: &[u8],
+    conf: &config::SpectralScan,
+    stop_receive: Receiver<Signal>,
+) -> Result<()> {
+    debug!(
+        "Starting spectral scan loop, frequency_start: {}, frequency_step: {}, channels: {}, interval: {:?}",
+        conf.frequency_start, conf.frequency_step, conf.channels, conf.interval
+    );
+
+    let frequencies = get_frequencies(conf.frequency_start, conf.frequency_step, conf.channels)?;
+    STATE.lock().unwrap().enabled = true;
+    let gateway_id = hex::encode(gateway_id);
+    let mut channel = 0;
+
+    loop {
+        // Instead of an interval sleep, we receive from the stop channel with a
+        // timeout equal to the interval.
+        if let Ok(v) = stop_receive.recv_timeout(conf.interval) {
+            debug!("Received stop signal, signal: {}", v);
+            STATE.lock().unwrap().enabled = false;
+            return Ok(());
+        }
+
+        if !tx_idle()? {
+            debug!("Downlink scheduled or being transmitted, skipping spectral scan");
+            continue;
+        }
+
+        let frequency = match STATE.lock().unwrap().pop_requested() {
+            Some(v) => v,
+            None => {
+                let f = frequencies[channel];
+                channel = (channel + 1) % frequencies.len();
+                f
+            }
+        };
+
+        let result = match scan(frequency, conf.nb_scan) {
+            Ok(Some(v)) => v,
+            Ok(None) => {
+                debug!("Spectral scan aborted, frequency: {}", frequency);
+                STATE.lock().unwrap().push_requested(frequency, true);
+                continue;
+            }
+            Err(e) => {
+                warn!(
+                    "Spectral scan error, frequency: {}, error: {}",
+                    frequency, e
+                );
+                continue;
+            }
+        };
+
+        STATE
+            .lock()
+            .unwrap()
+            .results
+            .insert(frequency, result.clone());
+
+        events::send_spectral_scan(api::SpectralScan {
+            gateway_id: gateway_id.clone(),
+            results: vec![result],
+        })?;
+    }
+}
+
+// Scan the given frequency. This returns None when the scan was aborted because a downlink
+// was scheduled.
+fn scan(frequency: u32, nb_scan: u16) -> Result<Option<api::SpectralScanResult>> {
+    debug!(
+        "Starting spectral scan, frequency: {}, nb_scan: {}",
+        frequency, nb_scan
+    );
+
+    let time = SystemTime::now();
+    let start = Instant::now();
+    backend::spectral_scan_start(frequency, nb_scan)?;
+
+    loop {
+        match backend::spectral_scan_get_status()? {
+            hal::SpectralScanStatus::Completed => break,
+            hal::SpectralScanStatus::Aborted => return Ok(None),
+            _ => {}
+        }
+
+        if !tx_idle()? {
+            backend::spectral_scan_abort()?;
+            return Ok(None);
+        }
+
+        if start.elapsed() > SCAN_TIMEOUT {
+            backend::spectral_scan_abort()?;
+            return Err(anyhow!("Spectral scan timeout"));
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    Ok(Some(api::SpectralScanResult {
+        frequency,
+        time: Some(time.into()),
+        histogram: to_histogram(&backend::spectral_scan_get_results()?),
+    }))
+}
+
+// The SX1261 is shared with LBT, a scan must not overlap with a scheduled or on-going
+// transmission.
+fn tx_idle() -> Result<bool> {
+    for rf_chain in 0..2 {
+        match backend::status(rf_chain, hal::StatusSelect::Tx)? {
+            hal::StatusReturn::Tx(hal::TxStatus::Scheduled)
+            | hal::StatusReturn::Tx(hal::TxStatus::Emitting) => return Ok(false),
+            _ => {}
+        }
+    }
+
+    Ok(true)
+}
+
+fn to_histogram(results: &[hal::SpectralScanResult]) -> Vec<api::RssiHistogramBin> {
+    results
+        .iter()
+        .filter(|v| v.result != 0)
+        .map(|v| api::RssiHistogramBin {
+            rssi: v.dbm_level.into(),
+            count: v.result.into(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_histogram() {
+        let results = vec![
+            hal::SpectralScanResult {
+                dbm_level: -120,
+                result: 10,
+            },
+            hal::SpectralScanResult {
+                dbm_level: -118,
+                result: 0,
+            },
+            hal::SpectralScanResult {
+                dbm_level: -116,
+                result: 3,
+            },
+        ];
+
+        assert_eq!(
+            vec![
+                api::RssiHistogramBin {
+                    rssi: -120,
+                    count: 10,
+                },
+                api::RssiHistogramBin {
+                    rssi: -116,
+                    count: 3,
+                },
+            ],
+            to_histogram(&results)
+        );
+    }
+
+    #[test]
+    fn test_get_frequencies() {
+        assert_eq!(
+            vec![868100000, 868300000, 868500000],
+            get_frequencies(868100000, 200000, 3).unwrap()
+        );
+
+        assert!(get_frequencies(868100000, 200000, 0).is_err());
+        assert!(get_frequencies(868100000, 200000, MAX_CHANNELS + 1).is_err());
+        assert!(get_frequencies(868100000, 200000, u32::MAX).is_err());
+        assert!(get_frequencies(100000000, 200000, 1).is_err());
+        assert!(get_frequencies(959900000, 200000, 2).is_err());
+        assert!(get_frequencies(868100000, u32::MAX, 2).is_err());
+        assert!(get_frequencies(u32::MAX, 1, 1).is_err());
+    }
+
+    #[test]
+    fn test_push_requested() {
+        let mut state = State::default();
+        state.push_requested(868100000, false);
+        state.push_requested(868300000, false);
+        state.push_requested(868100000, false);
+        state.push_requested(868500000, true);
+
+        assert_eq!(Some(868500000), state.pop_requested());
+        assert_eq!(Some(868100000), state.pop_requested());
+
+        // the popped frequency can be requested again
+        state.push_requested(868100000, false);
+        assert_eq!(Some(868300000), state.pop_requested());
+        assert_eq!(Some(868100000), state.pop_requested());
+        assert_eq!(None, state.pop_requested());
+    }
+
+    #[test]
+    fn test_schedule_disabled() {
+        assert!(schedule(&[868100000]).is_err());
+        assert!(get_results(&[868100000]).is_empty());
+    }
+}
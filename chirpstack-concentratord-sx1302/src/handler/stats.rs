@@ -7,10 +7,9 @@ use anyhow::{Context, Result};
 
 use chirpstack_api::gw::DutyCycleStats;
 use libconcentratord::signals::Signal;
-use libconcentratord::{gnss, jitqueue, stats};
-use libloragw_sx1302::hal;
+use libconcentratord::{gnss, jitqueue, metrics, stats};
 
-use crate::wrapper;
+use crate::{backend, wrapper};
 
 pub fn stats_loop(
     gateway_id: &[u8],
@@ -19,6 +18,7 @@ pub fn stats_loop(
     stop_receive: Receiver<Signal>,
     mut metadata: HashMap<String, String>,
     queue: Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+    duty_cycle_state_file: &str,
 ) -> Result<()> {
     debug!("Starting stats loop, stats_interval: {:?}", stats_interval);
 
@@ -32,7 +32,7 @@ pub fn stats_loop(
 
         // fetch the current gps coordinates
         let loc =
-            gnss::get_location(hal::get_instcnt()?).map(|v| chirpstack_api::common::Location {
+            gnss::get_location(backend::get_instcnt()?).map(|v| chirpstack_api::common::Location {
                 latitude: v.lat,
                 longitude: v.lon,
                 altitude: v.alt.into(),
@@ -42,18 +42,35 @@ pub fn stats_loop(
 
         // fetch the concentrator temperature.
         if get_temperature {
-            match hal::get_temperature() {
+            match backend::get_temperature() {
                 Ok(v) => {
                     metadata.insert("concentrator_temp".to_string(), format!("{}", v));
+                    metrics::set_concentrator_temperature(Some(v));
                 }
                 Err(err) => {
                     metadata.remove("concentrator_temp");
+                    metrics::set_concentrator_temperature(None);
                     error!("Get concentrator temperature error, error: {}", err);
                 }
             }
         }
 
+        // update the metrics gauges
+        metrics::set_jit_queue_size(queue.lock().map_err(|_| anyhow!("Queue lock error"))?.len());
+        metrics::set_jit_send_latency_max(
+            queue
+                .lock()
+                .map_err(|_| anyhow!("Queue lock error"))?
+                .take_send_latency_max(),
+        );
+        metrics::set_gnss_locked(gnss::count_to_epoch(backend::get_instcnt()?).is_some());
+
         let dc_stats = get_duty_cycle_stats(&queue)?;
+
+        if let Err(e) = save_duty_cycle_state(&queue, duty_cycle_state_file) {
+            error!("Save duty-cycle state error, error: {}", e);
+        }
+
         stats::send_and_reset(gateway_id, loc, dc_stats, &metadata).context("Send stats")?;
     }
 }
@@ -62,6 +79,19 @@ fn get_duty_cycle_stats(
     queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
 ) -> Result<Option<DutyCycleStats>> {
     let mut queue = queue.lock().map_err(|_| anyhow!("Lock queue error"))?;
-    let concentrator_count = hal::get_instcnt()?;
+    let concentrator_count = backend::get_instcnt()?;
     Ok(queue.get_duty_cycle_stats(concentrator_count))
 }
+
+fn save_duty_cycle_state(
+    queue: &Arc<Mutex<jitqueue::Queue<wrapper::TxPacket>>>,
+    path: &str,
+) -> Result<()> {
+    if path.is_empty() {
+        return Ok(());
+    }
+
+    let mut queue = queue.lock().map_err(|_| anyhow!("Lock queue error"))?;
+    let concentrator_count = backend::get_instcnt()?;
+    queue.save_duty_cycle_state(concentrator_count, path)
+}
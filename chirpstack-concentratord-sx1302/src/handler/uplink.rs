@@ -5,10 +5,10 @@ use std::time::Duration;
 use anyhow::{Context, Result};
 
 use libconcentratord::signals::Signal;
-use libconcentratord::{events, stats};
+use libconcentratord::{capture, events, stats};
 use libloragw_sx1302::hal;
 
-use super::super::wrapper;
+use super::super::{backend, wrapper};
 
 pub fn handle_loop(
     gateway_id: &[u8],
@@ -24,11 +24,15 @@ pub fn handle_loop(
             return Ok(());
         }
 
-        match hal::receive() {
+        match backend::receive() {
             Ok(frames) => {
                 for frame in frames {
                     stats::inc_rx_packets_received();
 
+                    if let Err(e) = capture::capture(&wrapper::uplink_to_capture(&frame)) {
+                        error!("Capture uplink error, error: {}", e);
+                    }
+
                     if !disable_crc_filter && frame.status != hal::CRC::CRCOk {
                         debug!(
                             "Frame received with invalid CRC, see disable_crc_filter configuration option if you want to receive these frames"
@@ -62,7 +66,7 @@ pub fn handle_loop(
                     );
 
                     if frame.status == hal::CRC::CRCOk {
-                        stats::inc_rx_counts(&proto);
+                        stats::inc_rx_counts(&proto, Some(frame.freq_offset));
                     }
                     events::send_uplink(proto).context("Send uplink")?;
                 }
@@ -6,8 +6,6 @@ extern crate simple_logger;
 extern crate syslog;
 
 use std::process;
-use std::rc::Rc;
-use std::str::FromStr;
 use std::sync::mpsc::channel;
 use std::thread;
 
@@ -21,6 +19,7 @@ use crate::config::vendor::ComType;
 use libconcentratord::reset;
 use libconcentratord::signals::Signal;
 
+mod backend;
 mod cmd;
 mod concentrator;
 mod config;
@@ -41,11 +40,24 @@ struct Cli {
 enum Commands {
     /// Print the configuration template
     Configfile {},
+    /// Validate the configuration files
+    Validate {},
 }
 
 fn main() {
     let cli = Cli::parse();
-    let mut config = config::get(cli.config.clone());
+
+    if let Some(Commands::Validate {}) = &cli.command {
+        process::exit(cmd::validate::run(&cli.config));
+    }
+
+    let mut config = match config::get(cli.config.clone()) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error loading configuration: {}", e);
+            process::exit(e.exit_code());
+        }
+    };
 
     if let Some(Commands::Configfile {}) = &cli.command {
         cmd::configfile::run(&config);
@@ -61,29 +73,18 @@ fn main() {
         };
         let logger = syslog::unix(formatter).expect("could not connect to syslog");
         log::set_boxed_logger(Box::new(BasicLogger::new(logger)))
-            .map(|()| {
-                log::set_max_level(
-                    log::Level::from_str(&config.concentratord.log_level)
-                        .unwrap()
-                        .to_level_filter(),
-                )
-            })
+            .map(|()| log::set_max_level(config.concentratord.log_level_filter))
             .unwrap();
     } else {
         SimpleLogger::new()
             .with_utc_timestamps()
-            .with_level(
-                log::Level::from_str(&config.concentratord.log_level)
-                    .unwrap()
-                    .to_level_filter(),
-            )
+            .with_level(config.concentratord.log_level_filter)
             .init()
             .unwrap();
     }
 
     let mut signals = Signals::new([SIGINT]).expect("error registering channels");
     let (stop_send, stop_receive) = channel();
-    let stop_receive = Rc::new(stop_receive);
 
     thread::spawn({
         let stop_send = stop_send.clone();
@@ -100,7 +101,7 @@ fn main() {
     });
 
     // configure concentrator reset pin
-    if config.gateway.model_config.com_type == ComType::Spi {
+    if !config.simulator.enabled && config.gateway.model_config.com_type == ComType::Spi {
         reset::setup_pins(reset::Configuration {
             sx130x_reset: config.gateway.model_config.sx1302_reset_pin.clone(),
             sx1302_power_en: config.gateway.model_config.sx1302_power_en_pin.clone(),
@@ -111,13 +112,9 @@ fn main() {
         .expect("setup reset pins error");
     }
 
-    loop {
-        match cmd::root::run(&config, stop_send.clone(), stop_receive.clone()).unwrap() {
-            Signal::Stop => process::exit(0),
-            Signal::Configuration(new_config) => {
-                handler::config::update_configuration(&mut config, &new_config)
-                    .expect("update configuration failed")
-            }
-        }
+    if let Err(e) = cmd::root::run(&mut config, stop_send, stop_receive) {
+        error!("Concentratord error, error: {:#}", e);
+        process::exit(1);
     }
+    process::exit(0);
 }
@@ -2,15 +2,15 @@ use std::time::{Duration, SystemTime};
 
 use anyhow::Result;
 use chirpstack_api::{gw, prost_types};
-use libconcentratord::{gnss, jitqueue};
+use libconcentratord::{capture, gnss, jitqueue};
 use libloragw_sx1302::hal;
 
 #[derive(Copy, Clone)]
-pub struct TxPacket(hal::TxPacket, u32);
+pub struct TxPacket(hal::TxPacket, u32, jitqueue::Priority);
 
 impl TxPacket {
-    pub fn new(id: u32, tx_packet: hal::TxPacket) -> TxPacket {
-        TxPacket(tx_packet, id)
+    pub fn new(id: u32, tx_packet: hal::TxPacket, priority: jitqueue::Priority) -> TxPacket {
+        TxPacket(tx_packet, id, priority)
     }
 
     pub fn tx_packet(&self) -> hal::TxPacket {
@@ -48,13 +48,33 @@ impl jitqueue::TxPacket for TxPacket {
         self.1
     }
 
+    fn get_priority(&self) -> jitqueue::Priority {
+        self.2
+    }
+
     fn get_frequency(&self) -> u32 {
         self.0.freq_hz
     }
 
+    fn get_bandwidth(&self) -> u32 {
+        self.0.bandwidth
+    }
+
     fn get_tx_power(&self) -> i8 {
         self.0.rf_power
     }
+
+    fn set_tx_power(&mut self, tx_power: i8) {
+        self.0.rf_power = tx_power;
+    }
+
+    fn get_rf_chain(&self) -> u8 {
+        self.0.rf_chain
+    }
+
+    fn set_rf_chain(&mut self, rf_chain: u8) {
+        self.0.rf_chain = rf_chain;
+    }
 }
 
 pub fn uplink_to_proto(
@@ -154,7 +174,9 @@ pub fn uplink_to_proto(
     })
 }
 
-pub fn downlink_from_proto(df: &gw::DownlinkFrameItem) -> Result<hal::TxPacket> {
+pub fn downlink_from_proto(
+    df: &gw::DownlinkFrameItem,
+) -> Result<(hal::TxPacket, jitqueue::Priority)> {
     let mut data: [u8; 256] = [0; 256];
     let mut data_slice = df.phy_payload.clone();
     data_slice.resize(data.len(), 0);
@@ -178,6 +200,10 @@ pub fn downlink_from_proto(df: &gw::DownlinkFrameItem) -> Result<hal::TxPacket>
         ..Default::default()
     };
 
+    // Class A downlinks are sent after a delay, Class B downlinks are sent on GPS time and
+    // Class C downlinks are sent immediately.
+    let mut priority = jitqueue::Priority::ClassC;
+
     if let Some(timing) = &tx_info.timing
         && let Some(params) = &timing.parameters
     {
@@ -187,6 +213,7 @@ pub fn downlink_from_proto(df: &gw::DownlinkFrameItem) -> Result<hal::TxPacket>
             }
             gw::timing::Parameters::Delay(v) => {
                 packet.tx_mode = hal::TxMode::Timestamped;
+                priority = jitqueue::Priority::ClassA;
 
                 let ctx = &tx_info.context;
                 if ctx.len() != 4 {
@@ -210,6 +237,7 @@ pub fn downlink_from_proto(df: &gw::DownlinkFrameItem) -> Result<hal::TxPacket>
             }
             gw::timing::Parameters::GpsEpoch(v) => {
                 packet.tx_mode = hal::TxMode::Timestamped;
+                priority = jitqueue::Priority::ClassB;
 
                 match v.time_since_gps_epoch.as_ref() {
                     Some(v) => {
@@ -266,7 +294,7 @@ pub fn downlink_from_proto(df: &gw::DownlinkFrameItem) -> Result<hal::TxPacket>
         }
     }
 
-    Ok(packet)
+    Ok((packet, priority))
 }
 
 pub fn downlink_to_tx_info_proto(packet: &hal::TxPacket) -> Result<gw::DownlinkTxInfo> {
@@ -316,3 +344,145 @@ pub fn downlink_to_tx_info_proto(packet: &hal::TxPacket) -> Result<gw::DownlinkT
         ..Default::default()
     })
 }
+
+pub fn uplink_to_capture(packet: &hal::RxPacket) -> capture::Packet {
+    capture::Packet {
+        frequency: packet.freq_hz,
+        bandwidth: packet.bandwidth,
+        spreading_factor: spreading_factor_to_capture(packet.datarate),
+        coding_rate: coding_rate_to_capture(packet.coderate),
+        datarate: match packet.datarate {
+            hal::DataRate::FSK(v) => v,
+            _ => 0,
+        },
+        fsk: matches!(packet.modulation, hal::Modulation::FSK),
+        iq_inverted: false,
+        implicit_header: false,
+        crc: match packet.status {
+            hal::CRC::CRCOk => capture::Crc::Ok,
+            hal::CRC::BadCRC => capture::Crc::Bad,
+            _ => capture::Crc::None,
+        },
+        rssi: packet.rssis,
+        snr: packet.snr,
+        count_us: packet.count_us,
+        if_chain: packet.if_chain,
+        rf_chain: packet.rf_chain,
+        payload: packet.payload[..packet.size as usize].to_vec(),
+    }
+}
+
+pub fn downlink_to_capture(packet: &hal::TxPacket) -> capture::Packet {
+    capture::Packet {
+        frequency: packet.freq_hz,
+        bandwidth: packet.bandwidth,
+        spreading_factor: spreading_factor_to_capture(packet.datarate),
+        coding_rate: coding_rate_to_capture(packet.coderate),
+        datarate: match packet.datarate {
+            hal::DataRate::FSK(v) => v,
+            _ => 0,
+        },
+        fsk: matches!(packet.modulation, hal::Modulation::FSK),
+        iq_inverted: packet.invert_pol,
+        implicit_header: packet.no_header,
+        crc: match packet.no_crc {
+            true => capture::Crc::None,
+            false => capture::Crc::Ok,
+        },
+        rssi: 0.0,
+        snr: 0.0,
+        count_us: packet.count_us,
+        if_chain: 0,
+        rf_chain: packet.rf_chain,
+        payload: packet.payload[..packet.size as usize].to_vec(),
+    }
+}
+
+fn spreading_factor_to_capture(datarate: hal::DataRate) -> u8 {
+    match datarate {
+        hal::DataRate::SF5 => 5,
+        hal::DataRate::SF6 => 6,
+        hal::DataRate::SF7 => 7,
+        hal::DataRate::SF8 => 8,
+        hal::DataRate::SF9 => 9,
+        hal::DataRate::SF10 => 10,
+        hal::DataRate::SF11 => 11,
+        hal::DataRate::SF12 => 12,
+        _ => 0,
+    }
+}
+
+fn coding_rate_to_capture(coderate: hal::CodeRate) -> u8 {
+    match coderate {
+        hal::CodeRate::LoRa4_5 => 5,
+        hal::CodeRate::LoRa4_6 => 6,
+        hal::CodeRate::LoRa4_7 => 7,
+        hal::CodeRate::LoRa4_8 => 8,
+        hal::CodeRate::Undefined => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Only the timing related fields are set, as these are the fields under test.
+    fn downlink_item(timing: gw::timing::Parameters) -> gw::DownlinkFrameItem {
+        gw::DownlinkFrameItem {
+            tx_info: Some(gw::DownlinkTxInfo {
+                timing: Some(gw::Timing {
+                    parameters: Some(timing),
+                }),
+                context: vec![0, 0, 0, 100],
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_downlink_from_proto_immediately() {
+        let item = downlink_item(gw::timing::Parameters::Immediately(
+            gw::ImmediatelyTimingInfo {},
+        ));
+        let (packet, priority) = downlink_from_proto(&item).unwrap();
+        assert!(matches!(packet.tx_mode, hal::TxMode::Immediate));
+        assert_eq!(jitqueue::Priority::ClassC, priority);
+    }
+
+    #[test]
+    fn test_downlink_from_proto_delay() {
+        let item = downlink_item(gw::timing::Parameters::Delay(gw::DelayTimingInfo {
+            delay: Some(prost_types::Duration {
+                seconds: 1,
+                nanos: 0,
+            }),
+        }));
+        let (packet, priority) = downlink_from_proto(&item).unwrap();
+        assert!(matches!(packet.tx_mode, hal::TxMode::Timestamped));
+        assert_eq!(1_000_100, packet.count_us);
+        assert_eq!(jitqueue::Priority::ClassA, priority);
+    }
+
+    #[test]
+    fn test_downlink_from_proto_gps_epoch() {
+        gnss::sync(
+            &gnss::GnssResult::TimeSinceGpsEpoch(gnss::GnssTimeSinceGpsEpoch {
+                time_since_gps_epoch: Duration::from_secs(1_000_000),
+            }),
+            5_000_000,
+        )
+        .unwrap();
+
+        let item = downlink_item(gw::timing::Parameters::GpsEpoch(gw::GpsEpochTimingInfo {
+            time_since_gps_epoch: Some(prost_types::Duration {
+                seconds: 1_000_002,
+                nanos: 0,
+            }),
+        }));
+        let (packet, priority) = downlink_from_proto(&item).unwrap();
+        assert!(matches!(packet.tx_mode, hal::TxMode::Timestamped));
+        assert_eq!(7_000_000, packet.count_us);
+        assert_eq!(jitqueue::Priority::ClassB, priority);
+    }
+}
@@ -0,0 +1,417 @@
+//! Concentratord specific extensions to the ChirpStack Gateway API.
+//!
+//! The messages in this module are wire-compatible with the messages defined by the
+//! ChirpStack Gateway API (chirpstack_api::gw). Extensions use field tags and enum values of
+//! 100 and higher, so that clients that only know about the ChirpStack Gateway API will skip
+//! these fields (or see an unknown enum value).
+
+use chirpstack_api::gw;
+
+use crate::error::ConfigurationError;
+
+/// Event extensions.
+///
+/// This message is published on the same socket as gw::Event. As the oneof tags do not overlap
+/// with the gw::Event oneof tags, decoding this event as gw::Event results in an empty event.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Event {
+    #[prost(oneof = "event::Event", tags = "100, 101")]
+    pub event: Option<event::Event>,
+}
+
+pub mod event {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Event {
+        /// Final outcome of a downlink transmission.
+        #[prost(message, tag = "100")]
+        DownlinkTxResult(super::DownlinkTxResult),
+
+        /// Spectral scan results.
+        #[prost(message, tag = "101")]
+        SpectralScan(super::SpectralScan),
+    }
+}
+
+/// Command extensions.
+///
+/// This message is received on the same socket as gw::Command. As the oneof tags do not
+/// overlap with the gw::Command oneof tags, a command is either a gw::Command or an extension
+/// command.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Command {
+    #[prost(oneof = "command::Command", tags = "100, 101, 102")]
+    pub command: Option<command::Command>,
+}
+
+pub mod command {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Command {
+        /// Request a spectral scan. The response is a SpectralScanResponse.
+        #[prost(message, tag = "100")]
+        SpectralScan(super::SpectralScanRequest),
+
+        /// List the downlinks in the JIT queue. The response is a GetQueueResponse.
+        #[prost(message, tag = "101")]
+        GetQueue(super::GetQueueRequest),
+
+        /// Cancel a queued downlink. The response is a CancelDownlinkResponse.
+        #[prost(message, tag = "102")]
+        CancelDownlink(super::CancelDownlinkRequest),
+    }
+}
+
+/// Final outcome of a downlink, published after the downlink was acknowledged by the
+/// DownlinkTxAck and has been handed to (or dropped before reaching) the concentrator.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DownlinkTxResult {
+    /// Gateway ID.
+    #[prost(string, tag = "1")]
+    pub gateway_id: String,
+
+    /// Downlink ID.
+    #[prost(uint32, tag = "2")]
+    pub downlink_id: u32,
+
+    /// Outcome of the transmission.
+    #[prost(enumeration = "TxResult", tag = "3")]
+    pub result: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum TxResult {
+    /// The outcome of the transmission could not be determined.
+    Unknown = 0,
+
+    /// The packet has been emitted by the concentrator.
+    Emitted = 1,
+
+    /// The packet was not emitted because the channel was busy (LBT).
+    LbtBusy = 2,
+
+    /// The packet was dropped, as it was too late to hand it to the concentrator.
+    TooLate = 3,
+
+    /// The concentrator returned an error when scheduling the packet.
+    SendError = 4,
+
+    /// The packet was removed from the queue for a colliding packet with a higher priority.
+    Preempted = 5,
+
+    /// The packet was removed from the queue by the CancelDownlink command.
+    Cancelled = 6,
+
+    /// The transmission was aborted by the CancelDownlink command.
+    Aborted = 7,
+}
+
+impl TxResult {
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            TxResult::Unknown => "UNKNOWN",
+            TxResult::Emitted => "EMITTED",
+            TxResult::LbtBusy => "LBT_BUSY",
+            TxResult::TooLate => "TOO_LATE",
+            TxResult::SendError => "SEND_ERROR",
+            TxResult::Preempted => "PREEMPTED",
+            TxResult::Cancelled => "CANCELLED",
+            TxResult::Aborted => "ABORTED",
+        }
+    }
+}
+
+/// Downlink TX acknowledgement.
+///
+/// This message is wire-compatible with gw::DownlinkTxAck, but uses the extended TxAckStatus.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DownlinkTxAck {
+    /// Gateway ID.
+    #[prost(string, tag = "6")]
+    pub gateway_id: String,
+
+    /// Downlink ID.
+    #[prost(uint32, tag = "2")]
+    pub downlink_id: u32,
+
+    /// Downlink frame items.
+    #[prost(message, repeated, tag = "5")]
+    pub items: Vec<DownlinkTxAckItem>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DownlinkTxAckItem {
+    /// The Ack status of this item.
+    #[prost(enumeration = "TxAckStatus", tag = "1")]
+    pub status: i32,
+
+    /// TX power (EIRP) applied to the enqueued item. This can be lower than the requested TX
+    /// power in case it has been clamped to the max. EIRP of the band.
+    #[prost(int32, optional, tag = "100")]
+    pub tx_power: Option<i32>,
+
+    /// Concentrator counter value (in microseconds) at which the enqueued item will be sent.
+    /// For an immediate downlink, this is the slot chosen by the JIT queue.
+    #[prost(uint32, optional, tag = "101")]
+    pub count_us: Option<u32>,
+}
+
+/// TX acknowledgement status.
+///
+/// Values < 100 are equal to the gw::TxAckStatus values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum TxAckStatus {
+    Ignored = 0,
+    Ok = 1,
+    TooLate = 2,
+    TooEarly = 3,
+    CollisionPacket = 4,
+    CollisionBeacon = 5,
+    TxFreq = 6,
+    TxPower = 7,
+    GpsUnlocked = 8,
+    QueueFull = 9,
+    InternalError = 10,
+    DutyCycleOverflow = 11,
+
+    /// The time on air exceeds the max. dwell-time.
+    DwellTime = 100,
+}
+
+impl TxAckStatus {
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            TxAckStatus::DwellTime => "DWELL_TIME",
+            _ => self.to_gw().as_str_name(),
+        }
+    }
+
+    /// Returns the gw::TxAckStatus. Extended status values are returned as InternalError.
+    pub fn to_gw(self) -> gw::TxAckStatus {
+        gw::TxAckStatus::try_from(self as i32).unwrap_or(gw::TxAckStatus::InternalError)
+    }
+}
+
+impl From<gw::TxAckStatus> for TxAckStatus {
+    fn from(s: gw::TxAckStatus) -> Self {
+        TxAckStatus::try_from(s as i32).unwrap_or(TxAckStatus::InternalError)
+    }
+}
+
+/// Response to the gw::SetGatewayConfiguration command.
+///
+/// The response is sent after the configuration has been applied, or when it has been
+/// rejected. On rejection, the previous configuration remains active.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SetGatewayConfigurationResponse {
+    /// The configuration has been accepted and applied.
+    #[prost(bool, tag = "1")]
+    pub accepted: bool,
+
+    /// Reason why the configuration was rejected.
+    #[prost(string, tag = "2")]
+    pub error: String,
+
+    /// Error code.
+    #[prost(enumeration = "ConfigurationErrorCode", tag = "3")]
+    pub error_code: i32,
+
+    /// Frequency of the channel which caused the error, 0 if the error is not related to a
+    /// single channel.
+    #[prost(uint32, tag = "4")]
+    pub frequency: u32,
+}
+
+impl From<Result<(), ConfigurationError>> for SetGatewayConfigurationResponse {
+    fn from(r: Result<(), ConfigurationError>) -> Self {
+        match r {
+            Ok(_) => SetGatewayConfigurationResponse {
+                accepted: true,
+                ..Default::default()
+            },
+            Err(e) => SetGatewayConfigurationResponse {
+                accepted: false,
+                error: e.to_string(),
+                error_code: e.code().into(),
+                frequency: e.frequency(),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum ConfigurationErrorCode {
+    /// No error.
+    None = 0,
+
+    /// The configuration exceeds the max. number of channels.
+    TooManyChannels = 1,
+
+    /// The configuration exceeds the max. number of multi-SF channels.
+    TooManyMultiSfChannels = 2,
+
+    /// A channel does not fit within the bandwidth of the radios.
+    ChannelOutsideRadioBandwidth = 3,
+
+    /// A channel has an invalid or unsupported modulation configuration.
+    InvalidChannel = 4,
+
+    /// The stats interval is invalid.
+    InvalidStatsInterval = 5,
+
+    /// The concentrator could not be started using the configuration.
+    Concentrator = 6,
+}
+
+/// Spectral scan request.
+///
+/// The requested frequencies are scanned as soon as the SX1261 radio is available. The
+/// results are published as SpectralScan events.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SpectralScanRequest {
+    /// First frequency to scan (Hz).
+    #[prost(uint32, tag = "1")]
+    pub frequency_start: u32,
+
+    /// Frequency step (Hz).
+    #[prost(uint32, tag = "2")]
+    pub frequency_step: u32,
+
+    /// Number of frequencies to scan.
+    #[prost(uint32, tag = "3")]
+    pub channels: u32,
+}
+
+/// Spectral scan response.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SpectralScanResponse {
+    /// The spectral scan has been scheduled.
+    #[prost(bool, tag = "1")]
+    pub scheduled: bool,
+
+    /// Most recent (cached) results for the requested frequencies, if any.
+    #[prost(message, repeated, tag = "2")]
+    pub results: Vec<SpectralScanResult>,
+
+    /// Reason why the spectral scan was not scheduled.
+    #[prost(string, tag = "3")]
+    pub error: String,
+}
+
+/// Spectral scan event.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SpectralScan {
+    /// Gateway ID.
+    #[prost(string, tag = "1")]
+    pub gateway_id: String,
+
+    /// Results.
+    #[prost(message, repeated, tag = "2")]
+    pub results: Vec<SpectralScanResult>,
+}
+
+/// Spectral scan result of a single frequency.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SpectralScanResult {
+    /// Frequency (Hz).
+    #[prost(uint32, tag = "1")]
+    pub frequency: u32,
+
+    /// Time of the scan.
+    #[prost(message, optional, tag = "2")]
+    pub time: Option<chirpstack_api::prost_types::Timestamp>,
+
+    /// RSSI histogram.
+    #[prost(message, repeated, tag = "3")]
+    pub histogram: Vec<RssiHistogramBin>,
+}
+
+/// RSSI histogram bin.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RssiHistogramBin {
+    /// RSSI level (dBm).
+    #[prost(int32, tag = "1")]
+    pub rssi: i32,
+
+    /// Number of measurements at this RSSI level.
+    #[prost(uint32, tag = "2")]
+    pub count: u32,
+}
+
+/// Request the downlinks in the JIT queue.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetQueueRequest {}
+
+/// JIT queue response.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetQueueResponse {
+    /// Queued downlinks, ordered by their transmission time.
+    #[prost(message, repeated, tag = "1")]
+    pub items: Vec<QueueItem>,
+}
+
+/// Downlink in the JIT queue.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct QueueItem {
+    /// Downlink ID.
+    #[prost(uint32, tag = "1")]
+    pub downlink_id: u32,
+
+    /// Concentrator counter value (in microseconds) at which the downlink will be sent.
+    #[prost(uint32, tag = "2")]
+    pub count_us: u32,
+
+    /// Frequency (Hz).
+    #[prost(uint32, tag = "3")]
+    pub frequency: u32,
+
+    /// Time on air.
+    #[prost(message, optional, tag = "4")]
+    pub time_on_air: Option<chirpstack_api::prost_types::Duration>,
+
+    /// Time until the transmission starts.
+    #[prost(message, optional, tag = "5")]
+    pub time_until_tx: Option<chirpstack_api::prost_types::Duration>,
+
+    /// RF chain used for the transmission.
+    #[prost(uint32, tag = "6")]
+    pub rf_chain: u32,
+}
+
+/// Cancel downlink request.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CancelDownlinkRequest {
+    /// Downlink ID.
+    #[prost(uint32, tag = "1")]
+    pub downlink_id: u32,
+
+    /// Abort the transmission when the downlink has already been handed to the
+    /// concentrator.
+    #[prost(bool, tag = "2")]
+    pub abort_tx: bool,
+}
+
+/// Cancel downlink response.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CancelDownlinkResponse {
+    /// Outcome of the cancellation.
+    #[prost(enumeration = "CancelStatus", tag = "1")]
+    pub status: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum CancelStatus {
+    /// The downlink is not queued nor being transmitted.
+    NotFound = 0,
+
+    /// The downlink has been removed from the queue.
+    Removed = 1,
+
+    /// The transmission of the downlink has been aborted.
+    Aborted = 2,
+
+    /// The concentrator returned an error when aborting the transmission.
+    AbortError = 3,
+}
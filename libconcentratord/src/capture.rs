@@ -0,0 +1,359 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::sync::{LazyLock, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use log::info;
+
+// LINKTYPE_LORATAP, see https://www.tcpdump.org/linktypes.html.
+const LINKTYPE_LORATAP: u32 = 270;
+const SNAPLEN: u32 = 65535;
+
+// LoRaTap version 1 header length.
+const LORATAP_HEADER_LEN: u16 = 35;
+
+// LoRaTap RSSI values are encoded as an offset from -139 dBm.
+const LORATAP_RSSI_OFFSET: f32 = 139.0;
+
+static CAPTURE: LazyLock<Mutex<Option<Writer>>> = LazyLock::new(|| Mutex::new(None));
+
+/// CRC status of a captured packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crc {
+    Ok,
+    Bad,
+    None,
+}
+
+/// Packet to capture, this is the HAL independent representation of the received or sent
+/// packet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packet {
+    /// Frequency (Hz).
+    pub frequency: u32,
+    /// Bandwidth (Hz).
+    pub bandwidth: u32,
+    /// Spreading-factor (LoRa only).
+    pub spreading_factor: u8,
+    /// Coding-rate denominator, e.g. 5 for 4/5 (LoRa only).
+    pub coding_rate: u8,
+    /// Datarate (FSK only).
+    pub datarate: u32,
+    /// FSK modulation.
+    pub fsk: bool,
+    /// Inverted IQ polarity.
+    pub iq_inverted: bool,
+    /// Implicit header.
+    pub implicit_header: bool,
+    /// CRC status.
+    pub crc: Crc,
+    /// RSSI (dBm), 0 for sent packets.
+    pub rssi: f32,
+    /// SNR (dB), 0 for sent packets.
+    pub snr: f32,
+    /// Internal concentrator counter (microseconds).
+    pub count_us: u32,
+    /// IF chain.
+    pub if_chain: u8,
+    /// RF chain.
+    pub rf_chain: u8,
+    /// Payload.
+    pub payload: Vec<u8>,
+}
+
+/// Setup the capture. When the path is empty, packets will not be captured.
+///
+/// The sync-word is set to the LoRaWAN public or private sync-word, depending on the
+/// lorawan_public argument. The gateway ID is set as the source gateway of the packets.
+pub fn setup(
+    path: &str,
+    max_file_size: u64,
+    max_files: usize,
+    lorawan_public: bool,
+    gateway_id: &[u8],
+) -> Result<()> {
+    let mut capture = CAPTURE.lock().unwrap();
+
+    if path.is_empty() {
+        *capture = None;
+        return Ok(());
+    }
+
+    info!(
+        "Setting up packet capture, path: {}, max_file_size: {}, max_files: {}",
+        path, max_file_size, max_files
+    );
+
+    let sync_word = if lorawan_public { 0x34 } else { 0x12 };
+    let gateway_id = gateway_id.try_into().context("Invalid gateway ID")?;
+    *capture = Some(Writer::new(
+        path,
+        max_file_size,
+        max_files,
+        sync_word,
+        gateway_id,
+    )?);
+    Ok(())
+}
+
+/// Capture the given packet. This is a no-op when the capture has not been setup.
+pub fn capture(pkt: &Packet) -> Result<()> {
+    let mut capture = CAPTURE.lock().unwrap();
+    if let Some(capture) = capture.as_mut() {
+        capture.write(SystemTime::now(), pkt)?;
+    }
+
+    Ok(())
+}
+
+/// PCAP writer which rotates the file when it exceeds the max. size.
+///
+/// On rotation, the current file is renamed to path.1 (and path.1 to path.2, ...) and the
+/// oldest file is removed such that at most max_files files are kept. When max_files is less
+/// than 2, the current file is truncated instead.
+pub struct Writer {
+    path: String,
+    max_file_size: u64,
+    max_files: usize,
+    sync_word: u8,
+    gateway_id: [u8; 8],
+    file: BufWriter<File>,
+    file_size: u64,
+}
+
+impl Writer {
+    pub fn new(
+        path: &str,
+        max_file_size: u64,
+        max_files: usize,
+        sync_word: u8,
+        gateway_id: [u8; 8],
+    ) -> Result<Self> {
+        let (file, file_size) = Writer::create(path)?;
+
+        Ok(Writer {
+            path: path.to_string(),
+            max_file_size,
+            max_files,
+            sync_word,
+            gateway_id,
+            file,
+            file_size,
+        })
+    }
+
+    pub fn write(&mut self, time: SystemTime, pkt: &Packet) -> Result<()> {
+        if self.max_file_size != 0 && self.file_size >= self.max_file_size {
+            self.rotate()?;
+        }
+
+        let time = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut b = loratap_header(pkt, self.sync_word, &self.gateway_id);
+        b.extend_from_slice(&pkt.payload);
+
+        // record header
+        self.file
+            .write_all(&(time.as_secs() as u32).to_le_bytes())?;
+        self.file.write_all(&time.subsec_micros().to_le_bytes())?;
+        self.file.write_all(&(b.len() as u32).to_le_bytes())?;
+        self.file.write_all(&(b.len() as u32).to_le_bytes())?;
+
+        // record data
+        self.file.write_all(&b)?;
+        self.file.flush()?;
+
+        self.file_size += 16 + b.len() as u64;
+
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        self.file.flush()?;
+
+        if self.max_files > 1 {
+            for i in (1..self.max_files - 1).rev() {
+                let from = format!("{}.{}", self.path, i);
+                if fs::metadata(&from).is_ok() {
+                    fs::rename(&from, format!("{}.{}", self.path, i + 1))?;
+                }
+            }
+            fs::rename(&self.path, format!("{}.1", self.path))?;
+        } else {
+            fs::remove_file(&self.path)?;
+        }
+
+        let (file, file_size) = Writer::create(&self.path)?;
+        self.file = file;
+        self.file_size = file_size;
+
+        Ok(())
+    }
+
+    // Open the capture file. When the file already exists (e.g. after a restart), packets are
+    // appended to it.
+    fn create(path: &str) -> Result<(BufWriter<File>, u64)> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .context("Open capture file")?;
+        let file_size = file.metadata()?.len();
+        let mut file = BufWriter::new(file);
+
+        if file_size != 0 {
+            return Ok((file, file_size));
+        }
+
+        // global header
+        file.write_all(&0xa1b2c3d4_u32.to_le_bytes())?;
+        file.write_all(&2_u16.to_le_bytes())?;
+        file.write_all(&4_u16.to_le_bytes())?;
+        file.write_all(&0_i32.to_le_bytes())?;
+        file.write_all(&0_u32.to_le_bytes())?;
+        file.write_all(&SNAPLEN.to_le_bytes())?;
+        file.write_all(&LINKTYPE_LORATAP.to_le_bytes())?;
+        file.flush()?;
+
+        Ok((file, 24))
+    }
+}
+
+// Returns the LoRaTap (version 1) header. All multi-byte fields are big-endian.
+fn loratap_header(pkt: &Packet, sync_word: u8, gateway_id: &[u8; 8]) -> Vec<u8> {
+    let mut b = Vec::with_capacity(LORATAP_HEADER_LEN as usize);
+    let rssi = (pkt.rssi + LORATAP_RSSI_OFFSET).round().clamp(0.0, 255.0) as u8;
+
+    // version, padding and header length
+    b.push(1);
+    b.push(0);
+    b.extend_from_slice(&LORATAP_HEADER_LEN.to_be_bytes());
+
+    // channel (bandwidth in 125 kHz steps)
+    b.extend_from_slice(&pkt.frequency.to_be_bytes());
+    b.push((pkt.bandwidth / 125000) as u8);
+    b.push(pkt.spreading_factor);
+
+    // packet rssi, max rssi, current rssi and snr (in 0.25 dB steps)
+    b.push(rssi);
+    b.push(rssi);
+    b.push(rssi);
+    b.push(((pkt.snr * 4.0).round().clamp(-128.0, 127.0) as i8) as u8);
+
+    // sync-word
+    b.push(sync_word);
+
+    // version 1 fields
+    b.extend_from_slice(gateway_id);
+    b.extend_from_slice(&pkt.count_us.to_be_bytes());
+    b.push(
+        (pkt.fsk as u8)
+            | (pkt.iq_inverted as u8) << 1
+            | (pkt.implicit_header as u8) << 2
+            | match pkt.crc {
+                Crc::Ok => 1 << 3,
+                Crc::Bad => 1 << 4,
+                Crc::None => 1 << 5,
+            },
+    );
+    b.push(pkt.coding_rate);
+    b.extend_from_slice(&(pkt.datarate.min(u16::MAX.into()) as u16).to_be_bytes());
+    b.push(pkt.if_chain);
+    b.push(pkt.rf_chain);
+    b.extend_from_slice(&0_u16.to_be_bytes());
+
+    b
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::time::Duration;
+
+    use super::*;
+
+    const GATEWAY_ID: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    fn packet() -> Packet {
+        Packet {
+            frequency: 868100000,
+            bandwidth: 125000,
+            spreading_factor: 7,
+            coding_rate: 5,
+            datarate: 0,
+            fsk: false,
+            iq_inverted: false,
+            implicit_header: false,
+            crc: Crc::Ok,
+            rssi: -60.0,
+            snr: 7.5,
+            count_us: 1234,
+            if_chain: 2,
+            rf_chain: 1,
+            payload: vec![1, 2, 3],
+        }
+    }
+
+    #[test]
+    fn test_loratap_header() {
+        let b = loratap_header(&packet(), 0x34, &GATEWAY_ID);
+        assert_eq!(
+            vec![
+                1, 0, 0, 35, // version, padding, length
+                0x33, 0xbe, 0x27, 0xa0, 1, 7, // channel
+                79, 79, 79, 30,   // rssi
+                0x34, // sync-word
+                1, 2, 3, 4, 5, 6, 7, 8, // source gateway
+                0, 0, 4, 210,  // count_us
+                0x08, // flags
+                5,    // cr
+                0, 0, // datarate
+                2, 1, // if chain, rf chain
+                0, 0, // tag
+            ],
+            b
+        );
+    }
+
+    #[test]
+    fn test_writer_rotate() {
+        let path = env::temp_dir().join(format!("capture_test_{}.pcap", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+        let time = UNIX_EPOCH + Duration::from_secs(10);
+
+        // global header (24) + record header (16) + loratap header (35) + payload (3)
+        let mut w = Writer::new(path, 78, 2, 0x34, GATEWAY_ID).unwrap();
+        w.write(time, &packet()).unwrap();
+        assert_eq!(78, fs::metadata(path).unwrap().len());
+
+        // the first file exceeds the max size, this causes a rotation
+        w.write(time, &packet()).unwrap();
+        assert_eq!(78, fs::metadata(path).unwrap().len());
+        assert_eq!(78, fs::metadata(format!("{}.1", path)).unwrap().len());
+
+        let b = fs::read(path).unwrap();
+        assert_eq!(0xa1b2c3d4_u32.to_le_bytes(), b[0..4]);
+        assert_eq!(LINKTYPE_LORATAP.to_le_bytes(), b[20..24]);
+        assert_eq!(10_u32.to_le_bytes(), b[24..28]);
+        assert_eq!(38_u32.to_le_bytes(), b[32..36]);
+        assert_eq!(vec![1, 2, 3], b[75..78]);
+
+        // re-opening the file appends to it
+        let mut w = Writer::new(path, 0, 2, 0x34, GATEWAY_ID).unwrap();
+        w.write(time, &packet()).unwrap();
+        assert_eq!(132, fs::metadata(path).unwrap().len());
+
+        fs::remove_file(path).unwrap();
+        fs::remove_file(format!("{}.1", path)).unwrap();
+
+        // without rotated files, the file is truncated
+        let mut w = Writer::new(path, 78, 1, 0x34, GATEWAY_ID).unwrap();
+        w.write(time, &packet()).unwrap();
+        w.write(time, &packet()).unwrap();
+        assert_eq!(78, fs::metadata(path).unwrap().len());
+        assert!(fs::metadata(format!("{}.1", path)).is_err());
+
+        fs::remove_file(path).unwrap();
+    }
+}
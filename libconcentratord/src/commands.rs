@@ -2,16 +2,49 @@ use std::time::Duration;
 
 use anyhow::Result;
 use chirpstack_api::{gw, prost::Message};
-use log::info;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
 
+use crate::api;
 use crate::error::Error;
 use crate::socket::ZMQ_CONTEXT;
 
-pub fn get_socket(bind: &str) -> Result<zmq::Socket> {
-    info!("Creating socket for receiving commands, bind: {}", bind);
+/// Command socket type.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum SocketType {
+    /// REP socket. Requests are handled in lock-step, one request at a time.
+    #[default]
+    Rep,
+
+    /// ROUTER socket. Clients are handled independently, a client that does not read its
+    /// replies does not block other clients. Besides REQ clients, DEALER clients are supported
+    /// which may prefix the request with (one or multiple) request ID frames, followed by an
+    /// empty delimiter frame. These frames are returned as-is with the reply, such that the
+    /// client can match replies with requests.
+    Router,
+}
+
+/// Create the command socket.
+///
+/// In case of a ROUTER socket, the timeout defines how long sending a reply may block (e.g.
+/// because the client does not read its replies) before the reply is discarded.
+pub fn get_socket(bind: &str, socket_type: SocketType, timeout: Duration) -> Result<zmq::Socket> {
+    info!(
+        "Creating socket for receiving commands, bind: {}, socket_type: {:?}",
+        bind, socket_type
+    );
 
     let zmq_ctx = ZMQ_CONTEXT.lock().unwrap();
-    let sock = zmq_ctx.socket(zmq::REP)?;
+    let sock = match socket_type {
+        SocketType::Rep => zmq_ctx.socket(zmq::REP)?,
+        SocketType::Router => {
+            let sock = zmq_ctx.socket(zmq::ROUTER)?;
+            sock.set_router_mandatory(true)?;
+            sock.set_sndtimeo(timeout.as_millis() as i32)?;
+            sock
+        }
+    };
     sock.bind(bind)?;
     Ok(sock)
 }
@@ -36,6 +69,76 @@ pub enum Command {
     Configuration(chirpstack_api::gw::GatewayConfiguration),
 }
 
+/// Received request, this is either a ChirpStack Gateway API command or a Concentratord
+/// extension command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    Gw(gw::Command),
+    Extension(api::Command),
+}
+
+impl Request {
+    fn decode(b: &[u8]) -> Result<Self> {
+        let cmd = gw::Command::decode(b)?;
+        if cmd.command.is_some() {
+            return Ok(Request::Gw(cmd));
+        }
+
+        let cmd_ext = api::Command::decode(b)?;
+        if cmd_ext.command.is_some() {
+            return Ok(Request::Extension(cmd_ext));
+        }
+
+        Ok(Request::Gw(cmd))
+    }
+}
+
+/// Envelope of a received command.
+///
+/// For ROUTER sockets this contains the routing frames (the client identity, optionally
+/// followed by request ID frames) that are needed to return the reply to the right client.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Envelope(Vec<Vec<u8>>);
+
+impl Envelope {
+    /// Split the received message parts into the envelope and the request body.
+    fn from_parts(mut parts: Vec<Vec<u8>>) -> Result<(Self, Vec<u8>)> {
+        let delimiter = parts
+            .iter()
+            .position(|p| p.is_empty())
+            .ok_or_else(|| anyhow!("Command is missing the envelope delimiter"))?;
+
+        if delimiter + 2 != parts.len() {
+            return Err(anyhow!("Command must contain exactly one body frame"));
+        }
+
+        let body = parts.pop().unwrap_or_default();
+        parts.truncate(delimiter);
+        Ok((Envelope(parts), body))
+    }
+}
+
+/// Send the reply for the command with the given envelope.
+///
+/// For ROUTER sockets, failing to deliver the reply (e.g. the client disconnected or the reply
+/// timed out) is logged, as this must not affect the handling of other clients.
+pub fn send_reply(sock: &zmq::Socket, envelope: Envelope, reply: Vec<u8>) -> Result<()> {
+    if sock.get_socket_type()? != zmq::ROUTER {
+        sock.send(reply, 0)?;
+        return Ok(());
+    }
+
+    let mut parts = envelope.0;
+    parts.push(Vec::new());
+    parts.push(reply);
+
+    if let Err(e) = sock.send_multipart(parts, 0) {
+        warn!("Sending command reply failed, error: {}", e);
+    }
+
+    Ok(())
+}
+
 pub struct Reader<'a> {
     rep_sock: &'a zmq::Socket,
     timeout: Duration,
@@ -45,12 +148,22 @@ impl<'a> Reader<'a> {
     pub fn new(rep_sock: &'a zmq::Socket, timeout: Duration) -> Self {
         Reader { rep_sock, timeout }
     }
+
+    fn read(&self) -> Result<(Envelope, Vec<u8>)> {
+        if self.rep_sock.get_socket_type()? == zmq::ROUTER {
+            Envelope::from_parts(self.rep_sock.recv_multipart(0)?)
+        } else {
+            Ok((Envelope::default(), self.rep_sock.recv_bytes(0)?))
+        }
+    }
 }
 
 impl Iterator for Reader<'_> {
-    type Item = Result<gw::Command, Error>;
+    type Item = Result<(Request, Envelope), Error>;
 
-    fn next(&mut self) -> Option<Result<gw::Command, Error>> {
+    /// Return the next command. In case the command could not be decoded, an empty reply is
+    /// sent before the error is returned.
+    fn next(&mut self) -> Option<Result<(Request, Envelope), Error>> {
         // set poller so that we can timeout
         let mut items = [self.rep_sock.as_poll_item(zmq::POLLIN)];
         zmq::poll(&mut items, self.timeout.as_millis() as i64).unwrap();
@@ -58,10 +171,125 @@ impl Iterator for Reader<'_> {
             return Some(Err(Error::Timeout));
         }
 
-        let b = self.rep_sock.recv_bytes(0).unwrap();
-        match gw::Command::decode(b.as_slice()).map_err(|e| Error::Anyhow(anyhow::Error::new(e))) {
-            Ok(v) => Some(Ok(v)),
-            Err(e) => Some(Err(e)),
+        let (envelope, b) = match self.read() {
+            Ok(v) => v,
+            // A malformed ROUTER message can not be replied to.
+            Err(e) => return Some(Err(Error::Anyhow(e))),
+        };
+
+        match Request::decode(&b).map_err(Error::Anyhow) {
+            Ok(v) => Some(Ok((v, envelope))),
+            Err(e) => {
+                if let Err(e) = send_reply(self.rep_sock, envelope, Vec::new()) {
+                    return Some(Err(Error::Anyhow(e)));
+                }
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_envelope_from_parts() {
+        // REQ client.
+        let (envelope, body) =
+            Envelope::from_parts(vec![vec![0, 1], vec![], vec![10, 11]]).unwrap();
+        assert_eq!(Envelope(vec![vec![0, 1]]), envelope);
+        assert_eq!(vec![10, 11], body);
+
+        // DEALER client with request ID.
+        let (envelope, body) =
+            Envelope::from_parts(vec![vec![0, 1], vec![5], vec![], vec![10, 11]]).unwrap();
+        assert_eq!(Envelope(vec![vec![0, 1], vec![5]]), envelope);
+        assert_eq!(vec![10, 11], body);
+
+        // Missing delimiter.
+        assert!(Envelope::from_parts(vec![vec![0, 1], vec![10, 11]]).is_err());
+
+        // Multiple body frames.
+        assert!(Envelope::from_parts(vec![vec![0, 1], vec![], vec![10], vec![11]]).is_err());
+    }
+
+    #[test]
+    fn test_request_decode() {
+        let cmd = gw::Command {
+            command: Some(gw::command::Command::GetGatewayId(
+                gw::GetGatewayIdRequest {},
+            )),
+        };
+        assert_eq!(
+            Request::Gw(cmd.clone()),
+            Request::decode(&cmd.encode_to_vec()).unwrap()
+        );
+
+        let cmd = api::Command {
+            command: Some(api::command::Command::SpectralScan(
+                api::SpectralScanRequest {
+                    frequency_start: 868100000,
+                    frequency_step: 200000,
+                    channels: 3,
+                },
+            )),
+        };
+        assert_eq!(
+            Request::Extension(cmd.clone()),
+            Request::decode(&cmd.encode_to_vec()).unwrap()
+        );
+
+        assert_eq!(
+            Request::Gw(gw::Command::default()),
+            Request::decode(&[]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_router_socket() {
+        let bind = "inproc://test_router_socket";
+        let sock = get_socket(bind, SocketType::Router, Duration::from_millis(100)).unwrap();
+
+        let zmq_ctx = ZMQ_CONTEXT.lock().unwrap().clone();
+
+        // A REQ client that never reads its reply.
+        let req_a = zmq_ctx.socket(zmq::REQ).unwrap();
+        req_a.connect(bind).unwrap();
+
+        // A DEALER client using request IDs.
+        let dealer = zmq_ctx.socket(zmq::DEALER).unwrap();
+        dealer.connect(bind).unwrap();
+
+        let cmd = gw::Command {
+            command: Some(gw::command::Command::GetGatewayId(
+                gw::GetGatewayIdRequest {},
+            )),
+        };
+
+        req_a.send(cmd.encode_to_vec(), 0).unwrap();
+        dealer
+            .send_multipart([vec![1], vec![], cmd.encode_to_vec()], 0)
+            .unwrap();
+        dealer
+            .send_multipart([vec![2], vec![], cmd.encode_to_vec()], 0)
+            .unwrap();
+
+        let mut reader = Reader::new(&sock, Duration::from_millis(100));
+        for _ in 0..3 {
+            let (c, envelope) = reader.next().unwrap().unwrap();
+            assert_eq!(Request::Gw(cmd.clone()), c);
+            let reply = envelope.0.last().cloned().unwrap();
+            send_reply(&sock, envelope, reply).unwrap();
         }
+
+        assert_eq!(
+            vec![vec![1], vec![], vec![1]],
+            dealer.recv_multipart(0).unwrap()
+        );
+        assert_eq!(
+            vec![vec![2], vec![], vec![2]],
+            dealer.recv_multipart(0).unwrap()
+        );
     }
 }
@@ -0,0 +1,249 @@
+//! Configuration file loading.
+//!
+//! The configuration can be split over multiple files, which are combined before the
+//! configuration is decoded. Environment variables are replaced within each file:
+//!
+//! * `${VAR}` is replaced by the value of VAR. It is an error when VAR is not set.
+//! * `${VAR:-default}` is replaced by the value of VAR, or by default when VAR is not set
+//!   or empty.
+//! * `$VAR` is replaced by the value of VAR when set, else it is kept as-is.
+//! * `$$` is replaced by a single `$`.
+
+use std::{env, fs};
+
+use serde::de::DeserializeOwned;
+
+use crate::error::ConfigFileError;
+
+/// A configuration file, after replacing environment variables.
+pub struct File {
+    pub name: String,
+    pub content: String,
+}
+
+impl File {
+    pub fn read(name: &str) -> Result<Self, ConfigFileError> {
+        let content =
+            fs::read_to_string(name).map_err(|e| ConfigFileError::Read(name.to_string(), e))?;
+
+        // Replace environment variables in config.
+        let content = substitute(&content, |k| env::var(k).ok()).map_err(|(offset, message)| {
+            ConfigFileError::EnvVar {
+                file: name.to_string(),
+                line: line_number(&content, offset),
+                message,
+            }
+        })?;
+
+        Ok(File {
+            name: name.to_string(),
+            content,
+        })
+    }
+}
+
+/// Read the given configuration files and decode the configuration.
+pub fn load<T: DeserializeOwned>(filenames: &[String]) -> Result<T, ConfigFileError> {
+    let files = filenames
+        .iter()
+        .map(|v| File::read(v))
+        .collect::<Result<Vec<File>, ConfigFileError>>()?;
+    parse(&files)
+}
+
+/// Decode the configuration from the combined configuration files. A parse error reports
+/// the file and line causing the error, when known.
+pub fn parse<T: DeserializeOwned>(files: &[File]) -> Result<T, ConfigFileError> {
+    let content: String = files.iter().map(|f| f.content.as_str()).collect();
+    toml::from_str(&content).map_err(|e| {
+        let location = e
+            .span()
+            .and_then(|span| file_offset(files, span.start))
+            .map(|(i, offset)| {
+                (
+                    files[i].name.clone(),
+                    line_number(&files[i].content, offset),
+                )
+            });
+
+        ConfigFileError::Parse {
+            file: location.as_ref().map(|v| v.0.clone()),
+            line: location.map(|v| v.1),
+            message: e.message().trim().to_string(),
+        }
+    })
+}
+
+/// Returns the line number (starting at 1) of the given byte offset.
+pub fn line_number(content: &str, offset: usize) -> usize {
+    content[..offset.min(content.len())].matches('\n').count() + 1
+}
+
+// Replace the variables in the given content using the lookup function. On error, this
+// returns the byte offset of the variable and the error message.
+fn substitute<F>(content: &str, lookup: F) -> Result<String, (usize, String)>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut out = String::with_capacity(content.len());
+    let mut rest = content;
+
+    while let Some(i) = rest.find('$') {
+        let offset = content.len() - rest.len() + i;
+        out.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+
+        if let Some(r) = rest.strip_prefix('$') {
+            out.push('$');
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix('{') {
+            let end = r
+                .find('}')
+                .ok_or_else(|| (offset, "Unterminated variable".to_string()))?;
+            let (name, default) = match r[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&r[..end], None),
+            };
+            if !is_name(name) {
+                return Err((offset, format!("Invalid variable name: {}", name)));
+            }
+
+            match (lookup(name), default) {
+                (Some(v), Some(default)) if v.is_empty() => out.push_str(default),
+                (Some(v), _) => out.push_str(&v),
+                (None, Some(default)) => out.push_str(default),
+                (None, None) => {
+                    return Err((offset, format!("Environment variable is not set: {}", name)));
+                }
+            }
+            rest = &r[end + 1..];
+        } else {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let name = &rest[..len];
+
+            match lookup(name).filter(|_| is_name(name)) {
+                Some(v) => out.push_str(&v),
+                None => {
+                    out.push('$');
+                    out.push_str(name);
+                }
+            }
+            rest = &rest[len..];
+        }
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn is_name(s: &str) -> bool {
+    s.chars()
+        .next()
+        .map(|c| c.is_ascii_alphabetic() || c == '_')
+        .unwrap_or(false)
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Map an offset within the combined configuration to the file index and the offset within
+// this file.
+fn file_offset(files: &[File], mut offset: usize) -> Option<(usize, usize)> {
+    for (i, f) in files.iter().enumerate() {
+        if offset < f.content.len() || i == files.len() - 1 {
+            return Some((i, offset));
+        }
+        offset -= f.content.len();
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize, Debug)]
+    struct Config {
+        gateway_id: String,
+        channels: Vec<u32>,
+    }
+
+    fn files(files: &[(&str, &str)]) -> Vec<File> {
+        files
+            .iter()
+            .map(|(name, content)| File {
+                name: name.to_string(),
+                content: content.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_substitute() {
+        let lookup = |k: &str| match k {
+            "HOME" => Some("/home/pi".to_string()),
+            "EMPTY" => Some("".to_string()),
+            _ => None,
+        };
+
+        let tests = [
+            ("dir=\"${HOME}/data\"", "dir=\"/home/pi/data\""),
+            ("dir=\"$HOME/data\"", "dir=\"/home/pi/data\""),
+            ("dir=\"$HOMEDIR\"", "dir=\"$HOMEDIR\""),
+            ("dir=\"${HOMEDIR:-/tmp}\"", "dir=\"/tmp\""),
+            ("dir=\"${EMPTY:-/tmp}\"", "dir=\"/tmp\""),
+            ("dir=\"${EMPTY}\"", "dir=\"\""),
+            ("dir=\"${HOMEDIR:-}\"", "dir=\"\""),
+            ("price=\"$$HOME $5 $\"", "price=\"$HOME $5 $\""),
+        ];
+        for (content, expected) in tests {
+            assert_eq!(
+                expected,
+                substitute(content, lookup).unwrap(),
+                "{}",
+                content
+            );
+        }
+
+        assert_eq!(
+            Err((14, "Environment variable is not set: HOMEDIR".to_string())),
+            substitute("# config\ndir=\"${HOMEDIR}\"", lookup)
+        );
+        assert_eq!(
+            Err((5, "Unterminated variable".to_string())),
+            substitute("dir=\"${HOME\"", lookup)
+        );
+        assert_eq!(
+            Err((5, "Invalid variable name: 1HOME".to_string())),
+            substitute("dir=\"${1HOME}\"", lookup)
+        );
+    }
+
+    #[test]
+    fn test_load() {
+        let err = load::<Config>(&["/does/not/exist.toml".into()]).unwrap_err();
+        assert!(matches!(err, ConfigFileError::Read(_, _)));
+        assert_eq!(66, err.exit_code());
+
+        let config: Config = parse(&files(&[
+            ("concentratord.toml", "gateway_id=\"0102030405060708\"\n"),
+            ("channels.toml", "channels=[868100000]\n"),
+        ]))
+        .unwrap();
+        assert_eq!("0102030405060708", config.gateway_id);
+        assert_eq!(vec![868100000], config.channels);
+
+        let err = parse::<Config>(&files(&[
+            ("concentratord.toml", "gateway_id=\"0102030405060708\"\n"),
+            ("channels.toml", "\nchannels=868100000\n"),
+        ]))
+        .unwrap_err();
+        assert_eq!(
+            "Parse config file error, file: channels.toml, line: 2, error: invalid type: integer `868100000`, expected a sequence",
+            err.to_string()
+        );
+        assert_eq!(78, err.exit_code());
+    }
+}
@@ -0,0 +1,57 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chirpstack_api::gw;
+use chirpstack_api::prost::Message;
+
+use crate::helpers;
+
+/// Load the gateway configuration from the given state file. This returns None when the file
+/// does not exist.
+pub fn load(path: &str) -> Result<Option<gw::GatewayConfiguration>> {
+    if !Path::new(path).exists() {
+        return Ok(None);
+    }
+
+    let b = fs::read(path).context("Read configuration state file")?;
+    let pl = gw::GatewayConfiguration::decode(b.as_slice())
+        .context("Decode configuration state file")?;
+
+    Ok(Some(pl))
+}
+
+/// Save the gateway configuration to the given state file. The file is replaced atomically,
+/// such that a crash does not leave a partially written state file behind.
+pub fn save(path: &str, pl: &gw::GatewayConfiguration) -> Result<()> {
+    helpers::write_atomic(path, &pl.encode_to_vec()).context("Write configuration state file")
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn test_save_load() {
+        let path = env::temp_dir().join(format!("config_state_test_{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+
+        assert!(load(path).unwrap().is_none());
+
+        let pl = gw::GatewayConfiguration {
+            version: "1.2.3".into(),
+            channels: vec![gw::ChannelConfiguration {
+                frequency: 868100000,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        save(path, &pl).unwrap();
+        assert_eq!(Some(pl), load(path).unwrap());
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -1,3 +1,5 @@
+use crate::api;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Item exceeds duty-cycle")]
@@ -9,9 +11,118 @@ pub enum Error {
     #[error("No band for freq: {0}, tx_power_eirp: {1}")]
     BandNotFound(u32, i8),
 
+    #[error("TX power exceeds max EIRP, freq: {0}, tx_power_eirp: {1}, tx_power_max_eirp: {2}")]
+    TxPowerExceeded(u32, i8, i8),
+
     #[error("Timeout")]
     Timeout,
 
     #[error(transparent)]
     Anyhow(#[from] anyhow::Error),
 }
+
+/// Error loading the configuration files.
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigFileError {
+    #[error("Read config file error, file: {0}, error: {1}")]
+    Read(String, std::io::Error),
+
+    #[error("Environment variable error, file: {file}, line: {line}, error: {message}")]
+    EnvVar {
+        file: String,
+        line: usize,
+        message: String,
+    },
+
+    #[error("Parse config file error, {}error: {message}", location(.file, .line))]
+    Parse {
+        file: Option<String>,
+        line: Option<usize>,
+        message: String,
+    },
+
+    #[error("Invalid gateway_id: {0}")]
+    InvalidGatewayId(String),
+
+    #[error("Invalid log_level: {0}")]
+    InvalidLogLevel(String),
+
+    #[error("Set channel plan error: {0}")]
+    ChannelPlan(String),
+
+    #[error("Load model definition error: {0}")]
+    Model(String),
+
+    #[error("Invalid JIT queue configuration: {0}")]
+    JitQueue(String),
+}
+
+impl ConfigFileError {
+    /// Returns the process exit code for the error, following sysexits.h.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            // EX_NOINPUT
+            ConfigFileError::Read(_, _) => 66,
+            // EX_CONFIG
+            _ => 78,
+        }
+    }
+}
+
+fn location(file: &Option<String>, line: &Option<usize>) -> String {
+    match (file, line) {
+        (Some(file), Some(line)) => format!("file: {}, line: {}, ", file, line),
+        (Some(file), None) => format!("file: {}, ", file),
+        _ => "".into(),
+    }
+}
+
+/// Reason why a gateway configuration was rejected.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConfigurationError {
+    #[error("Too many channels, max: {0}")]
+    TooManyChannels(usize),
+
+    #[error("Too many multi-SF channels, max: {0}")]
+    TooManyMultiSfChannels(usize),
+
+    #[error("Channel does not fit within the radio bandwidth, freq: {0}")]
+    ChannelOutsideRadioBandwidth(u32),
+
+    #[error("Invalid channel, freq: {0}, error: {1}")]
+    InvalidChannel(u32, String),
+
+    #[error("Invalid stats interval")]
+    InvalidStatsInterval,
+
+    #[error("Start concentrator error: {0}")]
+    Concentrator(String),
+}
+
+impl ConfigurationError {
+    pub fn code(&self) -> api::ConfigurationErrorCode {
+        match self {
+            ConfigurationError::TooManyChannels(_) => api::ConfigurationErrorCode::TooManyChannels,
+            ConfigurationError::TooManyMultiSfChannels(_) => {
+                api::ConfigurationErrorCode::TooManyMultiSfChannels
+            }
+            ConfigurationError::ChannelOutsideRadioBandwidth(_) => {
+                api::ConfigurationErrorCode::ChannelOutsideRadioBandwidth
+            }
+            ConfigurationError::InvalidChannel(_, _) => api::ConfigurationErrorCode::InvalidChannel,
+            ConfigurationError::InvalidStatsInterval => {
+                api::ConfigurationErrorCode::InvalidStatsInterval
+            }
+            ConfigurationError::Concentrator(_) => api::ConfigurationErrorCode::Concentrator,
+        }
+    }
+
+    /// Returns the frequency of the channel which caused the error, if any.
+    pub fn frequency(&self) -> u32 {
+        match self {
+            ConfigurationError::ChannelOutsideRadioBandwidth(v)
+            | ConfigurationError::InvalidChannel(v, _) => *v,
+            _ => 0,
+        }
+    }
+}
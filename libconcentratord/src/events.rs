@@ -4,12 +4,45 @@ use anyhow::Result;
 use chirpstack_api::{gw, prost::Message};
 use log::info;
 
+use super::api;
 use super::socket::ZMQ_CONTEXT;
 
-static ZMQ_PUB: LazyLock<Mutex<Option<zmq::Socket>>> = LazyLock::new(|| Mutex::new(None));
+static ZMQ_PUB: LazyLock<Mutex<Option<Publisher>>> = LazyLock::new(|| Mutex::new(None));
 
-pub fn bind_socket(bind: &str) -> Result<()> {
-    info!("Creating socket for publishing events, bind: {}", bind);
+struct Publisher {
+    sock: zmq::Socket,
+    topics: bool,
+}
+
+impl Publisher {
+    /// Publish the event. When topics are enabled, the event is prefixed by a topic frame such
+    /// that subscribers can filter events using the ZMQ subscription filter.
+    fn send(&self, topic: &str, event: Vec<u8>) -> Result<()> {
+        if self.topics {
+            self.sock
+                .send_multipart([topic.as_bytes().to_vec(), event], 0)?;
+        } else {
+            self.sock.send(event, 0)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Bind the event socket.
+///
+/// When topics is set to true, events are published as multipart messages, where the first
+/// frame contains the topic:
+///
+/// * `up/<modulation>/<frequency>`: uplink (e.g. `up/lora/868100000`)
+/// * `stats`: gateway stats
+/// * `ack`: downlink tx result
+/// * `spectral_scan`: spectral scan results
+pub fn bind_socket(bind: &str, topics: bool) -> Result<()> {
+    info!(
+        "Creating socket for publishing events, bind: {}, topics: {}",
+        bind, topics
+    );
 
     let zmq_ctx = ZMQ_CONTEXT.lock().unwrap();
     let mut zmq_pub = ZMQ_PUB.lock().unwrap();
@@ -17,7 +50,7 @@ pub fn bind_socket(bind: &str) -> Result<()> {
     let sock = zmq_ctx.socket(zmq::PUB)?;
     sock.bind(bind)?;
 
-    *zmq_pub = Some(sock);
+    *zmq_pub = Some(Publisher { sock, topics });
 
     Ok(())
 }
@@ -26,11 +59,12 @@ pub fn send_uplink(pl: chirpstack_api::gw::UplinkFrame) -> Result<()> {
     let pub_guard = ZMQ_PUB.lock().unwrap();
     let publisher = pub_guard.as_ref().unwrap();
 
+    let topic = uplink_topic(&pl);
     let event = gw::Event {
         event: Some(gw::event::Event::UplinkFrame(pl)),
     };
 
-    publisher.send(event.encode_to_vec(), 0).unwrap();
+    publisher.send(&topic, event.encode_to_vec()).unwrap();
 
     Ok(())
 }
@@ -51,7 +85,86 @@ pub fn send_stats(stats: chirpstack_api::gw::GatewayStats) -> Result<()> {
         event: Some(gw::event::Event::GatewayStats(stats)),
     };
 
-    publisher.send(event.encode_to_vec(), 0).unwrap();
+    publisher.send("stats", event.encode_to_vec()).unwrap();
+
+    Ok(())
+}
+
+pub fn send_tx_result(pl: api::DownlinkTxResult) -> Result<()> {
+    let pub_guard = ZMQ_PUB.lock().unwrap();
+    let publisher = pub_guard.as_ref().unwrap();
+
+    info!(
+        "Publishing downlink tx result event, downlink_id: {}, result: {}",
+        pl.downlink_id,
+        pl.result().as_str_name()
+    );
+
+    let event = api::Event {
+        event: Some(api::event::Event::DownlinkTxResult(pl)),
+    };
+
+    publisher.send("ack", event.encode_to_vec()).unwrap();
 
     Ok(())
 }
+
+pub fn send_spectral_scan(pl: api::SpectralScan) -> Result<()> {
+    let pub_guard = ZMQ_PUB.lock().unwrap();
+    let publisher = pub_guard.as_ref().unwrap();
+
+    info!(
+        "Publishing spectral scan event, results: {}",
+        pl.results.len()
+    );
+
+    let event = api::Event {
+        event: Some(api::event::Event::SpectralScan(pl)),
+    };
+
+    publisher
+        .send("spectral_scan", event.encode_to_vec())
+        .unwrap();
+
+    Ok(())
+}
+
+fn uplink_topic(pl: &gw::UplinkFrame) -> String {
+    let tx_info = pl.tx_info.as_ref();
+    let modulation = match tx_info
+        .and_then(|v| v.modulation.as_ref())
+        .and_then(|v| v.parameters.as_ref())
+    {
+        Some(gw::modulation::Parameters::Lora(_)) => "lora",
+        Some(gw::modulation::Parameters::Fsk(_)) => "fsk",
+        Some(gw::modulation::Parameters::LrFhss(_)) => "lr_fhss",
+        None => "unknown",
+    };
+    let frequency = tx_info.map(|v| v.frequency).unwrap_or_default();
+
+    format!("up/{}/{}", modulation, frequency)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_uplink_topic() {
+        let pl = gw::UplinkFrame {
+            tx_info: Some(gw::UplinkTxInfo {
+                frequency: 868100000,
+                modulation: Some(gw::Modulation {
+                    parameters: Some(gw::modulation::Parameters::Lora(
+                        gw::LoraModulationInfo::default(),
+                    )),
+                }),
+            }),
+            ..Default::default()
+        };
+        assert_eq!("up/lora/868100000", uplink_topic(&pl));
+
+        let pl = gw::UplinkFrame::default();
+        assert_eq!("up/unknown/0", uplink_topic(&pl));
+    }
+}
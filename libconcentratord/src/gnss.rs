@@ -64,7 +64,7 @@ pub enum Device {
     Gpsd(String),
 }
 
-#[derive(Default, Clone, PartialEq, Debug)]
+#[derive(Default, Clone, PartialEq, Debug, Deserialize)]
 pub enum Family {
     #[default]
     Ublox,
@@ -1,5 +1,9 @@
+use std::fs;
+use std::path::Path;
 use std::time::Duration;
 
+use anyhow::{Context, Result};
+
 pub trait ToConcentratorCount {
     fn to_concentrator_count(self) -> u32;
 }
@@ -9,3 +13,18 @@ impl ToConcentratorCount for Duration {
         (self.as_micros() % (1 << 32)) as u32
     }
 }
+
+/// Write the given bytes to the given path. To avoid a corrupted file, this first writes to a
+/// temporary file which is then renamed. Missing parent directories are created.
+pub fn write_atomic(path: &str, b: &[u8]) -> Result<()> {
+    let tmp_path = format!("{}.tmp", path);
+
+    if let Some(dir) = Path::new(path).parent() {
+        fs::create_dir_all(dir).context("Create directory")?;
+    }
+
+    fs::write(&tmp_path, b).context("Write temporary file")?;
+    fs::rename(&tmp_path, path).context("Rename temporary file")?;
+
+    Ok(())
+}
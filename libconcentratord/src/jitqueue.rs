@@ -1,12 +1,15 @@
-use std::time::Duration;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
 
 use anyhow::Result;
 use chirpstack_api::gw;
 use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
 
+use crate::api;
 use crate::error::Error;
 use crate::helpers::ToConcentratorCount;
-use crate::regulation::{dutycycle, tracker};
+use crate::regulation::{dutycycle, dwelltime, tracker};
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
 pub enum TxMode {
@@ -15,15 +18,134 @@ pub enum TxMode {
     OnGPS,
 }
 
+// The interval at which the daemons pop packets from the queue.
+const POP_INTERVAL: Duration = Duration::from_millis(10);
+
+// The resolution of the search for the first slot of an immediate packet which is within the
+// duty-cycle.
+const IMMEDIATE_SLOT_RESOLUTION: Duration = Duration::from_millis(1);
+
+/// Timing parameters and capacity of the queue.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct Configuration {
+    /// Max. number of packets in the queue.
+    pub capacity: usize,
+    /// Time needed by the concentrator to start the transmission.
+    #[serde(with = "humantime_serde")]
+    pub tx_start_delay: Duration,
+    /// Margin between two transmissions.
+    #[serde(with = "humantime_serde")]
+    pub tx_margin_delay: Duration,
+    /// Time before the transmission (excluding tx_start_delay) at which the packet is handed
+    /// to the concentrator.
+    #[serde(with = "humantime_serde")]
+    pub tx_jit_delay: Duration,
+    /// Max. time in advance that a packet can be enqueued.
+    #[serde(with = "humantime_serde")]
+    pub tx_max_advance_delay: Duration,
+    /// Max. time in advance that an immediate packet is scheduled, when searching for a slot
+    /// which does not collide with the queued packets and which is within the duty-cycle.
+    #[serde(with = "humantime_serde")]
+    pub tx_immediate_max_delay: Duration,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Configuration {
+            capacity: 32,
+            tx_start_delay: Duration::from_micros(1500),
+            tx_margin_delay: Duration::from_micros(1000),
+            tx_jit_delay: Duration::from_micros(40000),
+            tx_max_advance_delay: Duration::from_secs((3 + 1) * 128),
+            tx_immediate_max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl Configuration {
+    pub fn validate(&self) -> Result<()> {
+        if self.capacity == 0 {
+            return Err(anyhow!("capacity must be greater than 0"));
+        }
+
+        // The packet must be handed to the concentrator before the tx_jit_delay has passed,
+        // the queue is only polled every POP_INTERVAL.
+        if self.tx_jit_delay <= POP_INTERVAL {
+            return Err(anyhow!(
+                "tx_jit_delay must be greater than {:?}",
+                POP_INTERVAL
+            ));
+        }
+
+        let min_delay = self.tx_start_delay + self.tx_margin_delay + self.tx_jit_delay;
+        if self.tx_max_advance_delay <= min_delay {
+            return Err(anyhow!(
+                "tx_max_advance_delay must be greater than tx_start_delay + tx_margin_delay + tx_jit_delay ({:?})",
+                min_delay
+            ));
+        }
+
+        // An immediate packet is scheduled at the earliest 2 x tx_jit_delay in advance.
+        if self.tx_immediate_max_delay < 2 * self.tx_jit_delay
+            || self.tx_immediate_max_delay > self.tx_max_advance_delay
+        {
+            return Err(anyhow!(
+                "tx_immediate_max_delay must be between 2 x tx_jit_delay ({:?}) and tx_max_advance_delay",
+                2 * self.tx_jit_delay
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Priority of a packet. A packet can preempt the queued packets with a lower priority which
+/// collide with it.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
+pub enum Priority {
+    ClassC,
+    ClassB,
+    ClassA,
+    Beacon,
+}
+
+/// TX capable RF chain of a concentrator which can transmit on multiple RF chains at the same
+/// time (see Queue::with_rf_chains).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RfChain {
+    pub rf_chain: u8,
+    pub tx_min_max_freqs: Vec<(u32, u32)>,
+}
+
+impl RfChain {
+    fn supports_frequency(&self, frequency: u32) -> bool {
+        self.tx_min_max_freqs
+            .iter()
+            .any(|(min, max)| frequency >= *min && frequency <= *max)
+    }
+}
+
 pub trait TxPacket {
     fn get_time_on_air(&self) -> Result<Duration>;
     fn get_tx_mode(&self) -> TxMode;
     fn get_id(&self) -> u32;
+    fn get_priority(&self) -> Priority;
     fn set_tx_mode(&mut self, tx_mode: TxMode);
     fn get_count_us(&self) -> u32;
     fn set_count_us(&mut self, count_us: u32);
     fn get_frequency(&self) -> u32;
+    fn get_bandwidth(&self) -> u32;
     fn get_tx_power(&self) -> i8;
+    fn set_tx_power(&mut self, tx_power: i8);
+
+    /// Returns the RF chain used for the transmission. Concentrators with a single RF chain
+    /// can use the default implementation.
+    fn get_rf_chain(&self) -> u8 {
+        0
+    }
+
+    fn set_rf_chain(&mut self, _rf_chain: u8) {}
 }
 
 pub struct Item<T> {
@@ -32,17 +154,24 @@ pub struct Item<T> {
     linear_count: Duration,
     pre_delay: Duration,
     post_delay: Duration,
+    priority: Priority,
     packet: T,
 }
 
 pub struct Queue<T> {
     items: Vec<Item<T>>,
     dc_tracker: Option<tracker::Tracker>,
+    dwell_time: Option<dwelltime::Configuration>,
+
+    // TX capable RF chains which can transmit at the same time. When empty, all RF chains
+    // share a single transmitter.
+    rf_chains: Vec<RfChain>,
 
     tx_start_delay: Duration,
     tx_margin_delay: Duration,
     tx_jit_delay: Duration,
     tx_max_advance_delay: Duration,
+    tx_immediate_max_delay: Duration,
 
     // The queue instance keeps track of the last concentrator_count and linear_count values in
     // order to convert a new concentrator_count value into a linear_count. Note that the
@@ -51,29 +180,73 @@ pub struct Queue<T> {
     concentrator_count_last: u32,
     linear_count_last: Duration,
 
-    // This value holds the linear counter value after finishing the last downlink transmission. We
-    // need to store this as once the downlink is scheduled, it is popped from the queue and we no
-    // longer know until when the concentrator is busy transmitting.
-    tx_linear_count_finished: Duration,
+    // This value holds the linear counter value after finishing the last downlink transmission, by
+    // rf_chain. We need to store this as once the downlink is scheduled, it is popped from the
+    // queue and we no longer know until when the concentrator is busy transmitting.
+    tx_linear_count_finished: HashMap<u8, Duration>,
+
+    // Items which were removed from the queue without being handed to the concentrator, together
+    // with the reason. These are kept until they have been retrieved by take_dropped.
+    dropped: Vec<(T, api::TxResult)>,
+
+    // The max. latency between popping a packet and handing it to the concentrator, since the
+    // last call of take_send_latency_max.
+    send_latency_max: Option<Duration>,
 }
 
 impl<T: TxPacket + Copy> Queue<T> {
-    pub fn new(capacity: usize, dc_tracker: Option<tracker::Tracker>) -> Queue<T> {
-        info!("Initializing JIT queue, capacity: {}", capacity);
+    pub fn new(
+        config: &Configuration,
+        dc_tracker: Option<tracker::Tracker>,
+        dwell_time: Option<dwelltime::Configuration>,
+    ) -> Queue<T> {
+        info!(
+            "Initializing JIT queue, capacity: {}, tx_start_delay: {:?}, tx_margin_delay: {:?}, tx_jit_delay: {:?}, tx_max_advance_delay: {:?}, tx_immediate_max_delay: {:?}",
+            config.capacity,
+            config.tx_start_delay,
+            config.tx_margin_delay,
+            config.tx_jit_delay,
+            config.tx_max_advance_delay,
+            config.tx_immediate_max_delay
+        );
 
         Queue {
             dc_tracker,
-            items: Vec::with_capacity(capacity),
+            dwell_time,
+            items: Vec::with_capacity(config.capacity),
+            rf_chains: Vec::new(),
+
+            tx_start_delay: config.tx_start_delay,
+            tx_margin_delay: config.tx_margin_delay,
+            tx_jit_delay: config.tx_jit_delay,
+            tx_max_advance_delay: config.tx_max_advance_delay,
+            tx_immediate_max_delay: config.tx_immediate_max_delay,
+
+            // The linear_count starts at one concentrator_count rollover, such that duty-cycle
+            // items from before the queue was created can be restored. As this is a multiple of
+            // 2^32, it does not affect the conversion to the concentrator_count.
+            concentrator_count_last: 0,
+            linear_count_last: Duration::from_micros(1 << 32),
+            tx_linear_count_finished: HashMap::new(),
 
-            tx_start_delay: Duration::from_micros(1500),
-            tx_margin_delay: Duration::from_micros(1000),
-            tx_jit_delay: Duration::from_micros(40000),
-            tx_max_advance_delay: Duration::from_secs((3 + 1) * 128),
+            dropped: Vec::new(),
+            send_latency_max: None,
+        }
+    }
 
-            concentrator_count_last: 0,
-            linear_count_last: Duration::from_secs(0),
-            tx_linear_count_finished: Duration::from_secs(0),
+    /// Allow transmissions at the same time on the given TX capable RF chains, e.g. for a
+    /// full-duplex concentrator. When the RF chain of a packet is busy, the packet is moved to
+    /// one of the other RF chains supporting the frequency of the packet.
+    pub fn with_rf_chains(mut self, rf_chains: Vec<RfChain>) -> Self {
+        if !rf_chains.is_empty() {
+            info!(
+                "JIT queue allows concurrent transmissions, rf_chains: {:?}",
+                rf_chains.iter().map(|v| v.rf_chain).collect::<Vec<u8>>()
+            );
         }
+
+        self.rf_chains = rf_chains;
+        self
     }
 
     pub fn size(&self) -> usize {
@@ -105,7 +278,8 @@ impl<T: TxPacket + Copy> Queue<T> {
                         v.packet.get_count_us(),
                         concentrator_count
                     );
-                    self.items.remove(0);
+                    let item = self.items.remove(0);
+                    self.dropped.push((item.packet, api::TxResult::TooLate));
                     return None;
                 }
 
@@ -120,11 +294,147 @@ impl<T: TxPacket + Copy> Queue<T> {
 
         // This value holds the counter when the concentrator is done transmitting the packet. This
         // is needed to detect possible collisions if enqueueing new packets.
-        self.tx_linear_count_finished = item.linear_count + item.post_delay;
+        self.tx_linear_count_finished.insert(
+            item.packet.get_rf_chain(),
+            item.linear_count + item.post_delay,
+        );
 
         Some(item.packet)
     }
 
+    pub fn take_dropped(&mut self) -> Vec<(T, api::TxResult)> {
+        std::mem::take(&mut self.dropped)
+    }
+
+    /// Track the latency between popping a packet and handing it to the concentrator. When
+    /// this exceeds the tx_jit_delay, the packet was probably not sent in time.
+    pub fn track_send_latency(&mut self, downlink_id: u32, latency: Duration) {
+        if latency > self.tx_jit_delay {
+            warn!(
+                "Send latency exceeds tx_jit_delay, downlink_id: {}, latency: {:?}, tx_jit_delay: {:?}",
+                downlink_id, latency, self.tx_jit_delay
+            );
+        }
+
+        self.send_latency_max = Some(self.send_latency_max.unwrap_or_default().max(latency));
+    }
+
+    /// Returns the max. send latency since the previous call (see track_send_latency).
+    pub fn take_send_latency_max(&mut self) -> Option<Duration> {
+        self.send_latency_max.take()
+    }
+
+    /// Returns the number of items in the queue.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the queued packets, ordered by their transmission time.
+    pub fn get_items(&mut self, concentrator_count: u32) -> Vec<api::QueueItem> {
+        let linear_count = self.get_linear_count(concentrator_count);
+
+        self.items
+            .iter()
+            .map(|item| api::QueueItem {
+                downlink_id: item.packet.get_id(),
+                count_us: item.packet.get_count_us(),
+                frequency: item.packet.get_frequency(),
+                time_on_air: Some(item.post_delay.try_into().unwrap_or_default()),
+                time_until_tx: Some(
+                    item.linear_count
+                        .saturating_sub(linear_count)
+                        .try_into()
+                        .unwrap_or_default(),
+                ),
+                rf_chain: item.packet.get_rf_chain().into(),
+            })
+            .collect()
+    }
+
+    /// Remove the packets with the given downlink_id from the queue (see take_dropped). This
+    /// returns false when no packet was found.
+    pub fn remove(&mut self, downlink_id: u32) -> bool {
+        let len = self.items.len();
+
+        self.items.retain(|item| {
+            if item.packet.get_id() != downlink_id {
+                return true;
+            }
+
+            info!(
+                "Packet removed from queue, downlink_id: {}, count_us: {}",
+                downlink_id,
+                item.packet.get_count_us()
+            );
+
+            if let Some(dc_tracker) = &mut self.dc_tracker {
+                dc_tracker.remove(
+                    item.packet.get_frequency(),
+                    item.packet.get_tx_power(),
+                    &dutycycle::Item {
+                        start_time: item.linear_count,
+                        end_time: item.linear_count + item.post_delay,
+                    },
+                );
+            }
+
+            self.dropped.push((item.packet, api::TxResult::Cancelled));
+            false
+        });
+
+        self.items.len() != len
+    }
+
+    /// Migrate the queue to a new concentrator counter, e.g. after the concentrator has been
+    /// restarted. The old_count and new_count are the counter values read before stopping and
+    /// after starting the concentrator, elapsed is the time between both readings.
+    ///
+    /// The count_us of the queued packets is updated to the new counter. Packets which can no
+    /// longer be sent in time are removed from the queue (see take_dropped).
+    pub fn migrate(&mut self, old_count: u32, new_count: u32, elapsed: Duration) {
+        let linear_count = self.get_linear_count(old_count) + elapsed;
+        self.concentrator_count_last = new_count;
+        self.linear_count_last = linear_count;
+
+        // The concentrator was not transmitting during the restart.
+        for v in self.tx_linear_count_finished.values_mut() {
+            *v = (*v).min(linear_count);
+        }
+
+        let tx_jit_delay = self.tx_jit_delay;
+        let mut dropped = Vec::new();
+
+        self.items.retain_mut(|item| {
+            if item.linear_count < linear_count + tx_jit_delay {
+                warn!(
+                    "Queued packet can not be sent after concentrator restart, dropped: downlink_id: {}",
+                    item.packet.get_id()
+                );
+                dropped.push((item.packet, api::TxResult::TooLate));
+                return false;
+            }
+
+            let diff_us = (item.linear_count - linear_count).as_micros() as u32;
+            item.packet.set_count_us(new_count.wrapping_add(diff_us));
+            true
+        });
+
+        info!(
+            "JIT queue migrated, old_count: {}, new_count: {}, elapsed: {:?}, items: {}, dropped: {}",
+            old_count,
+            new_count,
+            elapsed,
+            self.items.len(),
+            dropped.len()
+        );
+
+        self.dropped.extend(dropped);
+    }
+
     pub fn get_duty_cycle_stats(&mut self, concentrator_count: u32) -> Option<gw::DutyCycleStats> {
         let linear_count = self.get_linear_count(concentrator_count);
 
@@ -162,7 +472,35 @@ impl<T: TxPacket + Copy> Queue<T> {
         None
     }
 
-    pub fn enqueue(&mut self, concentrator_count: u32, packet: T) -> Result<(), gw::TxAckStatus> {
+    /// Save the duty-cycle tracker state to the given file.
+    pub fn save_duty_cycle_state(&mut self, concentrator_count: u32, path: &str) -> Result<()> {
+        let linear_count = self.get_linear_count(concentrator_count);
+
+        if let Some(dc_tracker) = &self.dc_tracker {
+            dc_tracker
+                .get_state(linear_count, SystemTime::now())
+                .save(path)?;
+            debug!("Duty-cycle state saved, path: {}", path);
+        }
+
+        Ok(())
+    }
+
+    /// Restore the duty-cycle tracker state from the given file.
+    pub fn load_duty_cycle_state(&mut self, concentrator_count: u32, path: &str) -> Result<()> {
+        let linear_count = self.get_linear_count(concentrator_count);
+
+        if let Some(dc_tracker) = &mut self.dc_tracker {
+            let state = tracker::State::load(path)?;
+            dc_tracker.restore_state(state, linear_count, SystemTime::now());
+        }
+
+        Ok(())
+    }
+
+    /// Enqueue the given packet. On success, this returns the packet as it was enqueued, e.g.
+    /// with the updated count_us and tx_power.
+    pub fn enqueue(&mut self, concentrator_count: u32, packet: T) -> Result<T, api::TxAckStatus> {
         let linear_count = self.get_linear_count(concentrator_count);
 
         match packet.get_tx_mode() {
@@ -192,62 +530,160 @@ impl<T: TxPacket + Copy> Queue<T> {
         }
 
         if self.full() {
-            return Err(gw::TxAckStatus::QueueFull);
+            return Err(api::TxAckStatus::QueueFull);
         }
 
         let time_on_air = match packet.get_time_on_air() {
             Ok(v) => v,
             Err(err) => {
                 error!("Get time on air for tx packet error, error: {}", err);
-                return Err(gw::TxAckStatus::InternalError);
+                return Err(api::TxAckStatus::InternalError);
             }
         };
 
+        let dwell_time_max = self
+            .dwell_time
+            .as_ref()
+            .and_then(|v| v.get_dwell_time_max(packet.get_frequency(), packet.get_bandwidth()));
+        if let Some(dwell_time_max) = dwell_time_max.filter(|v| time_on_air > *v) {
+            warn!(
+                "Packet rejected because of dwell-time, downlink_id: {}, time_on_air: {:?}, dwell_time_max: {:?}",
+                packet.get_id(),
+                time_on_air,
+                dwell_time_max
+            );
+            return Err(api::TxAckStatus::DwellTime);
+        }
+
         let mut item = Item {
             // linear_count depends on packet count_us, will be set later
             linear_count: Duration::from_micros(0),
             pre_delay: self.tx_start_delay + self.tx_jit_delay,
             post_delay: time_on_air,
+            priority: packet.get_priority(),
             packet,
         };
 
+        if let Some(dc_tracker) = &mut self.dc_tracker {
+            dc_tracker.cleanup(linear_count);
+
+            // In case no band is found, this is handled by try_insert.
+            match dc_tracker.get_tx_power(item.packet.get_frequency(), item.packet.get_tx_power()) {
+                Ok(tx_power) => {
+                    if tx_power != item.packet.get_tx_power() {
+                        info!(
+                            "TX power clamped to max EIRP, downlink_id: {}, tx_power: {}, tx_power_max_eirp: {}",
+                            item.packet.get_id(),
+                            item.packet.get_tx_power(),
+                            tx_power
+                        );
+                        item.packet.set_tx_power(tx_power);
+                    }
+                }
+                Err(Error::TxPowerExceeded(f, t, max)) => {
+                    warn!(
+                        "Packet rejected because TX power exceeds max EIRP, downlink_id: {}, freq: {}, tx_power: {}, tx_power_max_eirp: {}",
+                        item.packet.get_id(),
+                        f,
+                        t,
+                        max
+                    );
+                    return Err(api::TxAckStatus::TxPower);
+                }
+                Err(_) => {}
+            }
+        }
+
+        // Queued packets which will be removed in favor of this packet.
+        let mut preempted: Vec<usize> = Vec::new();
+
         // An immediate downlink becomes a timestamped downlink "ASAP".
         // Set the packet count_us to the first available slot.
         if item.packet.get_tx_mode() == TxMode::Immediate {
             item.packet.set_tx_mode(TxMode::Timestamped);
 
-            // use now + 1 sec
-            let mut asap_count = linear_count + (2 * self.tx_jit_delay);
-
-            // eventual collision with currently running packet
-            // not anymore in queue but still there
-            let not_before_count =
-                self.tx_linear_count_finished + self.tx_margin_delay + item.pre_delay;
-            if asap_count < not_before_count {
-                asap_count = not_before_count;
-            }
-
-            // check if there is a collision
-            if self.collision_test(asap_count, item.pre_delay, item.post_delay) {
-                for p in self.items.iter() {
-                    asap_count =
-                        p.linear_count + p.post_delay + item.pre_delay + self.tx_margin_delay;
-
-                    if !self.collision_test(asap_count, item.pre_delay, item.post_delay) {
-                        break;
+            // Use the RF chain with the earliest slot, preferring the requested RF chain.
+            let mut slot: Option<(u8, Duration)> = None;
+            let mut slot_err = None;
+            for rf_chain in self.get_rf_chains(&item.packet) {
+                item.packet.set_rf_chain(rf_chain);
+                match self.get_immediate_slot(linear_count, &item) {
+                    Ok(count) => {
+                        if slot.is_none_or(|(_, c)| count < c) {
+                            slot = Some((rf_chain, count));
+                        }
+                    }
+                    Err(e) => {
+                        slot_err.get_or_insert(e);
                     }
                 }
             }
 
+            let (rf_chain, asap_count) = match slot {
+                Some(v) => v,
+                None => return Err(slot_err.unwrap_or(api::TxAckStatus::InternalError)),
+            };
+            item.packet.set_rf_chain(rf_chain);
             item.linear_count = asap_count;
             item.packet.set_count_us(asap_count.to_concentrator_count());
         } else {
             item.linear_count = self.concentrator_count_to_linear_count(item.packet.get_count_us());
             if (item.packet.get_tx_mode() == TxMode::Timestamped
                 || item.packet.get_tx_mode() == TxMode::OnGPS)
-                && self.collision_test(item.linear_count, item.pre_delay, item.post_delay)
+                && self.collision_test(
+                    item.linear_count,
+                    item.pre_delay,
+                    item.post_delay,
+                    item.packet.get_rf_chain(),
+                )
             {
-                return Err(gw::TxAckStatus::CollisionPacket);
+                // Use one of the other RF chains, when it is free.
+                let rf_chains = self.get_rf_chains(&item.packet);
+                let rf_chain = rf_chains[1..].iter().copied().find(|rf_chain| {
+                    !self.collision_test(
+                        item.linear_count,
+                        item.pre_delay,
+                        item.post_delay,
+                        *rf_chain,
+                    )
+                });
+
+                if let Some(rf_chain) = rf_chain {
+                    info!(
+                        "RF chain is busy, using other RF chain, downlink_id: {}, rf_chain: {}, other_rf_chain: {}",
+                        item.packet.get_id(),
+                        item.packet.get_rf_chain(),
+                        rf_chain
+                    );
+                    item.packet.set_rf_chain(rf_chain);
+                } else {
+                    let rf_chain = item.packet.get_rf_chain();
+
+                    // The packet can only preempt the colliding packets when all of these have
+                    // a lower priority. The packet currently being transmitted can not be
+                    // preempted.
+                    if self.running_collision_test(item.linear_count, item.pre_delay, rf_chain) {
+                        return Err(api::TxAckStatus::CollisionPacket);
+                    }
+
+                    preempted = (0..self.items.len())
+                        .filter(|i| {
+                            self.item_collision_test(
+                                &self.items[*i],
+                                item.linear_count,
+                                item.pre_delay,
+                                item.post_delay,
+                                rf_chain,
+                            )
+                        })
+                        .collect();
+                    if preempted
+                        .iter()
+                        .any(|i| self.items[*i].priority >= item.priority)
+                    {
+                        return Err(api::TxAckStatus::CollisionPacket);
+                    }
+                }
             }
         }
 
@@ -262,7 +698,7 @@ impl<T: TxPacket + Copy> Queue<T> {
                 item.packet.get_count_us(),
                 concentrator_count
             );
-            return Err(gw::TxAckStatus::TooLate);
+            return Err(api::TxAckStatus::TooLate);
         }
 
         // Is it too early to send this packet?
@@ -273,11 +709,29 @@ impl<T: TxPacket + Copy> Queue<T> {
                 item.packet.get_count_us(),
                 concentrator_count
             );
-            return Err(gw::TxAckStatus::TooEarly);
+            return Err(api::TxAckStatus::TooEarly);
         }
 
         if let Some(dc_tracker) = &mut self.dc_tracker {
-            dc_tracker.cleanup(linear_count);
+            // The preempted packets release their duty-cycle, such that it can be used by this
+            // packet. These are tracked again when this packet can not be tracked.
+            let preempted_dc: Vec<(u32, i8, dutycycle::Item)> = preempted
+                .iter()
+                .map(|i| {
+                    let p = &self.items[*i];
+                    (
+                        p.packet.get_frequency(),
+                        p.packet.get_tx_power(),
+                        dutycycle::Item {
+                            start_time: p.linear_count,
+                            end_time: p.linear_count + p.post_delay,
+                        },
+                    )
+                })
+                .collect();
+            for (tx_freq, tx_power, dc_item) in &preempted_dc {
+                dc_tracker.remove(*tx_freq, *tx_power, dc_item);
+            }
 
             let res = dc_tracker.try_insert(
                 item.packet.get_frequency(),
@@ -289,41 +743,134 @@ impl<T: TxPacket + Copy> Queue<T> {
             );
 
             if let Err(e) = res {
-                match e.downcast_ref::<Error>() {
-                    Some(Error::DutyCycle) | Some(Error::DutyCycleFutureItems) => {
-                        warn!(
-                            "Packet rejected because of duty-cycle, downlink_id: {}",
-                            item.packet.get_id()
-                        );
-                        return Err(gw::TxAckStatus::DutyCycleOverflow);
-                    }
-                    Some(Error::BandNotFound(f, t)) => {
-                        warn!(
-                            "No duty-cycle band found for packet, downlink_id: {}, freq: {}, tx_power: {}",
-                            item.packet.get_id(),
-                            f,
-                            t
-                        );
-                        return Err(gw::TxAckStatus::DutyCycleOverflow);
-                    }
-                    _ => {
-                        warn!("Duty-cycle tracker error, error: {}", e);
-                        return Err(gw::TxAckStatus::InternalError);
-                    }
+                for (tx_freq, tx_power, dc_item) in preempted_dc {
+                    dc_tracker.insert(tx_freq, tx_power, dc_item);
                 }
+                return Err(duty_cycle_error_status(item.packet.get_id(), e));
             }
         }
 
+        // Remove in reverse order, such that the remaining indices are still valid.
+        for i in preempted.into_iter().rev() {
+            let preempted = self.items.remove(i);
+
+            warn!(
+                "Queued packet preempted, downlink_id: {}, priority: {:?}, preempted_by: {}, priority: {:?}",
+                preempted.packet.get_id(),
+                preempted.priority,
+                item.packet.get_id(),
+                item.priority
+            );
+
+            self.dropped
+                .push((preempted.packet, api::TxResult::Preempted));
+        }
+
         debug!(
             "Packet enqueued, downlink_id: {}, count_us: {}",
             item.packet.get_id(),
             item.packet.get_count_us()
         );
 
+        let packet = item.packet;
         self.items.push(item);
         self.sort();
 
-        Ok(())
+        Ok(packet)
+    }
+
+    // Returns the earliest slot for the given immediate packet, which does not collide with
+    // the running and queued packets and which is within the duty-cycle. The slot is searched
+    // up to tx_immediate_max_delay in advance.
+    fn get_immediate_slot(
+        &self,
+        linear_count: Duration,
+        item: &Item<T>,
+    ) -> Result<Duration, api::TxAckStatus> {
+        let max_count = linear_count + self.tx_immediate_max_delay;
+
+        let rf_chain = item.packet.get_rf_chain();
+
+        // The first slot is 2 x tx_jit_delay in advance, or after the running packet(s).
+        let mut count = self
+            .tx_linear_count_finished
+            .iter()
+            .filter(|(c, _)| self.shares_transmitter(**c, rf_chain))
+            .map(|(_, finished)| *finished + self.tx_margin_delay + item.pre_delay)
+            .fold(linear_count + (2 * self.tx_jit_delay), Duration::max);
+        let mut status = api::TxAckStatus::CollisionPacket;
+
+        while count <= max_count {
+            // Move the slot after the last colliding packet. Note that item_collision_test
+            // also considers adjacent packets as colliding.
+            if let Some(p) = self.items.iter().rfind(|p| {
+                self.item_collision_test(p, count, item.pre_delay, item.post_delay, rf_chain)
+            }) {
+                count = p.linear_count
+                    + p.post_delay
+                    + item.pre_delay
+                    + self.tx_margin_delay
+                    + Duration::from_micros(1);
+                status = api::TxAckStatus::CollisionPacket;
+                continue;
+            }
+
+            let dc_tracker = match &self.dc_tracker {
+                Some(v) => v,
+                None => return Ok(count),
+            };
+
+            let dc_item = dutycycle::Item {
+                start_time: count,
+                end_time: count + item.post_delay,
+            };
+            let (freq, tx_power) = (item.packet.get_frequency(), item.packet.get_tx_power());
+
+            match dc_tracker.check(freq, tx_power, &dc_item) {
+                Ok(_) => return Ok(count),
+                Err(e) => match e.downcast_ref::<Error>() {
+                    Some(Error::DutyCycle) | Some(Error::DutyCycleFutureItems) => {
+                        status = api::TxAckStatus::DutyCycleOverflow;
+
+                        // The next time at which a tracked item has left the window.
+                        let mut next = match dc_tracker.next_release_time(
+                            freq,
+                            tx_power,
+                            count,
+                            item.post_delay,
+                        ) {
+                            Some(v) => v,
+                            None => break,
+                        };
+
+                        // As the tracked items gradually leave the window, the packet might
+                        // already be within the duty-cycle before this time.
+                        while next - count > IMMEDIATE_SLOT_RESOLUTION {
+                            let mid = count + (next - count) / 2;
+                            let dc_item = dutycycle::Item {
+                                start_time: mid,
+                                end_time: mid + item.post_delay,
+                            };
+
+                            match dc_tracker.check(freq, tx_power, &dc_item) {
+                                Ok(_) => next = mid,
+                                Err(_) => count = mid,
+                            }
+                        }
+                        count = next;
+                    }
+                    _ => return Err(duty_cycle_error_status(item.packet.get_id(), e)),
+                },
+            }
+        }
+
+        warn!(
+            "No slot found for immediate packet, downlink_id: {}, tx_immediate_max_delay: {:?}, status: {}",
+            item.packet.get_id(),
+            self.tx_immediate_max_delay,
+            status.as_str_name()
+        );
+        Err(status)
     }
 
     fn get_linear_count(&mut self, concentrator_count: u32) -> Duration {
@@ -345,37 +892,118 @@ impl<T: TxPacket + Copy> Queue<T> {
             .sort_by(|a, b| a.linear_count.cmp(&b.linear_count))
     }
 
-    fn collision_test(&self, count: Duration, pre_delay: Duration, post_delay: Duration) -> bool {
-        if count < self.tx_linear_count_finished + pre_delay + self.tx_margin_delay {
+    // Returns the RF chains which can be used for the given packet, starting with the requested
+    // RF chain.
+    fn get_rf_chains(&self, packet: &T) -> Vec<u8> {
+        let rf_chain = packet.get_rf_chain();
+        let mut out = vec![rf_chain];
+
+        out.extend(
+            self.rf_chains
+                .iter()
+                .filter(|c| c.rf_chain != rf_chain && c.supports_frequency(packet.get_frequency()))
+                .map(|c| c.rf_chain),
+        );
+
+        out
+    }
+
+    // Returns true when the transmissions on both RF chains can not overlap.
+    fn shares_transmitter(&self, rf_chain_a: u8, rf_chain_b: u8) -> bool {
+        self.rf_chains.is_empty() || rf_chain_a == rf_chain_b
+    }
+
+    fn collision_test(
+        &self,
+        count: Duration,
+        pre_delay: Duration,
+        post_delay: Duration,
+        rf_chain: u8,
+    ) -> bool {
+        if self.running_collision_test(count, pre_delay, rf_chain) {
             // a packet is currently running, then we need to take it into account
             return true;
         }
 
-        for p2 in self.items.iter() {
-            if count > p2.linear_count {
-                if count - p2.linear_count <= pre_delay + p2.post_delay + self.tx_margin_delay {
-                    return true;
-                }
-            } else if p2.linear_count - count <= p2.pre_delay + post_delay + self.tx_margin_delay {
-                return true;
-            }
+        self.items
+            .iter()
+            .any(|p2| self.item_collision_test(p2, count, pre_delay, post_delay, rf_chain))
+    }
+
+    fn running_collision_test(&self, count: Duration, pre_delay: Duration, rf_chain: u8) -> bool {
+        self.tx_linear_count_finished
+            .iter()
+            .filter(|(c, _)| self.shares_transmitter(**c, rf_chain))
+            .any(|(_, finished)| count < *finished + pre_delay + self.tx_margin_delay)
+    }
+
+    fn item_collision_test(
+        &self,
+        p2: &Item<T>,
+        count: Duration,
+        pre_delay: Duration,
+        post_delay: Duration,
+        rf_chain: u8,
+    ) -> bool {
+        if !self.shares_transmitter(p2.packet.get_rf_chain(), rf_chain) {
+            return false;
+        }
+
+        if count > p2.linear_count {
+            count - p2.linear_count <= pre_delay + p2.post_delay + self.tx_margin_delay
+        } else {
+            p2.linear_count - count <= p2.pre_delay + post_delay + self.tx_margin_delay
         }
+    }
+}
 
-        false
+// Returns the TX ack status for the given duty-cycle tracker error.
+fn duty_cycle_error_status(downlink_id: u32, e: anyhow::Error) -> api::TxAckStatus {
+    match e.downcast_ref::<Error>() {
+        Some(Error::DutyCycle) | Some(Error::DutyCycleFutureItems) => {
+            warn!(
+                "Packet rejected because of duty-cycle, downlink_id: {}",
+                downlink_id
+            );
+            api::TxAckStatus::DutyCycleOverflow
+        }
+        Some(Error::BandNotFound(f, t)) => {
+            warn!(
+                "No duty-cycle band found for packet, downlink_id: {}, freq: {}, tx_power: {}",
+                downlink_id, f, t
+            );
+            api::TxAckStatus::DutyCycleOverflow
+        }
+        _ => {
+            warn!("Duty-cycle tracker error, error: {}", e);
+            api::TxAckStatus::InternalError
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::regulation::standard;
+
+    fn config(capacity: usize) -> Configuration {
+        Configuration {
+            capacity,
+            ..Default::default()
+        }
+    }
 
     #[derive(Copy, Clone)]
     struct TxPacketMock {
+        id: u32,
         time_on_air: Duration,
         tx_mode: TxMode,
         count_us: u32,
         frequency: u32,
+        bandwidth: u32,
         tx_power: i8,
+        priority: Priority,
+        rf_chain: u8,
     }
 
     impl TxPacket for TxPacketMock {
@@ -388,7 +1016,11 @@ mod tests {
         }
 
         fn get_id(&self) -> u32 {
-            0
+            self.id
+        }
+
+        fn get_priority(&self) -> Priority {
+            self.priority
         }
 
         fn set_tx_mode(&mut self, tx_mode: TxMode) {
@@ -407,29 +1039,85 @@ mod tests {
             self.frequency
         }
 
+        fn get_bandwidth(&self) -> u32 {
+            self.bandwidth
+        }
+
         fn get_tx_power(&self) -> i8 {
             self.tx_power
         }
+
+        fn set_tx_power(&mut self, tx_power: i8) {
+            self.tx_power = tx_power;
+        }
+
+        fn get_rf_chain(&self) -> u8 {
+            self.rf_chain
+        }
+
+        fn set_rf_chain(&mut self, rf_chain: u8) {
+            self.rf_chain = rf_chain;
+        }
     }
 
     #[test]
     fn test_size() {
-        let q: Queue<TxPacketMock> = Queue::new(10, None);
+        let q: Queue<TxPacketMock> = Queue::new(&config(10), None, None);
         assert_eq!(10, q.size());
     }
 
+    #[test]
+    fn test_configuration_validate() {
+        assert!(Configuration::default().validate().is_ok());
+        assert!(config(0).validate().is_err());
+
+        let c = Configuration {
+            tx_jit_delay: Duration::from_millis(5),
+            ..Default::default()
+        };
+        assert!(c.validate().is_err());
+
+        let c = Configuration {
+            tx_max_advance_delay: Duration::from_millis(40),
+            ..Default::default()
+        };
+        assert!(c.validate().is_err());
+
+        let c = Configuration {
+            tx_immediate_max_delay: Duration::from_millis(50),
+            ..Default::default()
+        };
+        assert!(c.validate().is_err());
+    }
+
+    #[test]
+    fn test_track_send_latency() {
+        let mut q: Queue<TxPacketMock> = Queue::new(&config(2), None, None);
+        assert_eq!(None, q.take_send_latency_max());
+
+        q.track_send_latency(1, Duration::from_millis(5));
+        q.track_send_latency(2, Duration::from_millis(50));
+        q.track_send_latency(3, Duration::from_millis(10));
+        assert_eq!(Some(Duration::from_millis(50)), q.take_send_latency_max());
+        assert_eq!(None, q.take_send_latency_max());
+    }
+
     #[test]
     fn test_enqueue_full() {
-        let mut q: Queue<TxPacketMock> = Queue::new(2, None);
+        let mut q: Queue<TxPacketMock> = Queue::new(&config(2), None, None);
 
         q.enqueue(
             100,
             TxPacketMock {
+                id: 0,
                 time_on_air: Duration::from_millis(100),
                 tx_mode: TxMode::Immediate,
                 count_us: 0,
                 frequency: 868100000,
+                bandwidth: 125000,
                 tx_power: 14,
+                priority: Priority::ClassC,
+                rf_chain: 0,
             },
         )
         .unwrap();
@@ -437,11 +1125,15 @@ mod tests {
         q.enqueue(
             100,
             TxPacketMock {
+                id: 0,
                 time_on_air: Duration::from_millis(100),
                 tx_mode: TxMode::Immediate,
                 count_us: 0,
                 frequency: 868100000,
+                bandwidth: 125000,
                 tx_power: 14,
+                priority: Priority::ClassC,
+                rf_chain: 0,
             },
         )
         .unwrap();
@@ -450,11 +1142,15 @@ mod tests {
             q.enqueue(
                 100,
                 TxPacketMock {
+                    id: 0,
                     time_on_air: Duration::from_millis(100),
                     tx_mode: TxMode::Immediate,
                     count_us: 0,
                     frequency: 868100000,
+                    bandwidth: 125000,
                     tx_power: 14,
+                    priority: Priority::ClassC,
+                    rf_chain: 0,
                 },
             )
             .is_err(),
@@ -464,17 +1160,21 @@ mod tests {
 
     #[test]
     fn test_enqueue_immediate() {
-        let mut q: Queue<TxPacketMock> = Queue::new(2, None);
+        let mut q: Queue<TxPacketMock> = Queue::new(&config(2), None, None);
         let concentrator_count = 100;
 
         q.enqueue(
             concentrator_count,
             TxPacketMock {
+                id: 0,
                 time_on_air: Duration::from_millis(100),
                 tx_mode: TxMode::Immediate,
                 count_us: 0,
                 frequency: 868100000,
+                bandwidth: 125000,
                 tx_power: 14,
+                priority: Priority::ClassC,
+                rf_chain: 0,
             },
         )
         .unwrap();
@@ -482,11 +1182,15 @@ mod tests {
         q.enqueue(
             concentrator_count,
             TxPacketMock {
+                id: 0,
                 time_on_air: Duration::from_millis(100),
                 tx_mode: TxMode::Immediate,
                 count_us: 0,
                 frequency: 868100000,
+                bandwidth: 125000,
                 tx_power: 14,
+                priority: Priority::ClassC,
+                rf_chain: 0,
             },
         )
         .unwrap();
@@ -509,14 +1213,17 @@ mod tests {
         assert_eq!(Duration::from_micros(1500 + 40000), item.pre_delay);
         assert_eq!(Duration::from_millis(100), item.post_delay);
         assert_eq!(
-            first_end_us + item.pre_delay.as_micros() as u32 + q.tx_margin_delay.as_micros() as u32,
+            first_end_us
+                + item.pre_delay.as_micros() as u32
+                + q.tx_margin_delay.as_micros() as u32
+                + 1,
             item.packet.get_count_us()
         );
     }
 
     #[test]
     fn test_enqueue_immediate_u32_wrapping() {
-        let mut q: Queue<TxPacketMock> = Queue::new(2, None);
+        let mut q: Queue<TxPacketMock> = Queue::new(&config(2), None, None);
         let concentrator_count = 0_u32.wrapping_sub(
             (Duration::from_millis(80)
                 + Duration::from_micros(1500 + 40000)
@@ -527,11 +1234,15 @@ mod tests {
         q.enqueue(
             concentrator_count,
             TxPacketMock {
+                id: 0,
                 time_on_air: Duration::from_millis(100),
                 tx_mode: TxMode::Immediate,
                 count_us: 0,
                 frequency: 868100000,
+                bandwidth: 125000,
                 tx_power: 14,
+                priority: Priority::ClassC,
+                rf_chain: 0,
             },
         )
         .unwrap();
@@ -539,11 +1250,15 @@ mod tests {
         q.enqueue(
             concentrator_count,
             TxPacketMock {
+                id: 0,
                 time_on_air: Duration::from_millis(100),
                 tx_mode: TxMode::Immediate,
                 count_us: 0,
                 frequency: 868100000,
+                bandwidth: 125000,
                 tx_power: 14,
+                priority: Priority::ClassC,
+                rf_chain: 0,
             },
         )
         .unwrap();
@@ -552,12 +1267,12 @@ mod tests {
         assert_eq!(4294825796, item.packet.get_count_us());
 
         let item = &q.items[1];
-        assert_eq!(1000, item.packet.get_count_us());
+        assert_eq!(1001, item.packet.get_count_us());
     }
 
     #[test]
     fn test_pop_empty() {
-        let mut q: Queue<TxPacketMock> = Queue::new(2, None);
+        let mut q: Queue<TxPacketMock> = Queue::new(&config(2), None, None);
 
         let item = q.pop(Duration::from_secs(1).as_micros() as u32);
         assert!(item.is_none());
@@ -565,17 +1280,21 @@ mod tests {
 
     #[test]
     fn test_pop() {
-        let mut q: Queue<TxPacketMock> = Queue::new(2, None);
+        let mut q: Queue<TxPacketMock> = Queue::new(&config(2), None, None);
         let concentrator_count = Duration::from_secs(1).as_micros() as u32;
 
         q.enqueue(
             concentrator_count,
             TxPacketMock {
+                id: 0,
                 time_on_air: Duration::from_millis(100),
                 tx_mode: TxMode::Timestamped,
                 count_us: Duration::from_secs(2).as_micros() as u32,
                 frequency: 868100000,
+                bandwidth: 125000,
                 tx_power: 14,
+                priority: Priority::ClassC,
+                rf_chain: 0,
             },
         )
         .unwrap();
@@ -586,17 +1305,21 @@ mod tests {
 
     #[test]
     fn test_pop_too_far_in_future() {
-        let mut q: Queue<TxPacketMock> = Queue::new(2, None);
+        let mut q: Queue<TxPacketMock> = Queue::new(&config(2), None, None);
         let concentrator_count = Duration::from_secs(1).as_micros() as u32;
 
         q.enqueue(
             concentrator_count,
             TxPacketMock {
+                id: 0,
                 time_on_air: Duration::from_millis(100),
                 tx_mode: TxMode::Timestamped,
                 count_us: Duration::from_secs(2).as_micros() as u32,
                 frequency: 868100000,
+                bandwidth: 125000,
                 tx_power: 14,
+                priority: Priority::ClassC,
+                rf_chain: 0,
             },
         )
         .unwrap();
@@ -605,19 +1328,394 @@ mod tests {
         assert!(item.is_none());
     }
 
+    #[test]
+    fn test_pop_too_old() {
+        let mut q: Queue<TxPacketMock> = Queue::new(&config(2), None, None);
+        let concentrator_count = Duration::from_secs(1).as_micros() as u32;
+
+        q.enqueue(
+            concentrator_count,
+            TxPacketMock {
+                id: 0,
+                time_on_air: Duration::from_millis(100),
+                tx_mode: TxMode::Timestamped,
+                count_us: Duration::from_secs(2).as_micros() as u32,
+                frequency: 868100000,
+                bandwidth: 125000,
+                tx_power: 14,
+                priority: Priority::ClassC,
+                rf_chain: 0,
+            },
+        )
+        .unwrap();
+
+        let item = q.pop(Duration::from_secs(3).as_micros() as u32);
+        assert!(item.is_none());
+        assert!(q.empty());
+
+        let dropped = q.take_dropped();
+        assert_eq!(1, dropped.len());
+        assert_eq!(api::TxResult::TooLate, dropped[0].1);
+        assert!(q.take_dropped().is_empty());
+    }
+
+    #[test]
+    fn test_migrate() {
+        let mut q: Queue<TxPacketMock> = Queue::new(&config(2), None, None);
+        let concentrator_count = Duration::from_secs(1).as_micros() as u32;
+
+        for count_us in [Duration::from_secs(2), Duration::from_secs(5)] {
+            q.enqueue(
+                concentrator_count,
+                TxPacketMock {
+                    id: 0,
+                    time_on_air: Duration::from_millis(100),
+                    tx_mode: TxMode::Timestamped,
+                    count_us: count_us.as_micros() as u32,
+                    frequency: 868100000,
+                    bandwidth: 125000,
+                    tx_power: 14,
+                    priority: Priority::ClassC,
+                    rf_chain: 0,
+                },
+            )
+            .unwrap();
+        }
+
+        // The concentrator was restarted 1.5 seconds after the old counter value of 1 second,
+        // the new counter value is 100ms. The packet scheduled at 2 seconds can no longer be
+        // sent, the packet scheduled at 5 seconds must be sent 2.5 seconds after restart.
+        q.migrate(
+            concentrator_count,
+            Duration::from_millis(100).as_micros() as u32,
+            Duration::from_millis(1500),
+        );
+
+        let dropped = q.take_dropped();
+        assert_eq!(1, dropped.len());
+        assert_eq!(
+            Duration::from_secs(2).as_micros() as u32,
+            dropped[0].0.count_us
+        );
+        assert_eq!(1, q.len());
+
+        let item = q.pop(Duration::from_millis(2600).as_micros() as u32);
+        assert_eq!(
+            Duration::from_millis(2600).as_micros() as u32,
+            item.unwrap().count_us
+        );
+    }
+
+    #[test]
+    fn test_enqueue_preempt() {
+        let mut q: Queue<TxPacketMock> = Queue::new(&config(2), None, None);
+        let concentrator_count = Duration::from_secs(1).as_micros() as u32;
+        let packet = |priority, count_us: Duration| TxPacketMock {
+            id: 0,
+            time_on_air: Duration::from_millis(100),
+            tx_mode: TxMode::Timestamped,
+            count_us: count_us.as_micros() as u32,
+            frequency: 868100000,
+            bandwidth: 125000,
+            tx_power: 14,
+            priority,
+            rf_chain: 0,
+        };
+
+        q.enqueue(
+            concentrator_count,
+            packet(Priority::ClassB, Duration::from_secs(2)),
+        )
+        .unwrap();
+
+        // A packet with the same priority can not preempt the queued packet.
+        assert_eq!(
+            Some(api::TxAckStatus::CollisionPacket),
+            q.enqueue(
+                concentrator_count,
+                packet(Priority::ClassB, Duration::from_millis(2050))
+            )
+            .err()
+        );
+
+        // A packet with a higher priority preempts the queued packet.
+        q.enqueue(
+            concentrator_count,
+            packet(Priority::Beacon, Duration::from_millis(2050)),
+        )
+        .unwrap();
+        assert_eq!(1, q.len());
+
+        let dropped = q.take_dropped();
+        assert_eq!(1, dropped.len());
+        assert_eq!(
+            Duration::from_secs(2).as_micros() as u32,
+            dropped[0].0.count_us
+        );
+        assert_eq!(api::TxResult::Preempted, dropped[0].1);
+
+        // A packet with a lower priority can not preempt the beacon.
+        assert_eq!(
+            Some(api::TxAckStatus::CollisionPacket),
+            q.enqueue(
+                concentrator_count,
+                packet(Priority::ClassA, Duration::from_secs(2))
+            )
+            .err()
+        );
+        assert!(q.take_dropped().is_empty());
+    }
+
+    #[test]
+    fn test_enqueue_preempt_duty_cycle() {
+        // The queue starts at a linear_count of one concentrator_count rollover.
+        let linear_count = Duration::from_micros(1 << 32);
+        let tracker = || {
+            let mut tracker = tracker::Tracker::new(
+                standard::Configuration::new(standard::Standard::ETSI_EN_300_220),
+                true,
+                standard::TxPowerPolicy::Reject,
+            );
+
+            // Only 100ms of the band (1%) is left during the last hour.
+            tracker
+                .try_insert(
+                    868100000,
+                    14,
+                    dutycycle::Item {
+                        start_time: linear_count - Duration::from_secs(3000),
+                        end_time: linear_count - Duration::from_secs(3000)
+                            + Duration::from_millis(35900),
+                    },
+                )
+                .unwrap();
+            Some(tracker)
+        };
+        let concentrator_count = Duration::from_secs(1).as_micros() as u32;
+        let packet = |priority, count_us: Duration, time_on_air| TxPacketMock {
+            id: 0,
+            time_on_air,
+            tx_mode: TxMode::Timestamped,
+            count_us: count_us.as_micros() as u32,
+            frequency: 868100000,
+            bandwidth: 125000,
+            tx_power: 14,
+            priority,
+            rf_chain: 0,
+        };
+
+        // The preempted packet releases its duty-cycle.
+        let mut q: Queue<TxPacketMock> = Queue::new(&config(2), tracker(), None);
+        q.enqueue(
+            concentrator_count,
+            packet(
+                Priority::ClassB,
+                Duration::from_secs(2),
+                Duration::from_millis(100),
+            ),
+        )
+        .unwrap();
+        q.enqueue(
+            concentrator_count,
+            packet(
+                Priority::Beacon,
+                Duration::from_millis(2050),
+                Duration::from_millis(100),
+            ),
+        )
+        .unwrap();
+        assert_eq!(1, q.len());
+        assert_eq!(1, q.take_dropped().len());
+
+        // The packet exceeds the duty-cycle, even when the queued packet is preempted. The
+        // queued packet is kept and its duty-cycle is still tracked.
+        let mut q: Queue<TxPacketMock> = Queue::new(&config(2), tracker(), None);
+        q.enqueue(
+            concentrator_count,
+            packet(
+                Priority::ClassB,
+                Duration::from_secs(2),
+                Duration::from_millis(100),
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            Some(api::TxAckStatus::DutyCycleOverflow),
+            q.enqueue(
+                concentrator_count,
+                packet(
+                    Priority::Beacon,
+                    Duration::from_millis(2050),
+                    Duration::from_millis(150),
+                ),
+            )
+            .err()
+        );
+        assert_eq!(1, q.len());
+        assert!(q.take_dropped().is_empty());
+        assert_eq!(
+            Some(api::TxAckStatus::DutyCycleOverflow),
+            q.enqueue(
+                concentrator_count,
+                packet(
+                    Priority::ClassC,
+                    Duration::from_secs(3),
+                    Duration::from_millis(1),
+                ),
+            )
+            .err()
+        );
+    }
+
+    #[test]
+    fn test_get_items_and_remove() {
+        let mut q: Queue<TxPacketMock> = Queue::new(&config(2), None, None);
+        let concentrator_count = Duration::from_secs(1).as_micros() as u32;
+
+        for (id, count_us) in [(1, Duration::from_secs(2)), (2, Duration::from_secs(5))] {
+            q.enqueue(
+                concentrator_count,
+                TxPacketMock {
+                    id,
+                    time_on_air: Duration::from_millis(100),
+                    tx_mode: TxMode::Timestamped,
+                    count_us: count_us.as_micros() as u32,
+                    frequency: 868100000,
+                    bandwidth: 125000,
+                    tx_power: 14,
+                    priority: Priority::ClassC,
+                    rf_chain: 0,
+                },
+            )
+            .unwrap();
+        }
+
+        let items = q.get_items(Duration::from_millis(1500).as_micros() as u32);
+        assert_eq!(
+            vec![
+                api::QueueItem {
+                    downlink_id: 1,
+                    count_us: 2000000,
+                    frequency: 868100000,
+                    time_on_air: Some(Duration::from_millis(100).try_into().unwrap()),
+                    time_until_tx: Some(Duration::from_millis(500).try_into().unwrap()),
+                    rf_chain: 0,
+                },
+                api::QueueItem {
+                    downlink_id: 2,
+                    count_us: 5000000,
+                    frequency: 868100000,
+                    time_on_air: Some(Duration::from_millis(100).try_into().unwrap()),
+                    time_until_tx: Some(Duration::from_millis(3500).try_into().unwrap()),
+                    rf_chain: 0,
+                },
+            ],
+            items
+        );
+
+        assert!(!q.remove(3));
+        assert!(q.remove(1));
+        assert_eq!(1, q.len());
+
+        let dropped = q.take_dropped();
+        assert_eq!(1, dropped.len());
+        assert_eq!(1, dropped[0].0.id);
+        assert_eq!(api::TxResult::Cancelled, dropped[0].1);
+    }
+
+    #[test]
+    fn test_enqueue_rf_chains() {
+        let concentrator_count = Duration::from_secs(1).as_micros() as u32;
+        let packet = |tx_mode, count_us: Duration, rf_chain| TxPacketMock {
+            id: 0,
+            time_on_air: Duration::from_millis(100),
+            tx_mode,
+            count_us: count_us.as_micros() as u32,
+            frequency: 868100000,
+            bandwidth: 125000,
+            tx_power: 14,
+            priority: Priority::ClassA,
+            rf_chain,
+        };
+
+        // All RF chains share a single transmitter.
+        let mut q: Queue<TxPacketMock> = Queue::new(&config(4), None, None);
+        q.enqueue(
+            concentrator_count,
+            packet(TxMode::Timestamped, Duration::from_millis(1100), 0),
+        )
+        .unwrap();
+        assert_eq!(
+            Some(api::TxAckStatus::CollisionPacket),
+            q.enqueue(
+                concentrator_count,
+                packet(TxMode::Timestamped, Duration::from_millis(1120), 1)
+            )
+            .err()
+        );
+
+        // Both RF chains can transmit at the same time.
+        let mut q: Queue<TxPacketMock> = Queue::new(&config(4), None, None).with_rf_chains(
+            (0..2)
+                .map(|rf_chain| RfChain {
+                    rf_chain,
+                    tx_min_max_freqs: vec![(863000000, 870000000)],
+                })
+                .collect(),
+        );
+        q.enqueue(
+            concentrator_count,
+            packet(TxMode::Timestamped, Duration::from_millis(1100), 0),
+        )
+        .unwrap();
+
+        // The immediate packet is sent earlier using the other RF chain.
+        let enqueued = q
+            .enqueue(
+                concentrator_count,
+                packet(TxMode::Immediate, Duration::ZERO, 0),
+            )
+            .unwrap();
+        assert_eq!(1, enqueued.get_rf_chain());
+        assert_eq!(1080000, enqueued.get_count_us());
+
+        // Both RF chains are busy.
+        assert_eq!(
+            Some(api::TxAckStatus::CollisionPacket),
+            q.enqueue(
+                concentrator_count,
+                packet(TxMode::Timestamped, Duration::from_millis(1120), 0)
+            )
+            .err()
+        );
+
+        // The requested RF chain is busy, the other RF chain is used.
+        let enqueued = q
+            .enqueue(
+                concentrator_count,
+                packet(TxMode::Timestamped, Duration::from_millis(1230), 0),
+            )
+            .unwrap();
+        assert_eq!(1, enqueued.get_rf_chain());
+    }
+
     #[test]
     fn test_pop_u32_wrapping() {
-        let mut q: Queue<TxPacketMock> = Queue::new(2, None);
+        let mut q: Queue<TxPacketMock> = Queue::new(&config(2), None, None);
         let concentrator_count = 0_u32.wrapping_sub(Duration::from_secs(1).as_micros() as u32);
 
         q.enqueue(
             concentrator_count,
             TxPacketMock {
+                id: 0,
                 time_on_air: Duration::from_millis(100),
                 tx_mode: TxMode::Timestamped,
                 count_us: 1,
                 frequency: 868100000,
+                bandwidth: 125000,
                 tx_power: 14,
+                priority: Priority::ClassC,
+                rf_chain: 0,
             },
         )
         .unwrap();
@@ -625,4 +1723,164 @@ mod tests {
         let item = q.pop(0_u32.wrapping_sub(100));
         assert!(item.is_some());
     }
+
+    #[test]
+    fn test_enqueue_dwell_time() {
+        let mut q: Queue<TxPacketMock> =
+            Queue::new(&config(2), None, Some(dwelltime::Configuration::us915()));
+
+        assert_eq!(
+            Some(api::TxAckStatus::DwellTime),
+            q.enqueue(
+                100,
+                TxPacketMock {
+                    id: 0,
+                    time_on_air: Duration::from_millis(401),
+                    tx_mode: TxMode::Immediate,
+                    count_us: 0,
+                    frequency: 902300000,
+                    bandwidth: 125000,
+                    tx_power: 14,
+                    priority: Priority::ClassC,
+                    rf_chain: 0,
+                },
+            )
+            .err()
+        );
+
+        // 500 kHz channels do not have a dwell-time limit.
+        q.enqueue(
+            100,
+            TxPacketMock {
+                id: 0,
+                time_on_air: Duration::from_millis(401),
+                tx_mode: TxMode::Immediate,
+                count_us: 0,
+                frequency: 923300000,
+                bandwidth: 500000,
+                tx_power: 14,
+                priority: Priority::ClassC,
+                rf_chain: 0,
+            },
+        )
+        .unwrap();
+
+        q.enqueue(
+            100,
+            TxPacketMock {
+                id: 0,
+                time_on_air: Duration::from_millis(400),
+                tx_mode: TxMode::Immediate,
+                count_us: 0,
+                frequency: 902300000,
+                bandwidth: 125000,
+                tx_power: 14,
+                priority: Priority::ClassC,
+                rf_chain: 0,
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_enqueue_tx_power() {
+        let tracker = |policy| {
+            Some(tracker::Tracker::new(
+                standard::Configuration::new(standard::Standard::ETSI_EN_300_220),
+                true,
+                policy,
+            ))
+        };
+        let packet = TxPacketMock {
+            id: 0,
+            time_on_air: Duration::from_millis(100),
+            tx_mode: TxMode::Immediate,
+            count_us: 0,
+            frequency: 868100000,
+            bandwidth: 125000,
+            tx_power: 20,
+            priority: Priority::ClassC,
+            rf_chain: 0,
+        };
+
+        let mut q: Queue<TxPacketMock> =
+            Queue::new(&config(2), tracker(standard::TxPowerPolicy::Reject), None);
+        assert_eq!(
+            Some(api::TxAckStatus::TxPower),
+            q.enqueue(100, packet).err()
+        );
+
+        let mut q: Queue<TxPacketMock> =
+            Queue::new(&config(2), tracker(standard::TxPowerPolicy::Clamp), None);
+        let enqueued = q.enqueue(100, packet).unwrap();
+        assert_eq!(16, enqueued.get_tx_power());
+        assert_eq!(16, q.items[0].packet.get_tx_power());
+    }
+
+    #[test]
+    fn test_enqueue_immediate_duty_cycle() {
+        // The queue starts at a linear_count of one concentrator_count rollover.
+        let linear_count = Duration::from_micros(1 << 32);
+        let tracker = || {
+            let mut tracker = tracker::Tracker::new(
+                standard::Configuration::new(standard::Standard::ETSI_EN_300_220),
+                true,
+                standard::TxPowerPolicy::Reject,
+            );
+
+            // The band (1%) is fully used during the last hour.
+            tracker
+                .try_insert(
+                    868100000,
+                    14,
+                    dutycycle::Item {
+                        start_time: linear_count - Duration::from_secs(3590),
+                        end_time: linear_count - Duration::from_secs(3554),
+                    },
+                )
+                .unwrap();
+            Some(tracker)
+        };
+        let packet = TxPacketMock {
+            id: 0,
+            time_on_air: Duration::from_millis(100),
+            tx_mode: TxMode::Immediate,
+            count_us: 0,
+            frequency: 868100000,
+            bandwidth: 125000,
+            tx_power: 14,
+            priority: Priority::ClassC,
+            rf_chain: 0,
+        };
+
+        // The packet fits once 100ms of the tracked item has left the window, at 10s.
+        let mut q: Queue<TxPacketMock> = Queue::new(
+            &Configuration {
+                tx_immediate_max_delay: Duration::from_secs(60),
+                ..Default::default()
+            },
+            tracker(),
+            None,
+        );
+        let enqueued = q.enqueue(0, packet).unwrap();
+        assert!(
+            (10_000_000..=10_001_000).contains(&enqueued.get_count_us()),
+            "count_us: {}",
+            enqueued.get_count_us()
+        );
+
+        // The slot is beyond the tx_immediate_max_delay.
+        let mut q: Queue<TxPacketMock> = Queue::new(
+            &Configuration {
+                tx_immediate_max_delay: Duration::from_secs(5),
+                ..Default::default()
+            },
+            tracker(),
+            None,
+        );
+        assert_eq!(
+            Some(api::TxAckStatus::DutyCycleOverflow),
+            q.enqueue(0, packet).err()
+        );
+    }
 }
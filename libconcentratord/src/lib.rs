@@ -1,16 +1,23 @@
 #[macro_use]
 extern crate anyhow;
 
+pub mod api;
+pub mod capture;
 pub mod commands;
+pub mod config_file;
+pub mod config_state;
 pub mod error;
 pub mod events;
 pub mod gnss;
 pub mod gpsd;
 mod helpers;
 pub mod jitqueue;
+pub mod metrics;
+pub mod model;
 pub mod region;
 pub mod regulation;
 pub mod reset;
 pub mod signals;
 mod socket;
 pub mod stats;
+pub mod validate;
@@ -0,0 +1,470 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{LazyLock, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+use chirpstack_api::gw;
+use log::{info, warn};
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+static METRICS: LazyLock<Mutex<Metrics>> = LazyLock::new(|| Mutex::new(Default::default()));
+static HTTP_BIND: LazyLock<Mutex<Option<String>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Cumulative gateway metrics.
+///
+/// Unlike the gateway stats, the counters are never reset. The gauges are updated every stats
+/// interval.
+#[derive(Default)]
+struct Metrics {
+    rx_packets_received: u64,
+    rx_packets_received_ok: u64,
+    rx_packets_per_frequency: BTreeMap<u32, u64>,
+    rx_packets_per_modulation: BTreeMap<String, u64>,
+    tx_packets_received: u64,
+    tx_packets_emitted: u64,
+    tx_packets_per_frequency: BTreeMap<u32, u64>,
+    tx_packets_per_modulation: BTreeMap<String, u64>,
+    tx_packets_per_status: BTreeMap<String, u64>,
+    jit_queue_size: Option<usize>,
+    jit_send_latency_max: Option<Duration>,
+    duty_cycle_stats: Option<gw::DutyCycleStats>,
+    concentrator_temperature: Option<f32>,
+    gnss_locked: Option<bool>,
+}
+
+impl Metrics {
+    fn encode(&self) -> String {
+        let mut out = String::new();
+
+        counter(
+            &mut out,
+            "concentratord_rx_packets_received",
+            "Number of received packets (including packets with CRC error).",
+            [(String::new(), self.rx_packets_received)],
+        );
+        counter(
+            &mut out,
+            "concentratord_rx_packets_received_ok",
+            "Number of received packets with valid CRC.",
+            [(String::new(), self.rx_packets_received_ok)],
+        );
+        counter(
+            &mut out,
+            "concentratord_rx_packets_per_frequency",
+            "Number of received packets with valid CRC per frequency.",
+            self.rx_packets_per_frequency
+                .iter()
+                .map(|(k, v)| (format!("frequency=\"{}\"", k), *v)),
+        );
+        counter(
+            &mut out,
+            "concentratord_rx_packets_per_modulation",
+            "Number of received packets with valid CRC per modulation.",
+            self.rx_packets_per_modulation
+                .iter()
+                .map(|(k, v)| (format!("modulation=\"{}\"", k), *v)),
+        );
+        counter(
+            &mut out,
+            "concentratord_tx_packets_received",
+            "Number of received downlink requests.",
+            [(String::new(), self.tx_packets_received)],
+        );
+        counter(
+            &mut out,
+            "concentratord_tx_packets_emitted",
+            "Number of packets scheduled for transmission.",
+            [(String::new(), self.tx_packets_emitted)],
+        );
+        counter(
+            &mut out,
+            "concentratord_tx_packets_per_frequency",
+            "Number of packets scheduled for transmission per frequency.",
+            self.tx_packets_per_frequency
+                .iter()
+                .map(|(k, v)| (format!("frequency=\"{}\"", k), *v)),
+        );
+        counter(
+            &mut out,
+            "concentratord_tx_packets_per_modulation",
+            "Number of packets scheduled for transmission per modulation.",
+            self.tx_packets_per_modulation
+                .iter()
+                .map(|(k, v)| (format!("modulation=\"{}\"", k), *v)),
+        );
+        counter(
+            &mut out,
+            "concentratord_tx_packets_per_status",
+            "Number of downlink requests per TX ack status.",
+            self.tx_packets_per_status
+                .iter()
+                .map(|(k, v)| (format!("status=\"{}\"", k), *v)),
+        );
+
+        if let Some(v) = self.jit_queue_size {
+            gauge(
+                &mut out,
+                "concentratord_jit_queue_size",
+                "Number of packets in the JIT queue.",
+                None,
+                [(String::new(), v as f64)],
+            );
+        }
+
+        if let Some(v) = self.jit_send_latency_max {
+            gauge(
+                &mut out,
+                "concentratord_jit_send_latency_max_seconds",
+                "Max. latency between popping a packet from the JIT queue and handing it to the concentrator, within the last stats interval.",
+                Some("seconds"),
+                [(String::new(), v.as_secs_f64())],
+            );
+        }
+
+        if let Some(dc_stats) = &self.duty_cycle_stats {
+            let window = dc_stats
+                .window
+                .and_then(|v| Duration::try_from(v).ok())
+                .unwrap_or_default()
+                .as_secs_f64();
+
+            if window > 0.0 {
+                let ratio = |d: Option<chirpstack_api::prost_types::Duration>| {
+                    d.and_then(|v| Duration::try_from(v).ok())
+                        .unwrap_or_default()
+                        .as_secs_f64()
+                        / window
+                };
+                let labels = |b: &gw::DutyCycleBand| {
+                    format!(
+                        "band=\"{}\",frequency_min=\"{}\",frequency_max=\"{}\"",
+                        escape(&b.name),
+                        b.frequency_min,
+                        b.frequency_max
+                    )
+                };
+
+                gauge(
+                    &mut out,
+                    "concentratord_duty_cycle_load_ratio",
+                    "Tracked duty-cycle load per band.",
+                    Some("ratio"),
+                    dc_stats
+                        .bands
+                        .iter()
+                        .map(|b| (labels(b), ratio(b.load_tracked))),
+                );
+                gauge(
+                    &mut out,
+                    "concentratord_duty_cycle_load_max_ratio",
+                    "Max. duty-cycle load per band.",
+                    Some("ratio"),
+                    dc_stats
+                        .bands
+                        .iter()
+                        .map(|b| (labels(b), ratio(b.load_max))),
+                );
+            }
+        }
+
+        if let Some(v) = self.concentrator_temperature {
+            gauge(
+                &mut out,
+                "concentratord_concentrator_temperature_celsius",
+                "Concentrator temperature.",
+                Some("celsius"),
+                [(String::new(), v.into())],
+            );
+        }
+
+        if let Some(v) = self.gnss_locked {
+            gauge(
+                &mut out,
+                "concentratord_gnss_locked",
+                "GNSS lock state (1 = locked).",
+                None,
+                [(String::new(), if v { 1.0 } else { 0.0 })],
+            );
+        }
+
+        out.push_str("# EOF\n");
+        out
+    }
+}
+
+pub fn inc_rx_packets_received() {
+    let mut m = METRICS.lock().unwrap();
+    m.rx_packets_received += 1;
+}
+
+pub fn inc_rx_counts(pl: &gw::UplinkFrame) {
+    let mut m = METRICS.lock().unwrap();
+    m.rx_packets_received_ok += 1;
+
+    if let Some(tx_info) = &pl.tx_info {
+        *m.rx_packets_per_frequency
+            .entry(tx_info.frequency)
+            .or_default() += 1;
+        *m.rx_packets_per_modulation
+            .entry(modulation_label(&tx_info.modulation))
+            .or_default() += 1;
+    }
+}
+
+pub fn inc_tx_packets_received() {
+    let mut m = METRICS.lock().unwrap();
+    m.tx_packets_received += 1;
+}
+
+pub fn inc_tx_counts(tx_info: &gw::DownlinkTxInfo) {
+    let mut m = METRICS.lock().unwrap();
+    m.tx_packets_emitted += 1;
+    *m.tx_packets_per_frequency
+        .entry(tx_info.frequency)
+        .or_default() += 1;
+    *m.tx_packets_per_modulation
+        .entry(modulation_label(&tx_info.modulation))
+        .or_default() += 1;
+}
+
+pub fn inc_tx_status_count(status: &str) {
+    let mut m = METRICS.lock().unwrap();
+    *m.tx_packets_per_status
+        .entry(status.to_string())
+        .or_default() += 1;
+}
+
+pub fn set_duty_cycle_stats(duty_cycle_stats: Option<gw::DutyCycleStats>) {
+    let mut m = METRICS.lock().unwrap();
+    m.duty_cycle_stats = duty_cycle_stats;
+}
+
+pub fn set_jit_queue_size(size: usize) {
+    let mut m = METRICS.lock().unwrap();
+    m.jit_queue_size = Some(size);
+}
+
+pub fn set_jit_send_latency_max(latency: Option<Duration>) {
+    let mut m = METRICS.lock().unwrap();
+    m.jit_send_latency_max = latency;
+}
+
+pub fn set_concentrator_temperature(temperature: Option<f32>) {
+    let mut m = METRICS.lock().unwrap();
+    m.concentrator_temperature = temperature;
+}
+
+pub fn set_gnss_locked(locked: bool) {
+    let mut m = METRICS.lock().unwrap();
+    m.gnss_locked = Some(locked);
+}
+
+/// Return the metrics in OpenMetrics text format.
+pub fn encode() -> String {
+    METRICS.lock().unwrap().encode()
+}
+
+/// Start the HTTP server serving the metrics on /metrics.
+///
+/// As the server is not affected by configuration updates, it is only started once. An empty
+/// bind disables the server.
+pub fn bind_http(bind: &str) -> Result<()> {
+    let mut http_bind = HTTP_BIND.lock().unwrap();
+    if bind.is_empty() || http_bind.is_some() {
+        return Ok(());
+    }
+
+    info!("Starting metrics server, bind: {}", bind);
+
+    let listener = TcpListener::bind(bind)?;
+    *http_bind = Some(bind.to_string());
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let res = match stream {
+                Ok(v) => handle_http(v),
+                Err(e) => Err(e.into()),
+            };
+
+            if let Err(e) = res {
+                warn!("Handle metrics request error, error: {}", e);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn handle_http(mut stream: TcpStream) -> Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // consume headers
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts
+        .next()
+        .unwrap_or_default()
+        .split('?')
+        .next()
+        .unwrap_or_default();
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/metrics") => ("200 OK", CONTENT_TYPE, encode()),
+        _ => ("404 Not Found", "text/plain", "Not Found\n".to_string()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+
+    Ok(())
+}
+
+fn counter<I>(out: &mut String, name: &str, help: &str, samples: I)
+where
+    I: IntoIterator<Item = (String, u64)>,
+{
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    for (labels, value) in samples {
+        if labels.is_empty() {
+            let _ = writeln!(out, "{}_total {}", name, value);
+        } else {
+            let _ = writeln!(out, "{}_total{{{}}} {}", name, labels, value);
+        }
+    }
+}
+
+fn gauge<I>(out: &mut String, name: &str, help: &str, unit: Option<&str>, samples: I)
+where
+    I: IntoIterator<Item = (String, f64)>,
+{
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    if let Some(unit) = unit {
+        let _ = writeln!(out, "# UNIT {} {}", name, unit);
+    }
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    for (labels, value) in samples {
+        if labels.is_empty() {
+            let _ = writeln!(out, "{} {}", name, value);
+        } else {
+            let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+        }
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn modulation_label(modulation: &Option<gw::Modulation>) -> String {
+    match modulation.as_ref().and_then(|v| v.parameters.as_ref()) {
+        Some(gw::modulation::Parameters::Lora(v)) => {
+            format!("LORA_SF{}_BW{}", v.spreading_factor, v.bandwidth)
+        }
+        Some(gw::modulation::Parameters::Fsk(v)) => format!("FSK_{}", v.datarate),
+        Some(gw::modulation::Parameters::LrFhss(v)) => {
+            format!("LR_FHSS_OCW{}", v.operating_channel_width)
+        }
+        None => "UNKNOWN".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let mut m = Metrics {
+            rx_packets_received: 3,
+            rx_packets_received_ok: 2,
+            ..Default::default()
+        };
+        m.rx_packets_per_frequency.insert(868100000, 2);
+        m.rx_packets_per_modulation
+            .insert("LORA_SF7_BW125000".to_string(), 2);
+        m.tx_packets_per_status.insert("OK".to_string(), 1);
+        m.jit_queue_size = Some(1);
+        m.jit_send_latency_max = Some(Duration::from_millis(12));
+        m.gnss_locked = Some(false);
+        m.duty_cycle_stats = Some(gw::DutyCycleStats {
+            window: Some(chirpstack_api::prost_types::Duration {
+                seconds: 100,
+                nanos: 0,
+            }),
+            bands: vec![gw::DutyCycleBand {
+                name: "L".to_string(),
+                frequency_min: 868000000,
+                frequency_max: 868600000,
+                load_max: Some(chirpstack_api::prost_types::Duration {
+                    seconds: 1,
+                    nanos: 0,
+                }),
+                load_tracked: Some(chirpstack_api::prost_types::Duration {
+                    seconds: 0,
+                    nanos: 500_000_000,
+                }),
+            }],
+            ..Default::default()
+        });
+
+        let out = m.encode();
+        let expected = [
+            "# TYPE concentratord_rx_packets_received counter",
+            "concentratord_rx_packets_received_total 3",
+            "concentratord_rx_packets_received_ok_total 2",
+            "concentratord_rx_packets_per_frequency_total{frequency=\"868100000\"} 2",
+            "concentratord_rx_packets_per_modulation_total{modulation=\"LORA_SF7_BW125000\"} 2",
+            "concentratord_tx_packets_per_status_total{status=\"OK\"} 1",
+            "concentratord_jit_queue_size 1",
+            "concentratord_jit_send_latency_max_seconds 0.012",
+            "# UNIT concentratord_duty_cycle_load_ratio ratio",
+            "concentratord_duty_cycle_load_ratio{band=\"L\",frequency_min=\"868000000\",frequency_max=\"868600000\"} 0.005",
+            "concentratord_duty_cycle_load_max_ratio{band=\"L\",frequency_min=\"868000000\",frequency_max=\"868600000\"} 0.01",
+            "concentratord_gnss_locked 0",
+        ];
+        for line in expected {
+            assert!(out.lines().any(|l| l == line), "missing: {}", line);
+        }
+        assert!(!out.contains("concentratord_concentrator_temperature_celsius"));
+        assert!(out.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn test_modulation_label() {
+        assert_eq!("UNKNOWN", modulation_label(&None));
+        assert_eq!(
+            "FSK_50000",
+            modulation_label(&Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Fsk(gw::FskModulationInfo {
+                    datarate: 50000,
+                    ..Default::default()
+                })),
+            }))
+        );
+    }
+}
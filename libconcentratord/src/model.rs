@@ -0,0 +1,228 @@
+//! Gateway model definitions.
+//!
+//! A model definition describes the hardware of a gateway model (radios, TX gain tables,
+//! device paths, pins, ...) as TOML. Parts of the definition can be changed for specific
+//! regions, model flags or antenna gains using overrides:
+//!
+//! ```toml
+//! regions = ["EU868", "US915"]
+//! com_type = "SPI"
+//! com_path = "/dev/spidev0.0"
+//!
+//! [[overrides]]
+//!   flags = ["USB"]
+//!   com_type = "USB"
+//!   com_path = "/dev/ttyACM0"
+//! ```
+//!
+//! When set, regions restricts the regions supported by the model and antenna_gains restricts
+//! the supported antenna gains (dBi). The regions, antenna_gains and overrides keys are handled
+//! by the loader and removed before the definition is decoded.
+//!
+//! An override applies when the region is one of its regions, all its flags are set and the
+//! antenna gain is one of its antenna_gains (an omitted condition always matches). Overrides are applied in order. Tables are merged, the
+//! radios array is merged by index and all other values are replaced.
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use toml::{Table, Value};
+
+/// Load the given model definition, applying the overrides matching the region, model flags
+/// and antenna gain. When the region is not set, the default_region of the definition is used.
+pub fn load<T: DeserializeOwned>(
+    definition: &str,
+    region: Option<&str>,
+    flags: &[String],
+    antenna_gain: i8,
+) -> Result<T> {
+    let mut def: Table = toml::from_str(definition).context("Parse model definition")?;
+
+    let overrides = match def.remove("overrides") {
+        Some(Value::Array(v)) => v,
+        Some(_) => return Err(anyhow!("overrides must be an array of tables")),
+        None => vec![],
+    };
+
+    let region = match region {
+        Some(v) => Some(v.to_string()),
+        None => def
+            .get("default_region")
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string()),
+    };
+
+    if let Some(regions) = def.remove("regions") {
+        let region = region
+            .as_deref()
+            .ok_or_else(|| anyhow!("You must specify a region"))?;
+        if !contains(&regions, region) {
+            return Err(anyhow!("Region is not supported: {}", region));
+        }
+    }
+
+    if let Some(antenna_gains) = def.remove("antenna_gains")
+        && !contains_integer(&antenna_gains, antenna_gain.into())
+    {
+        return Err(anyhow!("Antenna gain is not supported: {}", antenna_gain));
+    }
+
+    for o in overrides {
+        let mut o = match o {
+            Value::Table(v) => v,
+            _ => return Err(anyhow!("overrides must be an array of tables")),
+        };
+
+        let region_match = match o.remove("regions") {
+            Some(v) => region.as_deref().map(|r| contains(&v, r)).unwrap_or(false),
+            None => true,
+        };
+        let flags_match = match o.remove("flags") {
+            Some(Value::Array(v)) => v
+                .iter()
+                .all(|f| flags.iter().any(|flag| Some(flag.as_str()) == f.as_str())),
+            Some(_) => return Err(anyhow!("override flags must be an array")),
+            None => true,
+        };
+        let antenna_gain_match = match o.remove("antenna_gains") {
+            Some(v) => contains_integer(&v, antenna_gain.into()),
+            None => true,
+        };
+
+        if region_match && flags_match && antenna_gain_match {
+            merge(&mut def, o);
+        }
+    }
+
+    Value::Table(def)
+        .try_into()
+        .context("Decode model definition")
+}
+
+fn contains(list: &Value, s: &str) -> bool {
+    match list {
+        Value::Array(v) => v.iter().any(|v| v.as_str() == Some(s)),
+        _ => false,
+    }
+}
+
+fn contains_integer(list: &Value, i: i64) -> bool {
+    match list {
+        Value::Array(v) => v.iter().any(|v| v.as_integer() == Some(i)),
+        _ => false,
+    }
+}
+
+fn merge(base: &mut Table, other: Table) {
+    for (k, v) in other {
+        match (base.get_mut(&k), v) {
+            (Some(Value::Table(base)), Value::Table(other)) => merge(base, other),
+            (Some(Value::Array(base)), Value::Array(other)) if k == "radios" => {
+                for (i, v) in other.into_iter().enumerate() {
+                    match (base.get_mut(i), v) {
+                        (Some(Value::Table(base)), Value::Table(other)) => merge(base, other),
+                        (Some(base), v) => *base = v,
+                        (None, v) => base.push(v),
+                    }
+                }
+            }
+            (_, v) => {
+                base.insert(k, v);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Definition {
+        default_region: Option<String>,
+        com_path: String,
+        radios: Vec<Radio>,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Radio {
+        rssi_offset: f32,
+        tx_gain_table: Vec<u8>,
+    }
+
+    const DEFINITION: &str = r#"
+        regions = ["EU868", "CN470"]
+        default_region = "EU868"
+        com_path = "/dev/spidev0.0"
+
+        [[radios]]
+          rssi_offset = -215.4
+          tx_gain_table = [1, 2, 3]
+
+        [[radios]]
+          rssi_offset = -215.4
+          tx_gain_table = []
+
+        [[overrides]]
+          regions = ["CN470"]
+
+          [[overrides.radios]]
+            rssi_offset = -207.0
+            tx_gain_table = [4]
+
+          [[overrides.radios]]
+            rssi_offset = -207.0
+
+        [[overrides]]
+          flags = ["USB"]
+          com_path = "/dev/ttyACM0"
+    "#;
+
+    #[test]
+    fn test_load() {
+        let def: Definition = load(DEFINITION, None, &[], 2).unwrap();
+        assert_eq!("/dev/spidev0.0", def.com_path);
+        assert_eq!(-215.4, def.radios[0].rssi_offset);
+        assert_eq!(vec![1, 2, 3], def.radios[0].tx_gain_table);
+
+        let def: Definition = load(DEFINITION, Some("CN470"), &["USB".into()], 2).unwrap();
+        assert_eq!("/dev/ttyACM0", def.com_path);
+        assert_eq!(
+            vec![
+                Radio {
+                    rssi_offset: -207.0,
+                    tx_gain_table: vec![4],
+                },
+                Radio {
+                    rssi_offset: -207.0,
+                    tx_gain_table: vec![],
+                },
+            ],
+            def.radios
+        );
+
+        assert!(load::<Definition>(DEFINITION, Some("US915"), &[], 2).is_err());
+    }
+
+    #[test]
+    fn test_load_antenna_gain() {
+        const DEFINITION: &str = r#"
+            antenna_gains = [2, 4]
+            com_path = "/dev/spidev0.0"
+            radios = []
+
+            [[overrides]]
+              antenna_gains = [4]
+              com_path = "/dev/spidev1.0"
+        "#;
+
+        let def: Definition = load(DEFINITION, None, &[], 2).unwrap();
+        assert_eq!("/dev/spidev0.0", def.com_path);
+
+        let def: Definition = load(DEFINITION, None, &[], 4).unwrap();
+        assert_eq!("/dev/spidev1.0", def.com_path);
+
+        assert!(load::<Definition>(DEFINITION, None, &[], 3).is_err());
+    }
+}
@@ -1 +1,16 @@
+use super::{ChannelPlan, LoRaStdChannel};
+
 pub const TX_MIN_MAX_FREQS: [(u32, u32); 1] = [(915000000, 928000000)];
+
+/// Default uplink channel plan.
+pub const CHANNEL_PLAN: ChannelPlan = ChannelPlan {
+    multi_sf_channels: [
+        923200000, 923400000, 923600000, 923800000, 924000000, 924200000, 924400000, 924600000,
+    ],
+    lora_std: Some(LoRaStdChannel {
+        frequency: 924500000,
+        bandwidth: 250000,
+        spreading_factor: 7,
+    }),
+    fsk: None,
+};
@@ -1 +1,16 @@
+use super::{ChannelPlan, LoRaStdChannel};
+
 pub const TX_MIN_MAX_FREQS: [(u32, u32); 1] = [(915000000, 928000000)];
+
+/// Default uplink channel plan.
+pub const CHANNEL_PLAN: ChannelPlan = ChannelPlan {
+    multi_sf_channels: [
+        921400000, 921600000, 921800000, 922000000, 922200000, 922400000, 922600000, 922800000,
+    ],
+    lora_std: Some(LoRaStdChannel {
+        frequency: 922700000,
+        bandwidth: 250000,
+        spreading_factor: 7,
+    }),
+    fsk: None,
+};
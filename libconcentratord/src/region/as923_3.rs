@@ -1 +1,16 @@
+use super::{ChannelPlan, LoRaStdChannel};
+
 pub const TX_MIN_MAX_FREQS: [(u32, u32); 1] = [(915000000, 928000000)];
+
+/// Default uplink channel plan.
+pub const CHANNEL_PLAN: ChannelPlan = ChannelPlan {
+    multi_sf_channels: [
+        916600000, 916800000, 917000000, 917200000, 917400000, 917600000, 917800000, 918000000,
+    ],
+    lora_std: Some(LoRaStdChannel {
+        frequency: 917900000,
+        bandwidth: 250000,
+        spreading_factor: 7,
+    }),
+    fsk: None,
+};
@@ -1 +1,16 @@
+use super::{ChannelPlan, LoRaStdChannel};
+
 pub const TX_MIN_MAX_FREQS: [(u32, u32); 1] = [(915000000, 928000000)];
+
+/// Default uplink channel plan.
+pub const CHANNEL_PLAN: ChannelPlan = ChannelPlan {
+    multi_sf_channels: [
+        917300000, 917500000, 917700000, 917900000, 918100000, 918300000, 918500000, 918700000,
+    ],
+    lora_std: Some(LoRaStdChannel {
+        frequency: 918600000,
+        bandwidth: 250000,
+        spreading_factor: 7,
+    }),
+    fsk: None,
+};
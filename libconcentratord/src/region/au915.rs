@@ -1 +1,26 @@
+use anyhow::Result;
+
+use super::{ChannelPlan, LoRaStdChannel};
+
 pub const TX_MIN_MAX_FREQS: [(u32, u32); 1] = [(915000000, 928000000)];
+
+/// Number of sub-bands. Each sub-band holds eight 125 kHz channels and one 500 kHz channel.
+pub const SUB_BANDS: u8 = 8;
+
+/// Returns the channel plan of the given sub-band (0 - 7), sub-band 0 holds channels 0 - 7
+/// and 64.
+pub fn channel_plan(sub_band: u8) -> Result<ChannelPlan> {
+    if sub_band >= SUB_BANDS {
+        return Err(anyhow!("Invalid AU915 sub-band: {}", sub_band));
+    }
+
+    Ok(ChannelPlan {
+        multi_sf_channels: super::sub_band_channels(915200000, sub_band),
+        lora_std: Some(LoRaStdChannel {
+            frequency: 915900000 + sub_band as u32 * 1600000,
+            bandwidth: 500000,
+            spreading_factor: 8,
+        }),
+        fsk: None,
+    })
+}
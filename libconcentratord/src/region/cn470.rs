@@ -1 +1,21 @@
+use anyhow::Result;
+
+use super::ChannelPlan;
+
 pub const TX_MIN_MAX_FREQS: [(u32, u32); 1] = [(470000000, 510000000)];
+
+/// Number of sub-bands. Each sub-band holds eight 125 kHz channels.
+pub const SUB_BANDS: u8 = 12;
+
+/// Returns the channel plan of the given sub-band (0 - 11), sub-band 0 holds channels 0 - 7.
+pub fn channel_plan(sub_band: u8) -> Result<ChannelPlan> {
+    if sub_band >= SUB_BANDS {
+        return Err(anyhow!("Invalid CN470 sub-band: {}", sub_band));
+    }
+
+    Ok(ChannelPlan {
+        multi_sf_channels: super::sub_band_channels(470300000, sub_band),
+        lora_std: None,
+        fsk: None,
+    })
+}
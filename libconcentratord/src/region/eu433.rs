@@ -1 +1,10 @@
+use super::ChannelPlan;
+
 pub const TX_MIN_MAX_FREQS: [(u32, u32); 1] = [(433050000, 434900000)];
+
+/// Default uplink channel plan.
+pub const CHANNEL_PLAN: ChannelPlan = ChannelPlan {
+    multi_sf_channels: [433175000, 433375000, 433575000, 0, 0, 0, 0, 0],
+    lora_std: None,
+    fsk: None,
+};
@@ -1 +1,20 @@
+use super::{ChannelPlan, FskChannel, LoRaStdChannel};
+
 pub const TX_MIN_MAX_FREQS: [(u32, u32); 1] = [(863000000, 870000000)];
+
+/// Default uplink channel plan.
+pub const CHANNEL_PLAN: ChannelPlan = ChannelPlan {
+    multi_sf_channels: [
+        868100000, 868300000, 868500000, 867100000, 867300000, 867500000, 867700000, 867900000,
+    ],
+    lora_std: Some(LoRaStdChannel {
+        frequency: 868300000,
+        bandwidth: 250000,
+        spreading_factor: 7,
+    }),
+    fsk: Some(FskChannel {
+        frequency: 868800000,
+        bandwidth: 125000,
+        datarate: 50000,
+    }),
+};
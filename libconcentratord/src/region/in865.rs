@@ -1 +1,10 @@
+use super::ChannelPlan;
+
 pub const TX_MIN_MAX_FREQS: [(u32, u32); 1] = [(865000000, 867000000)];
+
+/// Default uplink channel plan.
+pub const CHANNEL_PLAN: ChannelPlan = ChannelPlan {
+    multi_sf_channels: [865062500, 865402500, 865985000, 0, 0, 0, 0, 0],
+    lora_std: None,
+    fsk: None,
+};
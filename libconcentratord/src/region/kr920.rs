@@ -1 +1,10 @@
+use super::ChannelPlan;
+
 pub const TX_MIN_MAX_FREQS: [(u32, u32); 1] = [(920900000, 923300000)];
+
+/// Default uplink channel plan.
+pub const CHANNEL_PLAN: ChannelPlan = ChannelPlan {
+    multi_sf_channels: [922100000, 922300000, 922500000, 0, 0, 0, 0, 0],
+    lora_std: None,
+    fsk: None,
+};
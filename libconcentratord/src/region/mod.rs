@@ -11,3 +11,78 @@ pub mod ism2400;
 pub mod kr920;
 pub mod ru864;
 pub mod us915;
+
+// Spacing between the 125 kHz uplink channels of the sub-band regions.
+const SUB_BAND_CHANNEL_SPACING: u32 = 200000;
+
+/// Default uplink channel plan of a region (or of a sub-band of a region).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelPlan {
+    /// Multi spreading-factor (LoRa) channels, 0 for unused channels.
+    pub multi_sf_channels: [u32; 8],
+    /// LoRa std channel (single spreading-factor).
+    pub lora_std: Option<LoRaStdChannel>,
+    /// FSK channel.
+    pub fsk: Option<FskChannel>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoRaStdChannel {
+    pub frequency: u32,
+    pub bandwidth: u32,
+    pub spreading_factor: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FskChannel {
+    pub frequency: u32,
+    pub bandwidth: u32,
+    pub datarate: u32,
+}
+
+// Returns the eight 125 kHz channels of the given sub-band, where first_frequency is the
+// frequency of the first channel of the first sub-band.
+fn sub_band_channels(first_frequency: u32, sub_band: u8) -> [u32; 8] {
+    let mut channels = [0; 8];
+    for (i, c) in channels.iter_mut().enumerate() {
+        *c = first_frequency + (sub_band as u32 * 8 + i as u32) * SUB_BAND_CHANNEL_SPACING;
+    }
+    channels
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sub_band_channel_plans() {
+        assert_eq!(
+            ChannelPlan {
+                multi_sf_channels: [
+                    905500000, 905700000, 905900000, 906100000, 906300000, 906500000, 906700000,
+                    906900000,
+                ],
+                lora_std: Some(LoRaStdChannel {
+                    frequency: 906200000,
+                    bandwidth: 500000,
+                    spreading_factor: 8,
+                }),
+                fsk: None,
+            },
+            us915::channel_plan(2).unwrap()
+        );
+
+        let plan = au915::channel_plan(7).unwrap();
+        assert_eq!(926400000, plan.multi_sf_channels[0]);
+        assert_eq!(927800000, plan.multi_sf_channels[7]);
+        assert_eq!(927100000, plan.lora_std.unwrap().frequency);
+
+        let plan = cn470::channel_plan(11).unwrap();
+        assert_eq!(487900000, plan.multi_sf_channels[0]);
+        assert_eq!(489300000, plan.multi_sf_channels[7]);
+        assert_eq!(None, plan.lora_std);
+
+        assert!(us915::channel_plan(8).is_err());
+        assert!(cn470::channel_plan(12).is_err());
+    }
+}
@@ -1 +1,10 @@
+use super::ChannelPlan;
+
 pub const TX_MIN_MAX_FREQS: [(u32, u32); 1] = [(864000000, 870000000)];
+
+/// Default uplink channel plan.
+pub const CHANNEL_PLAN: ChannelPlan = ChannelPlan {
+    multi_sf_channels: [868900000, 869100000, 0, 0, 0, 0, 0, 0],
+    lora_std: None,
+    fsk: None,
+};
@@ -80,13 +80,38 @@ impl Tracker {
             .sum()
     }
 
+    /// This returns the tracked items.
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    /// This inserts the given item without checking the duty-cycle, e.g. when restoring
+    /// previously tracked items.
+    pub fn insert(&mut self, item: Item) {
+        self.items.push(item);
+    }
+
+    /// This removes the given item, e.g. when the packet will not be sent.
+    pub fn remove(&mut self, item: &Item) {
+        if let Some(i) = self.items.iter().position(|v| v == item) {
+            self.items.remove(i);
+        }
+    }
+
     // Try insert the given item. It returns an error in the following case:
     // - If by inserting the item the max_duration would be exceeded
     // - If by inserting the item, it would make already tracked items exceed
     //   the max_duration.
     pub fn try_insert(&mut self, item: Item) -> Result<(), Error> {
+        self.check(&item)?;
+        self.items.push(item);
+
+        Ok(())
+    }
+
+    /// This checks if the given item can be inserted, see try_insert.
+    pub fn check(&self, item: &Item) -> Result<(), Error> {
         if !self.enforce {
-            self.items.push(item);
             return Ok(());
         }
 
@@ -102,10 +127,19 @@ impl Tracker {
             }
         }
 
-        self.items.push(item);
-
         Ok(())
     }
+
+    /// This returns the first start time after the given start time, at which one of the
+    /// tracked items no longer overlaps with the window of an item with the given duration.
+    /// An item which could not be inserted might be accepted at this start time.
+    pub fn next_release_time(&self, start_time: Duration, duration: Duration) -> Option<Duration> {
+        self.items
+            .iter()
+            .map(|i| (i.end_time + self.window).saturating_sub(duration))
+            .filter(|t| *t > start_time)
+            .min()
+    }
 }
 
 #[cfg(test)]
@@ -570,4 +604,49 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_tracker_next_release_time() {
+        let t = Tracker {
+            enforce: true,
+            window: Duration::from_secs(3600),
+            max_duration: Duration::from_secs(36),
+            items: vec![
+                Item {
+                    start_time: Duration::from_secs(0),
+                    end_time: Duration::from_secs(12),
+                },
+                Item {
+                    start_time: Duration::from_secs(20),
+                    end_time: Duration::from_secs(32),
+                },
+                Item {
+                    start_time: Duration::from_secs(40),
+                    end_time: Duration::from_secs(52),
+                },
+            ],
+        };
+
+        let item = |start_time: u64| Item {
+            start_time: Duration::from_secs(start_time),
+            end_time: Duration::from_secs(start_time + 12),
+        };
+
+        // The item does not fit, until the first item slides out of the window.
+        assert!(t.check(&item(60)).is_err());
+        assert_eq!(
+            Some(Duration::from_secs(3600)),
+            t.next_release_time(Duration::from_secs(60), Duration::from_secs(12))
+        );
+        assert!(t.check(&item(3600)).is_ok());
+
+        assert_eq!(
+            Some(Duration::from_secs(3620)),
+            t.next_release_time(Duration::from_secs(3600), Duration::from_secs(12))
+        );
+        assert_eq!(
+            None,
+            t.next_release_time(Duration::from_secs(3640), Duration::from_secs(12))
+        );
+    }
 }
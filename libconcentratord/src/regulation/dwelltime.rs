@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Dwell-time limit for the given frequency range.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Limit {
+    pub frequency_min: u32,
+    pub frequency_max: u32,
+    // When set to 0, the limit applies to all bandwidths.
+    #[serde(default)]
+    pub bandwidth: u32,
+    #[serde(with = "humantime_serde")]
+    pub dwell_time_max: Duration,
+}
+
+impl Limit {
+    fn matches(&self, tx_freq: u32, bandwidth: u32) -> bool {
+        self.frequency_min <= tx_freq
+            && tx_freq < self.frequency_max
+            && (self.bandwidth == 0 || self.bandwidth == bandwidth)
+    }
+}
+
+pub struct Configuration {
+    limits: Vec<Limit>,
+}
+
+impl Configuration {
+    pub fn new(limits: Vec<Limit>) -> Self {
+        Configuration { limits }
+    }
+
+    /// US915: 400ms for 125 kHz channels.
+    pub fn us915() -> Self {
+        Configuration::new(vec![Limit {
+            frequency_min: 902000000,
+            frequency_max: 928000000,
+            bandwidth: 125000,
+            dwell_time_max: Duration::from_millis(400),
+        }])
+    }
+
+    /// AS923: 400ms for all channels.
+    pub fn as923() -> Self {
+        Configuration::new(vec![Limit {
+            frequency_min: 915000000,
+            frequency_max: 928000000,
+            bandwidth: 0,
+            dwell_time_max: Duration::from_millis(400),
+        }])
+    }
+
+    /// Returns the max. dwell-time for the given frequency and bandwidth. In case multiple
+    /// limits match, the most restrictive limit is returned.
+    pub fn get_dwell_time_max(&self, tx_freq: u32, bandwidth: u32) -> Option<Duration> {
+        self.limits
+            .iter()
+            .filter(|l| l.matches(tx_freq, bandwidth))
+            .map(|l| l.dwell_time_max)
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_dwell_time_max() {
+        let c = Configuration::us915();
+        assert_eq!(
+            Some(Duration::from_millis(400)),
+            c.get_dwell_time_max(902300000, 125000)
+        );
+        assert_eq!(None, c.get_dwell_time_max(923300000, 500000));
+        assert_eq!(None, c.get_dwell_time_max(868100000, 125000));
+
+        let c = Configuration::as923();
+        assert_eq!(
+            Some(Duration::from_millis(400)),
+            c.get_dwell_time_max(923200000, 125000)
+        );
+        assert_eq!(
+            Some(Duration::from_millis(400)),
+            c.get_dwell_time_max(923200000, 250000)
+        );
+
+        let c = Configuration::new(vec![
+            Limit {
+                frequency_min: 902000000,
+                frequency_max: 928000000,
+                bandwidth: 0,
+                dwell_time_max: Duration::from_secs(4),
+            },
+            Limit {
+                frequency_min: 920000000,
+                frequency_max: 921000000,
+                bandwidth: 0,
+                dwell_time_max: Duration::from_millis(200),
+            },
+        ]);
+        assert_eq!(
+            Some(Duration::from_secs(4)),
+            c.get_dwell_time_max(902300000, 125000)
+        );
+        assert_eq!(
+            Some(Duration::from_millis(200)),
+            c.get_dwell_time_max(920500000, 125000)
+        );
+    }
+}
@@ -1,3 +1,4 @@
 pub mod dutycycle;
+pub mod dwelltime;
 pub mod standard;
 pub mod tracker;
@@ -0,0 +1,17 @@
+use std::time::Duration;
+
+use super::{Band, Configuration};
+
+pub fn new() -> Configuration {
+    Configuration {
+        regulation: chirpstack_api::common::Regulation::Unknown,
+        bands: vec![Band {
+            label: "920".into(),
+            frequency_min: 920500000,
+            frequency_max: 928100000,
+            duty_cycle_permille_max: 100,
+            tx_power_max_eirp: 13 + 3,
+        }],
+        window_time: Duration::from_secs(60 * 60),
+    }
+}
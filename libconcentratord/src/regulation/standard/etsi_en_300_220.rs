@@ -6,6 +6,13 @@ pub fn new() -> Configuration {
     Configuration {
         regulation: chirpstack_api::common::Regulation::EtsiEn300220,
         bands: vec![
+            Band {
+                label: "433".into(),
+                frequency_min: 433050000,
+                frequency_max: 434790000,
+                duty_cycle_permille_max: 100,
+                tx_power_max_eirp: 10 + 2,
+            },
             Band {
                 label: "K".into(),
                 frequency_min: 863000000,
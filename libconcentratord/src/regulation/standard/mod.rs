@@ -7,12 +7,33 @@ use serde::{Deserialize, Serialize};
 
 use crate::error::Error;
 
+mod arib_std_t108;
 mod etsi_en_300_220;
+mod scrf_07_20_03_001;
+mod wpc_gsr_1048;
 
 #[allow(non_camel_case_types)]
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Standard {
+    /// Europe (EU868 and EU433).
     ETSI_EN_300_220,
+    /// Russia (RU864).
+    SCRF_07_20_03_001,
+    /// India (IN865).
+    WPC_GSR_1048,
+    /// Japan (AS923).
+    ARIB_STD_T108,
+}
+
+/// Policy for downlinks requesting more EIRP than the band allows.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum TxPowerPolicy {
+    /// Reject the downlink.
+    #[default]
+    Reject,
+    /// Clamp the TX power to the max. EIRP of the band.
+    Clamp,
 }
 
 impl fmt::Display for Standard {
@@ -21,7 +42,7 @@ impl fmt::Display for Standard {
     }
 }
 
-#[derive(Hash, Clone, PartialEq, Eq, Debug)]
+#[derive(Hash, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct Band {
     pub label: String,
     pub frequency_min: u32,
@@ -30,6 +51,26 @@ pub struct Band {
     pub tx_power_max_eirp: i8,
 }
 
+impl Band {
+    pub fn validate(&self) -> Result<()> {
+        if self.frequency_min >= self.frequency_max {
+            return Err(anyhow!(
+                "Band {}: frequency_min must be less than frequency_max",
+                self.label
+            ));
+        }
+
+        if self.duty_cycle_permille_max > 1000 {
+            return Err(anyhow!(
+                "Band {}: duty_cycle_permille_max must not exceed 1000",
+                self.label
+            ));
+        }
+
+        Ok(())
+    }
+}
+
 impl fmt::Display for Band {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -51,9 +92,24 @@ pub struct Configuration {
 
 impl Configuration {
     pub fn new(s: Standard) -> Self {
-        match s {
-            Standard::ETSI_EN_300_220 => etsi_en_300_220::new(),
+        get(s)
+    }
+
+    /// Returns a configuration using the given (custom) band table.
+    pub fn new_custom(bands: Vec<Band>, window_time: Duration) -> Result<Self> {
+        for b in &bands {
+            b.validate()?;
+        }
+
+        if window_time.is_zero() {
+            return Err(anyhow!("window_time must be greater than zero"));
         }
+
+        Ok(Configuration {
+            bands,
+            window_time,
+            regulation: Regulation::Unknown,
+        })
     }
 
     pub fn get_band(&self, tx_freq: u32, tx_power_eirp: i8) -> Result<Band, Error> {
@@ -69,6 +125,15 @@ impl Configuration {
         Err(Error::BandNotFound(tx_freq, tx_power_eirp))
     }
 
+    /// Returns the highest max. EIRP of the bands matching the given frequency.
+    pub fn get_tx_power_max_eirp(&self, tx_freq: u32) -> Option<i8> {
+        self.bands
+            .iter()
+            .filter(|b| b.frequency_min <= tx_freq && tx_freq < b.frequency_max)
+            .map(|b| b.tx_power_max_eirp)
+            .max()
+    }
+
     pub fn get_regulation(&self) -> Regulation {
         self.regulation
     }
@@ -77,6 +142,9 @@ impl Configuration {
 pub fn get(s: Standard) -> Configuration {
     match s {
         Standard::ETSI_EN_300_220 => etsi_en_300_220::new(),
+        Standard::SCRF_07_20_03_001 => scrf_07_20_03_001::new(),
+        Standard::WPC_GSR_1048 => wpc_gsr_1048::new(),
+        Standard::ARIB_STD_T108 => arib_std_t108::new(),
     }
 }
 
@@ -88,28 +156,78 @@ mod test {
     fn test_get_band() {
         struct Test {
             name: String,
+            standard: Standard,
             freq: u32,
             tx_power_eirp: i8,
             expected_band: Option<Band>,
         }
 
-        let tests = vec![Test {
-            name: "M band".into(),
-            freq: 868100000,
-            tx_power_eirp: 16,
-            expected_band: Some(Band {
-                label: "M".into(),
-                frequency_min: 868000000,
-                frequency_max: 868600000,
-                duty_cycle_permille_max: 10,
-                tx_power_max_eirp: 16,
-            }),
-        }];
+        let tests = vec![
+            Test {
+                name: "M band".into(),
+                standard: Standard::ETSI_EN_300_220,
+                freq: 868100000,
+                tx_power_eirp: 16,
+                expected_band: Some(Band {
+                    label: "M".into(),
+                    frequency_min: 868000000,
+                    frequency_max: 868600000,
+                    duty_cycle_permille_max: 10,
+                    tx_power_max_eirp: 16,
+                }),
+            },
+            Test {
+                name: "433 band".into(),
+                standard: Standard::ETSI_EN_300_220,
+                freq: 433175000,
+                tx_power_eirp: 12,
+                expected_band: Some(Band {
+                    label: "433".into(),
+                    frequency_min: 433050000,
+                    frequency_max: 434790000,
+                    duty_cycle_permille_max: 100,
+                    tx_power_max_eirp: 12,
+                }),
+            },
+            Test {
+                name: "RU864 RX2".into(),
+                standard: Standard::SCRF_07_20_03_001,
+                freq: 869100000,
+                tx_power_eirp: 16,
+                expected_band: Some(Band {
+                    label: "868.7".into(),
+                    frequency_min: 868700000,
+                    frequency_max: 869200000,
+                    duty_cycle_permille_max: 1,
+                    tx_power_max_eirp: 16,
+                }),
+            },
+            Test {
+                name: "IN865 RX2".into(),
+                standard: Standard::WPC_GSR_1048,
+                freq: 866550000,
+                tx_power_eirp: 30,
+                expected_band: Some(Band {
+                    label: "865".into(),
+                    frequency_min: 865000000,
+                    frequency_max: 867000000,
+                    duty_cycle_permille_max: 1000,
+                    tx_power_max_eirp: 38,
+                }),
+            },
+            Test {
+                name: "AS923 - tx power exceeds max EIRP".into(),
+                standard: Standard::ARIB_STD_T108,
+                freq: 923200000,
+                tx_power_eirp: 17,
+                expected_band: None,
+            },
+        ];
 
-        let c = Configuration::new(Standard::ETSI_EN_300_220);
         for tst in &tests {
             println!("> {}", tst.name);
 
+            let c = Configuration::new(tst.standard);
             let res = c.get_band(tst.freq, tst.tx_power_eirp);
             if tst.expected_band.is_none() {
                 assert!(res.is_err());
@@ -118,4 +236,52 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_get_tx_power_max_eirp() {
+        let c = Configuration::new(Standard::ETSI_EN_300_220);
+        assert_eq!(Some(16), c.get_tx_power_max_eirp(868100000));
+        assert_eq!(Some(29), c.get_tx_power_max_eirp(869525000));
+        // Overlapping P and Q bands.
+        assert_eq!(Some(16), c.get_tx_power_max_eirp(869800000));
+        assert_eq!(None, c.get_tx_power_max_eirp(868650000));
+    }
+
+    #[test]
+    fn test_new_custom() {
+        let band = Band {
+            label: "A".into(),
+            frequency_min: 902000000,
+            frequency_max: 928000000,
+            duty_cycle_permille_max: 500,
+            tx_power_max_eirp: 30,
+        };
+
+        let c = Configuration::new_custom(vec![band.clone()], Duration::from_secs(60)).unwrap();
+        assert_eq!(Duration::from_secs(60), c.window_time);
+        assert_eq!(Regulation::Unknown, c.get_regulation());
+        assert_eq!(band, c.get_band(915000000, 30).unwrap());
+
+        assert!(Configuration::new_custom(vec![band.clone()], Duration::ZERO).is_err());
+        assert!(
+            Configuration::new_custom(
+                vec![Band {
+                    frequency_max: 902000000,
+                    ..band.clone()
+                }],
+                Duration::from_secs(60)
+            )
+            .is_err()
+        );
+        assert!(
+            Configuration::new_custom(
+                vec![Band {
+                    duty_cycle_permille_max: 1001,
+                    ..band
+                }],
+                Duration::from_secs(60)
+            )
+            .is_err()
+        );
+    }
 }
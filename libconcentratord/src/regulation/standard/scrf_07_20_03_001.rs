@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use super::{Band, Configuration};
+
+pub fn new() -> Configuration {
+    Configuration {
+        regulation: chirpstack_api::common::Regulation::Unknown,
+        bands: vec![
+            Band {
+                label: "864".into(),
+                frequency_min: 864000000,
+                frequency_max: 865000000,
+                duty_cycle_permille_max: 1,
+                tx_power_max_eirp: 14 + 2,
+            },
+            Band {
+                label: "866".into(),
+                frequency_min: 866000000,
+                frequency_max: 868000000,
+                duty_cycle_permille_max: 10,
+                tx_power_max_eirp: 14 + 2,
+            },
+            Band {
+                label: "868.7".into(),
+                frequency_min: 868700000,
+                frequency_max: 869200000,
+                duty_cycle_permille_max: 1,
+                tx_power_max_eirp: 14 + 2,
+            },
+            Band {
+                label: "869.4".into(),
+                frequency_min: 869400000,
+                frequency_max: 869650000,
+                duty_cycle_permille_max: 100,
+                tx_power_max_eirp: 20 + 2,
+            },
+        ],
+        window_time: Duration::from_secs(60 * 60),
+    }
+}
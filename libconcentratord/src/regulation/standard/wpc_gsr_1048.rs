@@ -0,0 +1,17 @@
+use std::time::Duration;
+
+use super::{Band, Configuration};
+
+pub fn new() -> Configuration {
+    Configuration {
+        regulation: chirpstack_api::common::Regulation::Unknown,
+        bands: vec![Band {
+            label: "865".into(),
+            frequency_min: 865000000,
+            frequency_max: 867000000,
+            duty_cycle_permille_max: 1000,
+            tx_power_max_eirp: 36 + 2,
+        }],
+        window_time: Duration::from_secs(60 * 60),
+    }
+}
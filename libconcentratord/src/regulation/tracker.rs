@@ -1,25 +1,66 @@
 use std::collections::HashMap;
-use std::time::Duration;
+use std::fs;
+use std::time::{Duration, SystemTime};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use chirpstack_api::common::Regulation;
-use log::info;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
 
 use super::dutycycle;
 use super::standard;
-use crate::helpers::ToConcentratorCount;
+use crate::error::Error;
+use crate::helpers::{self, ToConcentratorCount};
+
+/// Snapshot of the tracked items, anchored to the wall-clock time (as the concentrator counter
+/// is reset when the concentrator is restarted).
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct State {
+    pub bands: Vec<BandState>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BandState {
+    pub band: standard::Band,
+    pub items: Vec<StateItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct StateItem {
+    pub start_time: SystemTime,
+    pub end_time: SystemTime,
+}
+
+impl State {
+    pub fn load(path: &str) -> Result<State> {
+        let b = fs::read(path).context("Read duty-cycle state file")?;
+        serde_json::from_slice(&b).context("Decode duty-cycle state file")
+    }
+
+    /// Save the state, replacing the state file atomically.
+    pub fn save(&self, path: &str) -> Result<()> {
+        let b = serde_json::to_vec(self)?;
+        helpers::write_atomic(path, &b).context("Write duty-cycle state file")
+    }
+}
 
 pub struct Tracker {
     enforce: bool,
+    tx_power_policy: standard::TxPowerPolicy,
     config: standard::Configuration,
     trackers: HashMap<standard::Band, dutycycle::Tracker>,
 }
 
 impl Tracker {
-    pub fn new(config: standard::Configuration, enforce: bool) -> Self {
+    pub fn new(
+        config: standard::Configuration,
+        enforce: bool,
+        tx_power_policy: standard::TxPowerPolicy,
+    ) -> Self {
         Tracker {
             config,
             enforce,
+            tx_power_policy,
             trackers: HashMap::new(),
         }
     }
@@ -50,6 +91,95 @@ impl Tracker {
         Ok(())
     }
 
+    /// Check if the given item can be tracked, without tracking it (see try_insert).
+    pub fn check(&self, tx_freq: u32, tx_power: i8, item: &dutycycle::Item) -> Result<()> {
+        let band = self.config.get_band(tx_freq, tx_power)?;
+
+        match self.trackers.get(&band) {
+            Some(tracker) => tracker.check(item)?,
+            None => dutycycle::Tracker::new(
+                self.config.window_time,
+                self.config.window_time / 1000 * band.duty_cycle_permille_max,
+                self.enforce,
+            )
+            .check(item)?,
+        }
+
+        Ok(())
+    }
+
+    /// Returns the first start time after the given start time, at which the tracked
+    /// duration of the band decreases for an item with the given duration.
+    pub fn next_release_time(
+        &self,
+        tx_freq: u32,
+        tx_power: i8,
+        start_time: Duration,
+        duration: Duration,
+    ) -> Option<Duration> {
+        let band = self.config.get_band(tx_freq, tx_power).ok()?;
+        self.trackers
+            .get(&band)?
+            .next_release_time(start_time, duration)
+    }
+
+    /// Track the given item without checking the duty-cycle, e.g. to restore a removed item.
+    pub fn insert(&mut self, tx_freq: u32, tx_power: i8, item: dutycycle::Item) {
+        let band = match self.config.get_band(tx_freq, tx_power) {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+
+        self.trackers
+            .entry(band.clone())
+            .or_insert_with(|| {
+                dutycycle::Tracker::new(
+                    self.config.window_time,
+                    self.config.window_time / 1000 * band.duty_cycle_permille_max,
+                    self.enforce,
+                )
+            })
+            .insert(item);
+    }
+
+    /// Remove a previously tracked item, e.g. when the packet was removed from the queue.
+    pub fn remove(&mut self, tx_freq: u32, tx_power: i8, item: &dutycycle::Item) {
+        let band = match self.config.get_band(tx_freq, tx_power) {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+
+        if let Some(tracker) = self.trackers.get_mut(&band) {
+            tracker.remove(item);
+            debug!(
+                "Item removed, band: {}, freq: {}, start_counter_us: {}",
+                band,
+                tx_freq,
+                item.start_time.to_concentrator_count()
+            );
+        }
+    }
+
+    /// Returns the TX power (EIRP) to use for the given frequency and requested TX power,
+    /// according to the TX power policy.
+    pub fn get_tx_power(&self, tx_freq: u32, tx_power: i8) -> Result<i8, Error> {
+        let tx_power_max = self
+            .config
+            .get_tx_power_max_eirp(tx_freq)
+            .ok_or(Error::BandNotFound(tx_freq, tx_power))?;
+
+        if tx_power <= tx_power_max {
+            return Ok(tx_power);
+        }
+
+        match self.tx_power_policy {
+            standard::TxPowerPolicy::Reject => {
+                Err(Error::TxPowerExceeded(tx_freq, tx_power, tx_power_max))
+            }
+            standard::TxPowerPolicy::Clamp => Ok(tx_power_max),
+        }
+    }
+
     pub fn cleanup(&mut self, cur_time: Duration) {
         for v in self.trackers.values_mut() {
             v.cleanup(cur_time);
@@ -73,6 +203,93 @@ impl Tracker {
     pub fn get_regulation(&self) -> Regulation {
         self.config.get_regulation()
     }
+
+    /// Returns the snapshot of the tracked items. The cur_time is the tracker time
+    /// corresponding with the now wall-clock time.
+    pub fn get_state(&self, cur_time: Duration, now: SystemTime) -> State {
+        let to_system_time = |t: Duration| {
+            if t >= cur_time {
+                now + (t - cur_time)
+            } else {
+                now - (cur_time - t)
+            }
+        };
+
+        State {
+            bands: self
+                .trackers
+                .iter()
+                .map(|(band, tracker)| BandState {
+                    band: band.clone(),
+                    items: tracker
+                        .items()
+                        .iter()
+                        .map(|i| StateItem {
+                            start_time: to_system_time(i.start_time),
+                            end_time: to_system_time(i.end_time),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Restore the tracked items from the given snapshot. Items of bands which are not part of
+    /// the current configuration and items that ended more than one window ago are discarded.
+    pub fn restore_state(&mut self, state: State, cur_time: Duration, now: SystemTime) {
+        let to_tracker_time = |t: SystemTime| match t.duration_since(now) {
+            Ok(v) => Some(cur_time + v),
+            Err(e) => cur_time.checked_sub(e.duration()),
+        };
+        let not_before = now
+            .checked_sub(self.config.window_time)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+
+        for band_state in state.bands {
+            if !self.config.bands.contains(&band_state.band) {
+                debug!(
+                    "Discarding duty-cycle state of unknown band, band: {}",
+                    band_state.band
+                );
+                continue;
+            }
+
+            let mut restored = 0;
+            for item in band_state.items {
+                if item.end_time < not_before {
+                    continue;
+                }
+
+                let (start_time, end_time) = match (
+                    to_tracker_time(item.start_time),
+                    to_tracker_time(item.end_time),
+                ) {
+                    (Some(start_time), Some(end_time)) => (start_time, end_time),
+                    _ => continue,
+                };
+
+                self.trackers
+                    .entry(band_state.band.clone())
+                    .or_insert_with(|| {
+                        dutycycle::Tracker::new(
+                            self.config.window_time,
+                            self.config.window_time / 1000 * band_state.band.duty_cycle_permille_max,
+                            self.enforce,
+                        )
+                    })
+                    .insert(dutycycle::Item {
+                        start_time,
+                        end_time,
+                    });
+                restored += 1;
+            }
+
+            info!(
+                "Duty-cycle state restored, band: {}, items: {}",
+                band_state.band, restored
+            );
+        }
+    }
 }
 
 #[cfg(test)]
@@ -187,7 +404,7 @@ mod test {
 
         for tst in &tests {
             let conf = standard::Configuration::new(standard::Standard::ETSI_EN_300_220);
-            let mut tracker = Tracker::new(conf, true);
+            let mut tracker = Tracker::new(conf, true, standard::TxPowerPolicy::Reject);
             for item in &tst.items {
                 assert_eq!(
                     tst.ok,
@@ -207,4 +424,126 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_get_tx_power() {
+        let conf = standard::Configuration::new(standard::Standard::ETSI_EN_300_220);
+        let tracker = Tracker::new(conf, true, standard::TxPowerPolicy::Reject);
+        assert_eq!(14, tracker.get_tx_power(868100000, 14).unwrap());
+        assert!(matches!(
+            tracker.get_tx_power(868100000, 20),
+            Err(Error::TxPowerExceeded(868100000, 20, 16))
+        ));
+        assert!(matches!(
+            tracker.get_tx_power(868650000, 14),
+            Err(Error::BandNotFound(868650000, 14))
+        ));
+
+        let conf = standard::Configuration::new(standard::Standard::ETSI_EN_300_220);
+        let tracker = Tracker::new(conf, true, standard::TxPowerPolicy::Clamp);
+        assert_eq!(14, tracker.get_tx_power(868100000, 14).unwrap());
+        assert_eq!(16, tracker.get_tx_power(868100000, 20).unwrap());
+        assert_eq!(29, tracker.get_tx_power(869525000, 30).unwrap());
+    }
+
+    #[test]
+    fn test_state() {
+        let now = SystemTime::now();
+        let window = Duration::from_secs(60 * 60);
+
+        let conf = standard::Configuration::new(standard::Standard::ETSI_EN_300_220);
+        let mut tracker = Tracker::new(conf, true, standard::TxPowerPolicy::Reject);
+        tracker
+            .try_insert(
+                868100000,
+                16,
+                dutycycle::Item {
+                    start_time: window + Duration::from_secs(10),
+                    end_time: window + Duration::from_secs(30),
+                },
+            )
+            .unwrap();
+
+        let state = tracker.get_state(window + Duration::from_secs(40), now);
+        assert_eq!(1, state.bands.len());
+        assert_eq!(
+            vec![StateItem {
+                start_time: now - Duration::from_secs(30),
+                end_time: now - Duration::from_secs(10),
+            }],
+            state.bands[0].items
+        );
+
+        // Restore 20 seconds later, with a different tracker time.
+        let conf = standard::Configuration::new(standard::Standard::ETSI_EN_300_220);
+        let mut tracker = Tracker::new(conf, true, standard::TxPowerPolicy::Reject);
+        let cur_time = window + Duration::from_secs(5);
+        tracker.restore_state(state, cur_time, now + Duration::from_secs(20));
+
+        let durations = tracker.get_tracked_durations(cur_time);
+        assert_eq!(1, durations.len());
+        assert_eq!(Some(&Duration::from_secs(20)), durations.values().next());
+
+        // The restored items count towards the duty-cycle (M band, 1%).
+        assert!(
+            tracker
+                .try_insert(
+                    868100000,
+                    16,
+                    dutycycle::Item {
+                        start_time: cur_time,
+                        end_time: cur_time + Duration::from_secs(16),
+                    },
+                )
+                .is_ok()
+        );
+        assert!(
+            tracker
+                .try_insert(
+                    868100000,
+                    16,
+                    dutycycle::Item {
+                        start_time: cur_time + Duration::from_secs(20),
+                        end_time: cur_time + Duration::from_secs(20) + Duration::from_millis(1),
+                    },
+                )
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_restore_state_discard() {
+        let now = SystemTime::now();
+        let window = Duration::from_secs(60 * 60);
+        let band = standard::Configuration::new(standard::Standard::ETSI_EN_300_220)
+            .get_band(868100000, 16)
+            .unwrap();
+
+        let state = State {
+            bands: vec![
+                BandState {
+                    band: band.clone(),
+                    items: vec![StateItem {
+                        start_time: now - window - Duration::from_secs(2),
+                        end_time: now - window - Duration::from_secs(1),
+                    }],
+                },
+                BandState {
+                    band: standard::Band {
+                        label: "X".into(),
+                        ..band
+                    },
+                    items: vec![StateItem {
+                        start_time: now - Duration::from_secs(2),
+                        end_time: now - Duration::from_secs(1),
+                    }],
+                },
+            ],
+        };
+
+        let conf = standard::Configuration::new(standard::Standard::ETSI_EN_300_220);
+        let mut tracker = Tracker::new(conf, true, standard::TxPowerPolicy::Reject);
+        tracker.restore_state(state, window, now);
+        assert!(tracker.get_tracked_durations(window).is_empty());
+    }
 }
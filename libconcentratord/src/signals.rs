@@ -1,17 +1,23 @@
 use std::fmt;
 use std::sync::mpsc::{channel, Receiver, Sender};
 
+use crate::error::ConfigurationError;
+
 #[derive(Clone)]
 pub enum Signal {
     Stop,
-    Configuration(chirpstack_api::gw::GatewayConfiguration),
+    // The result of applying the configuration is sent back on the given channel.
+    Configuration(
+        chirpstack_api::gw::GatewayConfiguration,
+        Sender<Result<(), ConfigurationError>>,
+    ),
 }
 
 impl fmt::Display for Signal {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Signal::Stop => write!(f, "Stop"),
-            Signal::Configuration(_) => write!(f, "Configuration"),
+            Signal::Configuration(_, _) => write!(f, "Configuration"),
         }
     }
 }
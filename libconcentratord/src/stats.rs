@@ -1,16 +1,168 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
 use std::sync::{LazyLock, Mutex};
 use std::time::SystemTime;
 
 use anyhow::Result;
 use chirpstack_api::prost_types;
 
-use super::events;
+use super::{api, events, metrics};
+
+// Percentiles included in the signal-quality summary.
+const PERCENTILES: [u32; 3] = [10, 50, 90];
 
 static STATS: LazyLock<Mutex<chirpstack_api::gw::GatewayStats>> =
     LazyLock::new(|| Mutex::new(Default::default()));
 
-pub fn inc_rx_counts(pl: &chirpstack_api::gw::UplinkFrame) {
+// Signal-quality stats, by frequency and data-rate (e.g. sf7, fsk or lr_fhss).
+static SIGNAL_STATS: LazyLock<Mutex<BTreeMap<(u32, String), SignalStats>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+/// Histogram with fixed-width bins.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    bin_width: i32,
+    // Count by bin lower bound.
+    bins: BTreeMap<i32, u32>,
+    count: u32,
+}
+
+impl Histogram {
+    pub fn new(bin_width: i32) -> Self {
+        Histogram {
+            bin_width,
+            bins: BTreeMap::new(),
+            count: 0,
+        }
+    }
+
+    pub fn add(&mut self, v: f32) {
+        let bin = (v / self.bin_width as f32).floor() as i32 * self.bin_width;
+        *self.bins.entry(bin).or_insert(0) += 1;
+        self.count += 1;
+    }
+
+    /// Return the lower bound of the bin containing the p-th percentile.
+    pub fn percentile(&self, p: u32) -> Option<i32> {
+        let target = (self.count as u64 * p as u64).div_ceil(100).max(1);
+        let mut cumulative: u64 = 0;
+
+        for (bin, count) in &self.bins {
+            cumulative += *count as u64;
+            if cumulative >= target {
+                return Some(*bin);
+            }
+        }
+
+        None
+    }
+
+    /// Return the bins, formatted as bin:count pairs (e.g. -120:3,-119:5).
+    pub fn bins_string(&self) -> String {
+        let mut s = String::new();
+        for (bin, count) in &self.bins {
+            if !s.is_empty() {
+                s.push(',');
+            }
+            write!(s, "{}:{}", bin, count).unwrap();
+        }
+        s
+    }
+
+    /// Return the percentiles, formatted as p:value pairs (e.g. p10:-110,p50:-100,p90:-95).
+    pub fn percentiles_string(&self) -> String {
+        let mut s = String::new();
+        for p in PERCENTILES {
+            if let Some(v) = self.percentile(p) {
+                if !s.is_empty() {
+                    s.push(',');
+                }
+                write!(s, "p{}:{}", p, v).unwrap();
+            }
+        }
+        s
+    }
+}
+
+/// Return the estimated noise floor (dBm). The RSSI is the power of the signal plus the noise,
+/// such that noise = RSSI / (1 + SNR) (linear).
+fn noise_floor(rssi: f32, snr: f32) -> f32 {
+    rssi - 10.0 * (1.0 + 10f32.powf(snr / 10.0)).log10()
+}
+
+/// Signal-quality stats of the frames received on a single frequency and data-rate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignalStats {
+    /// RSSI (1 dB bins).
+    pub rssi: Histogram,
+    /// SNR (1 dB bins).
+    pub snr: Histogram,
+    /// Estimated noise floor (1 dB bins), see noise_floor.
+    pub noise_floor: Histogram,
+    /// Frequency offset (100 Hz bins).
+    pub freq_offset: Histogram,
+}
+
+impl Default for SignalStats {
+    fn default() -> Self {
+        SignalStats {
+            rssi: Histogram::new(1),
+            snr: Histogram::new(1),
+            noise_floor: Histogram::new(1),
+            freq_offset: Histogram::new(100),
+        }
+    }
+}
+
+impl SignalStats {
+    fn add(&mut self, rssi: f32, snr: f32, freq_offset: Option<i32>) {
+        self.rssi.add(rssi);
+        self.snr.add(snr);
+        self.noise_floor.add(noise_floor(rssi, snr));
+        if let Some(v) = freq_offset {
+            self.freq_offset.add(v as f32);
+        }
+    }
+
+    // Add the histograms and percentiles to the metadata. Keys are formatted as
+    // <name>/<frequency>/<data-rate>, e.g. rssi_hist/868100000/sf7.
+    fn to_metadata(&self, frequency: u32, dr: &str, metadata: &mut HashMap<String, String>) {
+        for (name, hist) in [
+            ("rssi", &self.rssi),
+            ("snr", &self.snr),
+            ("noise_floor", &self.noise_floor),
+            ("freq_offset", &self.freq_offset),
+        ] {
+            if hist.count == 0 {
+                continue;
+            }
+
+            metadata.insert(
+                format!("{}_hist/{}/{}", name, frequency, dr),
+                hist.bins_string(),
+            );
+            metadata.insert(
+                format!("{}_pct/{}/{}", name, frequency, dr),
+                hist.percentiles_string(),
+            );
+        }
+    }
+}
+
+/// Increment the received packet counters. The frequency offset (Hz) is only used for the
+/// signal-quality stats and is None when not reported by the concentrator.
+pub fn inc_rx_counts(pl: &chirpstack_api::gw::UplinkFrame, freq_offset: Option<i32>) {
+    metrics::inc_rx_counts(pl);
+
+    if let (Some(tx_info), Some(rx_info)) = (&pl.tx_info, &pl.rx_info) {
+        SIGNAL_STATS
+            .lock()
+            .unwrap()
+            .entry((tx_info.frequency, data_rate_name(tx_info)))
+            .or_default()
+            .add(rx_info.rssi as f32, rx_info.snr, freq_offset);
+    }
+
     let mut stats = STATS.lock().unwrap();
     stats.rx_packets_received_ok += 1;
 
@@ -41,6 +193,8 @@ pub fn inc_rx_counts(pl: &chirpstack_api::gw::UplinkFrame) {
 }
 
 pub fn inc_tx_counts(tx_info: &chirpstack_api::gw::DownlinkTxInfo) {
+    metrics::inc_tx_counts(tx_info);
+
     let mut stats = STATS.lock().unwrap();
     stats.tx_packets_emitted += 1;
 
@@ -68,7 +222,9 @@ pub fn inc_tx_counts(tx_info: &chirpstack_api::gw::DownlinkTxInfo) {
     }
 }
 
-pub fn inc_tx_status_count(status: chirpstack_api::gw::TxAckStatus) {
+pub fn inc_tx_status_count(status: api::TxAckStatus) {
+    metrics::inc_tx_status_count(status.as_str_name());
+
     let mut stats = STATS.lock().unwrap();
     stats
         .tx_packets_per_status
@@ -78,11 +234,15 @@ pub fn inc_tx_status_count(status: chirpstack_api::gw::TxAckStatus) {
 }
 
 pub fn inc_rx_packets_received() {
+    metrics::inc_rx_packets_received();
+
     let mut stats = STATS.lock().unwrap();
     stats.rx_packets_received += 1;
 }
 
 pub fn inc_tx_packets_received() {
+    metrics::inc_tx_packets_received();
+
     let mut stats = STATS.lock().unwrap();
     stats.tx_packets_received += 1;
 }
@@ -93,6 +253,8 @@ pub fn send_and_reset(
     duty_cycle_stats: Option<chirpstack_api::gw::DutyCycleStats>,
     metadata: &HashMap<String, String>,
 ) -> Result<()> {
+    metrics::set_duty_cycle_stats(duty_cycle_stats.clone());
+
     let mut stats = STATS.lock().unwrap();
 
     stats.gateway_id = hex::encode(gateway_id);
@@ -101,10 +263,93 @@ pub fn send_and_reset(
     stats.duty_cycle_stats = duty_cycle_stats;
     stats.metadata.clone_from(metadata);
 
+    let mut signal_stats = SIGNAL_STATS.lock().unwrap();
+    for ((frequency, dr), v) in signal_stats.iter() {
+        v.to_metadata(*frequency, dr, &mut stats.metadata);
+    }
+
     events::send_stats(stats.clone()).unwrap();
 
     // reset stats
     *stats = Default::default();
+    signal_stats.clear();
 
     Ok(())
 }
+
+fn data_rate_name(tx_info: &chirpstack_api::gw::UplinkTxInfo) -> String {
+    match tx_info
+        .modulation
+        .as_ref()
+        .and_then(|v| v.parameters.as_ref())
+    {
+        Some(chirpstack_api::gw::modulation::Parameters::Lora(v)) => {
+            format!("sf{}", v.spreading_factor)
+        }
+        Some(chirpstack_api::gw::modulation::Parameters::Fsk(_)) => "fsk".to_string(),
+        Some(chirpstack_api::gw::modulation::Parameters::LrFhss(_)) => "lr_fhss".to_string(),
+        None => "unknown".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_histogram() {
+        let mut h = Histogram::new(1);
+        for v in [
+            -120.0, -119.5, -110.2, -100.0, -100.0, -99.0, -95.0, -95.0, -90.0, -80.0,
+        ] {
+            h.add(v);
+        }
+
+        assert_eq!(
+            "-120:2,-111:1,-100:2,-99:1,-95:2,-90:1,-80:1",
+            h.bins_string()
+        );
+        assert_eq!(Some(-120), h.percentile(10));
+        assert_eq!(Some(-100), h.percentile(50));
+        assert_eq!(Some(-90), h.percentile(90));
+        assert_eq!("p10:-120,p50:-100,p90:-90", h.percentiles_string());
+
+        let h = Histogram::new(100);
+        assert_eq!(None, h.percentile(50));
+        assert_eq!("", h.percentiles_string());
+    }
+
+    #[test]
+    fn test_signal_stats_to_metadata() {
+        let mut s = SignalStats::default();
+        s.add(-100.0, 5.5, None);
+        s.add(-110.0, -2.0, None);
+
+        let mut metadata = HashMap::new();
+        s.to_metadata(868100000, "sf7", &mut metadata);
+
+        assert_eq!(
+            Some(&"-110:1,-100:1".to_string()),
+            metadata.get("rssi_hist/868100000/sf7")
+        );
+        assert_eq!(
+            Some(&"p10:-110,p50:-110,p90:-100".to_string()),
+            metadata.get("rssi_pct/868100000/sf7")
+        );
+        assert_eq!(
+            Some(&"-113:1,-107:1".to_string()),
+            metadata.get("noise_floor_hist/868100000/sf7")
+        );
+        assert!(!metadata.contains_key("freq_offset_hist/868100000/sf7"));
+    }
+
+    #[test]
+    fn test_noise_floor() {
+        // Positive SNR, the noise floor is below the RSSI by a bit more than the SNR.
+        assert!((noise_floor(-100.0, 5.5) - -106.58).abs() < 0.01);
+
+        // Negative SNR, the noise floor is just below the RSSI.
+        assert!((noise_floor(-110.0, -2.0) - -112.12).abs() < 0.01);
+        assert!((noise_floor(-110.0, -20.0) - -110.04).abs() < 0.01);
+    }
+}
@@ -0,0 +1,289 @@
+//! Configuration validation.
+//!
+//! The Validator loads the configuration files the same way the daemons do and collects the
+//! problems found in the configuration. Each problem is reported with the file and line
+//! defining the key, when the key is set in one of the files.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use toml::de::{DeTable, DeValue};
+
+use crate::config_file::{self, File, line_number};
+use crate::error::ConfigFileError;
+
+/// A problem found in the configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub key: String,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => write!(f, "{}:{}: ", file, line)?,
+            (Some(file), None) => write!(f, "{}: ", file)?,
+            _ => {}
+        }
+
+        if self.key.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.key, self.message)
+        }
+    }
+}
+
+pub struct Validator {
+    files: Vec<File>,
+    // Key (e.g. gateway.concentrator.multi_sf_channels) => file index and byte offset.
+    keys: HashMap<String, (usize, usize)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator {
+    /// Read the given configuration files. Read and syntax errors are reported as problems.
+    pub fn new(filenames: &[String]) -> Self {
+        let mut files = Vec::new();
+        let mut diagnostics = Vec::new();
+
+        for name in filenames {
+            match File::read(name) {
+                Ok(f) => files.push(f),
+                Err(ConfigFileError::EnvVar {
+                    file,
+                    line,
+                    message,
+                }) => diagnostics.push(Diagnostic {
+                    file: Some(file),
+                    line: Some(line),
+                    key: "".into(),
+                    message,
+                }),
+                Err(e) => diagnostics.push(Diagnostic {
+                    file: Some(name.clone()),
+                    line: None,
+                    key: "".into(),
+                    message: match e {
+                        ConfigFileError::Read(_, e) => format!("Read file error: {}", e),
+                        _ => e.to_string(),
+                    },
+                }),
+            }
+        }
+
+        Self::from_files(files, diagnostics)
+    }
+
+    fn from_files(files: Vec<File>, diagnostics: Vec<Diagnostic>) -> Self {
+        let mut v = Validator {
+            files,
+            diagnostics,
+            keys: HashMap::new(),
+        };
+
+        for i in 0..v.files.len() {
+            match DeTable::parse(&v.files[i].content) {
+                Ok(table) => index_keys(&mut v.keys, i, "", table.get_ref()),
+                Err(e) => {
+                    let line = e.span().map(|s| line_number(&v.files[i].content, s.start));
+                    v.diagnostics.push(Diagnostic {
+                        file: Some(v.files[i].name.clone()),
+                        line,
+                        key: "".into(),
+                        message: e.message().trim().to_string(),
+                    });
+                }
+            }
+        }
+
+        v
+    }
+
+    /// Decode the configuration from the combined configuration files. This returns None
+    /// when the configuration could not be read or decoded.
+    pub fn parse<T: DeserializeOwned>(&mut self) -> Option<T> {
+        if !self.diagnostics.is_empty() {
+            return None;
+        }
+
+        match config_file::parse(&self.files) {
+            Ok(v) => Some(v),
+            Err(ConfigFileError::Parse {
+                file,
+                line,
+                message,
+            }) => {
+                self.diagnostics.push(Diagnostic {
+                    file,
+                    line,
+                    key: "".into(),
+                    message,
+                });
+                None
+            }
+            Err(e) => {
+                self.diagnostics.push(Diagnostic {
+                    file: None,
+                    line: None,
+                    key: "".into(),
+                    message: e.to_string(),
+                });
+                None
+            }
+        }
+    }
+
+    /// Report a problem for the given key. When the key is not set in the configuration
+    /// files, the closest parent key is used to find the file.
+    pub fn error<E: fmt::Display>(&mut self, key: &str, message: E) {
+        let mut lookup = key;
+        let location = loop {
+            if let Some((i, offset)) = self.keys.get(lookup) {
+                break Some((
+                    self.files[*i].name.clone(),
+                    line_number(&self.files[*i].content, *offset),
+                ));
+            }
+            match lookup.rfind('.') {
+                Some(n) => lookup = &lookup[..n],
+                None => break None,
+            }
+        };
+
+        self.diagnostics.push(Diagnostic {
+            file: location.as_ref().map(|v| v.0.clone()),
+            line: location.map(|v| v.1),
+            key: key.to_string(),
+            message: message.to_string(),
+        });
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Print the problems and return the process exit code.
+    pub fn report(&self) -> i32 {
+        if self.diagnostics.is_empty() {
+            println!("Configuration is valid");
+            return 0;
+        }
+
+        for d in &self.diagnostics {
+            println!("{}", d);
+        }
+        println!("Found {} problem(s)", self.diagnostics.len());
+        1
+    }
+}
+
+fn index_keys(keys: &mut HashMap<String, (usize, usize)>, file: usize, prefix: &str, t: &DeTable) {
+    for (k, v) in t.iter() {
+        let key = if prefix.is_empty() {
+            k.get_ref().to_string()
+        } else {
+            format!("{}.{}", prefix, k.get_ref())
+        };
+
+        keys.insert(key.clone(), (file, k.span().start));
+        if let DeValue::Table(t) = v.get_ref() {
+            index_keys(keys, file, &key, t);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Config {
+        gateway: Gateway,
+    }
+
+    #[derive(Deserialize)]
+    struct Gateway {
+        model: String,
+        #[serde(default)]
+        concentrator: Concentrator,
+    }
+
+    #[derive(Deserialize, Default)]
+    struct Concentrator {
+        multi_sf_channels: Vec<u32>,
+    }
+
+    fn validator(files: &[(&str, &str)]) -> Validator {
+        Validator::from_files(
+            files
+                .iter()
+                .map(|(name, content)| File {
+                    name: name.to_string(),
+                    content: content.to_string(),
+                })
+                .collect(),
+            vec![],
+        )
+    }
+
+    #[test]
+    fn test_error_location() {
+        let mut v = validator(&[
+            ("concentratord.toml", "[gateway]\n  model=\"rak_2287\"\n"),
+            (
+                "channels.toml",
+                "\n[gateway.concentrator]\n  multi_sf_channels=[868100000]\n",
+            ),
+        ]);
+
+        let config: Config = v.parse().unwrap();
+        assert_eq!("rak_2287", config.gateway.model);
+        assert_eq!(
+            vec![868100000],
+            config.gateway.concentrator.multi_sf_channels
+        );
+
+        v.error("gateway.model", "Unexpected gateway model");
+        v.error("gateway.concentrator.multi_sf_channels", "Invalid channel");
+        v.error("gateway.concentrator.lora_std.frequency", "Invalid channel");
+        v.error("concentratord.log_level", "Invalid log level");
+
+        assert_eq!(
+            vec![
+                "concentratord.toml:2: gateway.model: Unexpected gateway model",
+                "channels.toml:3: gateway.concentrator.multi_sf_channels: Invalid channel",
+                "channels.toml:2: gateway.concentrator.lora_std.frequency: Invalid channel",
+                "concentratord.log_level: Invalid log level",
+            ],
+            v.diagnostics()
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<String>>()
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let mut v = validator(&[
+            ("concentratord.toml", "[gateway]\n  model=\"rak_2287\"\n"),
+            (
+                "channels.toml",
+                "[gateway.concentrator]\n  multi_sf_channels=868100000\n",
+            ),
+        ]);
+        assert!(v.parse::<Config>().is_none());
+        assert_eq!(Some("channels.toml".to_string()), v.diagnostics()[0].file);
+        assert_eq!(Some(2), v.diagnostics()[0].line);
+
+        let mut v = validator(&[("concentratord.toml", "[gateway]\n  model=\n")]);
+        assert!(v.parse::<Config>().is_none());
+        assert_eq!(Some(2), v.diagnostics()[0].line);
+    }
+}
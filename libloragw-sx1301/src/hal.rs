@@ -6,6 +6,13 @@ use anyhow::Result;
 
 use super::{mutex, wrapper};
 
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// The packet was not sent because the LBT channel was busy.
+    #[error("TX not allowed by LBT")]
+    LbtNotAllowed,
+}
+
 // ConvertBandwidth is a trait to convert the bandwidth from / to the HAL
 // enum constants. A trait is needed as it is the only way to add methods to
 // a type alias.
@@ -640,6 +647,9 @@ pub fn send(pkt: &TxPacket) -> Result<()> {
     let pkt = pkt.to_hal();
 
     let ret = unsafe { wrapper::lgw_send(pkt) };
+    if ret == wrapper::LGW_LBT_ISSUE as i32 {
+        return Err(Error::LbtNotAllowed.into());
+    }
     if ret != 0 {
         return Err(anyhow!("lgw_send failed"));
     }
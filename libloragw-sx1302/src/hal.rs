@@ -7,6 +7,13 @@ use anyhow::Result;
 
 use super::{mutex, wrapper};
 
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// The packet was not sent because the LBT channel was busy.
+    #[error("TX not allowed by LBT")]
+    LbtNotAllowed,
+}
+
 // ConvertBandwidth is a trait to convert the bandwidth from / to the HAL
 // enum constants. A trait is needed as it is the only way to add methods to
 // a type alias.
@@ -790,6 +797,7 @@ impl TryFrom<&SX1261Config> for wrapper::lgw_conf_sx1261_s {
 }
 
 /// Spectral scan result.
+#[derive(Debug, Clone)]
 pub struct SpectralScanResult {
     /// dBm level.
     pub dbm_level: i16,
@@ -798,6 +806,36 @@ pub struct SpectralScanResult {
     pub result: u16,
 }
 
+/// Spectral scan status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpectralScanStatus {
+    None,
+    OnGoing,
+    Aborted,
+    Completed,
+    Unknown,
+}
+
+impl SpectralScanStatus {
+    fn from_hal(status: wrapper::lgw_spectral_scan_status_t) -> Self {
+        match status {
+            wrapper::lgw_spectral_scan_status_e_LGW_SPECTRAL_SCAN_STATUS_NONE => {
+                SpectralScanStatus::None
+            }
+            wrapper::lgw_spectral_scan_status_e_LGW_SPECTRAL_SCAN_STATUS_ON_GOING => {
+                SpectralScanStatus::OnGoing
+            }
+            wrapper::lgw_spectral_scan_status_e_LGW_SPECTRAL_SCAN_STATUS_ABORTED => {
+                SpectralScanStatus::Aborted
+            }
+            wrapper::lgw_spectral_scan_status_e_LGW_SPECTRAL_SCAN_STATUS_COMPLETED => {
+                SpectralScanStatus::Completed
+            }
+            _ => SpectralScanStatus::Unknown,
+        }
+    }
+}
+
 const MAX_PKT: usize = 8;
 
 /// Set I2C device path.
@@ -988,6 +1026,9 @@ pub fn send(pkt: &TxPacket) -> Result<()> {
     let _guard = mutex::CONCENTATOR.lock().unwrap();
     let mut pkt = pkt.to_hal();
     let ret = unsafe { wrapper::lgw_send(&mut pkt) };
+    if ret == wrapper::LGW_LBT_NOT_ALLOWED as i32 {
+        return Err(Error::LbtNotAllowed.into());
+    }
     if ret != 0 {
         return Err(anyhow!("lgw_send failed"));
     }
@@ -1092,6 +1133,30 @@ pub fn spectral_scan_start(freq_hz: u32, nb_scan: u16) -> Result<()> {
     Ok(())
 }
 
+/// Get the status of the spectral scan.
+pub fn spectral_scan_get_status() -> Result<SpectralScanStatus> {
+    let _guard = mutex::CONCENTATOR.lock().unwrap();
+    let mut status: wrapper::lgw_spectral_scan_status_t = Default::default();
+
+    let ret = unsafe { wrapper::lgw_spectral_scan_get_status(&mut status) };
+    if ret != 0 {
+        return Err(anyhow!("lgw_spectral_scan_get_status failed"));
+    }
+
+    Ok(SpectralScanStatus::from_hal(status))
+}
+
+/// Abort the spectral scan.
+pub fn spectral_scan_abort() -> Result<()> {
+    let _guard = mutex::CONCENTATOR.lock().unwrap();
+    let ret = unsafe { wrapper::lgw_spectral_scan_abort() };
+    if ret != 0 {
+        return Err(anyhow!("lgw_spectral_scan_abort failed"));
+    }
+
+    Ok(())
+}
+
 /// Get the channel scan results.
 pub fn spectral_scan_get_results() -> Result<Vec<SpectralScanResult>> {
     let _guard = mutex::CONCENTATOR.lock().unwrap();